@@ -0,0 +1,634 @@
+use bio::io::fasta::FastaRead;
+use bio::io::fasta::Reader as faReader;
+use bio::io::fasta::Record as faRecord;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// blastn の `-outfmt 6` に渡すカラム。`BlastHit` はこの順で読み書きする。
+pub const BLAST_OUTFMT_FIELDS: &str = "qseqid sseqid sacc qlen qstart qend slen sstart send qseq sseq evalue length staxid staxids ssciname scomname";
+pub const DEFAULT_BLASTN_PATH: &str = "/home/harazono/miniconda3/bin/blastn";
+pub const DEFAULT_BLASTDBCMD_PATH: &str = "blastdbcmd";
+
+// blastn-short (reward 1, penalty -3) の ungapped Karlin-Altschul パラメータ
+const KARLIN_LAMBDA: f64 = 1.374;
+const KARLIN_K: f64 = 0.711;
+
+/// One line of the tabular BLAST output, in `BLAST_OUTFMT_FIELDS` order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlastHit {
+    pub qseqid: String,
+    pub sseqid: String,
+    pub sacc: String,
+    pub qlen: usize,
+    pub qstart: usize,
+    pub qend: usize,
+    pub slen: usize,
+    pub sstart: usize,
+    pub send: usize,
+    pub qseq: String,
+    pub sseq: String,
+    pub evalue: f64,
+    pub length: usize,
+    pub staxid: String,
+    pub staxids: String,
+    pub ssciname: String,
+    pub scomname: String,
+}
+
+impl BlastHit {
+    pub fn from_tsv_line(line: &str) -> Result<BlastHit, String> {
+        let fields: Vec<&str> = line.trim_end_matches(['\r', '\n']).split('\t').collect();
+        if fields.len() != 17 {
+            return Err(format!(
+                "expected 17 columns ({}), found {}: {:?}",
+                BLAST_OUTFMT_FIELDS,
+                fields.len(),
+                line
+            ));
+        }
+        let number = |idx: usize| -> Result<usize, String> {
+            fields[idx]
+                .parse::<usize>()
+                .map_err(|e| format!("column {} ({:?}): {}", idx + 1, fields[idx], e))
+        };
+        Ok(BlastHit {
+            qseqid: fields[0].to_string(),
+            sseqid: fields[1].to_string(),
+            sacc: fields[2].to_string(),
+            qlen: number(3)?,
+            qstart: number(4)?,
+            qend: number(5)?,
+            slen: number(6)?,
+            sstart: number(7)?,
+            send: number(8)?,
+            qseq: fields[9].to_string(),
+            sseq: fields[10].to_string(),
+            evalue: fields[11]
+                .parse::<f64>()
+                .map_err(|e| format!("column 12 ({:?}): {}", fields[11], e))?,
+            length: number(12)?,
+            staxid: fields[13].to_string(),
+            staxids: fields[14].to_string(),
+            ssciname: fields[15].to_string(),
+            scomname: fields[16].to_string(),
+        })
+    }
+
+    pub fn to_tsv_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.2e}\t{}\t{}\t{}\t{}\t{}",
+            self.qseqid,
+            self.sseqid,
+            self.sacc,
+            self.qlen,
+            self.qstart,
+            self.qend,
+            self.slen,
+            self.sstart,
+            self.send,
+            self.qseq,
+            self.sseq,
+            self.evalue,
+            self.length,
+            self.staxid,
+            self.staxids,
+            self.ssciname,
+            self.scomname
+        )
+    }
+}
+
+/// Backend that searches a query FASTA against one database and reports
+/// BLAST-compatible hits. `run_blast_search` only talks to this trait.
+pub trait Aligner: Send + Sync {
+    /// Short description of the backend and its parameters, used in logs.
+    fn name(&self) -> String;
+    /// Taxonomy IDs present in `db`.
+    fn db_taxids(&self, db: &str) -> Result<HashSet<String>, String>;
+    /// Search every record of `query_fasta` against `db`, ignoring subjects
+    /// whose taxid is listed in `negative_taxids`. Each hit is handed to
+    /// `emit` as soon as it is found; an error from `emit` stops the search.
+    fn search(
+        &self,
+        query_fasta: &str,
+        db: &str,
+        negative_taxids: &[String],
+        emit: &mut dyn FnMut(BlastHit) -> Result<(), String>,
+    ) -> Result<(), String>;
+}
+
+/// BLAST+ driver (`blastn -task blastn-short` + `blastdbcmd`).
+pub struct BlastnAligner {
+    pub blastn_path: String,
+    pub blastdbcmd_path: String,
+    pub num_threads: usize,
+    pub word_size: usize,
+    pub perc_identity: f64,
+}
+
+impl BlastnAligner {
+    pub fn new(num_threads: usize) -> BlastnAligner {
+        BlastnAligner {
+            blastn_path: DEFAULT_BLASTN_PATH.to_string(),
+            blastdbcmd_path: DEFAULT_BLASTDBCMD_PATH.to_string(),
+            num_threads,
+            word_size: 15,
+            perc_identity: 100.0,
+        }
+    }
+
+    pub fn blastn_args(&self, query_fasta: &str, db: &str, negative_taxids: &[String]) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "-task".to_string(),
+            "blastn-short".to_string(),
+            "-query".to_string(),
+            query_fasta.to_string(),
+            "-db".to_string(),
+            db.to_string(),
+            "-outfmt".to_string(),
+            format!("6 {}", BLAST_OUTFMT_FIELDS),
+            "-num_threads".to_string(),
+            self.num_threads.to_string(),
+            "-dust".to_string(),
+            "no".to_string(),
+            "-soft_masking".to_string(),
+            "false".to_string(),
+            "-word_size".to_string(),
+            self.word_size.to_string(),
+            "-best_hit_overhang".to_string(),
+            "0.01".to_string(),
+            "-best_hit_score_edge".to_string(),
+            "0.49".to_string(),
+            "-perc_identity".to_string(),
+            self.perc_identity.to_string(),
+        ];
+        if !negative_taxids.is_empty() {
+            args.push("-negative_taxids".to_string());
+            args.push(negative_taxids.join(","));
+        }
+        args
+    }
+}
+
+impl Aligner for BlastnAligner {
+    fn name(&self) -> String {
+        format!(
+            "blastn({}) word_size={} perc_identity={}",
+            self.blastn_path, self.word_size, self.perc_identity
+        )
+    }
+
+    fn db_taxids(&self, db: &str) -> Result<HashSet<String>, String> {
+        let output = Command::new(&self.blastdbcmd_path)
+            .args(["-db", db, "-entry", "all", "-outfmt", "%T"])
+            .output()
+            .map_err(|e| format!("failed to execute {}: {}", self.blastdbcmd_path, e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "blastdbcmd failed on {}: {}",
+                db,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(stdout.lines().map(|s| s.trim().to_string()).collect())
+    }
+
+    fn search(
+        &self,
+        query_fasta: &str,
+        db: &str,
+        negative_taxids: &[String],
+        emit: &mut dyn FnMut(BlastHit) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let mut child = Command::new(&self.blastn_path)
+            .args(self.blastn_args(query_fasta, db, negative_taxids))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to execute {}: {}", self.blastn_path, e))?;
+        // stdoutを読んでいる間にstderrのパイプが詰まらないよう、別スレッドで読み切る
+        let mut stderr = child.stderr.take().unwrap();
+        let stderr_reader = thread::spawn(move || {
+            let mut message = String::new();
+            let _ = stderr.read_to_string(&mut message);
+            message
+        });
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut streamed: Result<(), String> = Ok(());
+        for line in stdout.lines() {
+            streamed = line
+                .map_err(|e| format!("failed to read blastn output on {}: {}", db, e))
+                .and_then(|line| {
+                    if line.trim().is_empty() {
+                        Ok(())
+                    } else {
+                        emit(BlastHit::from_tsv_line(&line)?)
+                    }
+                });
+            if streamed.is_err() {
+                let _ = child.kill();
+                break;
+            }
+        }
+        let status = child
+            .wait()
+            .map_err(|e| format!("failed to wait for {}: {}", self.blastn_path, e))?;
+        let message = stderr_reader.join().unwrap_or_default();
+        streamed?;
+        if !status.success() {
+            return Err(format!("blastn failed on {}: {}", db, message));
+        }
+        Ok(())
+    }
+}
+
+/// Taxonomy annotation of one subject sequence of a local database.
+#[derive(Clone, Debug, Default)]
+pub struct TaxonEntry {
+    pub taxid: String,
+    pub sciname: String,
+    pub comname: String,
+}
+
+/// Built-in aligner over a plain FASTA "database". Reports every maximal
+/// exact match of at least `word_size` bases on both strands, which is what
+/// `blastn-short -perc_identity 100` returns for primer-sized queries.
+///
+/// Taxids come from a TSV mapping file: `seqid<TAB>taxid[<TAB>sciname[<TAB>comname]]`.
+pub struct LocalAligner {
+    pub word_size: usize,
+    taxon_map: HashMap<String, TaxonEntry>,
+    taxon_map_digest: String,
+}
+
+struct Subject {
+    id: String,
+    seq: Vec<u8>,
+    taxon: TaxonEntry,
+}
+
+impl LocalAligner {
+    pub fn new(word_size: usize, taxon_map: HashMap<String, TaxonEntry>) -> LocalAligner {
+        assert!(word_size > 0, "LocalAligner::new: word_size must be positive");
+        // 対応表が変わればキャッシュ済みの結果のtaxidも変わるので、nameに中身のダイジェストを含める
+        let mut entries: Vec<(&String, &TaxonEntry)> = taxon_map.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        let mut hasher = Sha256::new();
+        for (seqid, taxon) in entries {
+            hasher.update(format!("{}\t{}\t{}\t{}\n", seqid, taxon.taxid, taxon.sciname, taxon.comname).as_bytes());
+        }
+        let taxon_map_digest = format!("{:x}", hasher.finalize())[..16].to_string();
+        LocalAligner {
+            word_size,
+            taxon_map,
+            taxon_map_digest,
+        }
+    }
+
+    pub fn from_taxid_map_file(word_size: usize, taxid_map_file: &str) -> Result<LocalAligner, String> {
+        let f = File::open(taxid_map_file)
+            .map_err(|e| format!("failed to open {}: {}", taxid_map_file, e))?;
+        let mut taxon_map: HashMap<String, TaxonEntry> = HashMap::new();
+        for (line_no, line) in BufReader::new(f).lines().enumerate() {
+            let line = line.map_err(|e| format!("{}: {}", taxid_map_file, e))?;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let columns: Vec<&str> = line.split('\t').map(|c| c.trim()).collect();
+            if columns.len() < 2 {
+                return Err(format!(
+                    "{}:{}: expected `seqid<TAB>taxid`, found {:?}",
+                    taxid_map_file,
+                    line_no + 1,
+                    line
+                ));
+            }
+            taxon_map.insert(
+                columns[0].to_string(),
+                TaxonEntry {
+                    taxid: columns[1].to_string(),
+                    sciname: columns.get(2).unwrap_or(&"N/A").to_string(),
+                    comname: columns.get(3).unwrap_or(&"N/A").to_string(),
+                },
+            );
+        }
+        Ok(LocalAligner::new(word_size, taxon_map))
+    }
+
+    fn taxon_of(&self, seqid: &str) -> TaxonEntry {
+        self.taxon_map.get(seqid).cloned().unwrap_or(TaxonEntry {
+            taxid: "0".to_string(),
+            sciname: "N/A".to_string(),
+            comname: "N/A".to_string(),
+        })
+    }
+
+    fn load_subjects(&self, db: &str) -> Result<Vec<Subject>, String> {
+        Ok(read_fasta(db)?
+            .into_iter()
+            .map(|(id, seq)| {
+                let taxon = self.taxon_of(&id);
+                Subject { id, seq, taxon }
+            })
+            .collect())
+    }
+
+    /// Maximal exact matches between `query` and `subject` seeded by
+    /// `word_size`-mers. Returns `(query_start, subject_start, length)`, 0-based.
+    fn exact_matches(&self, query: &[u8], subject: &[u8]) -> Vec<(usize, usize, usize)> {
+        let k = self.word_size;
+        let mut retval: Vec<(usize, usize, usize)> = Vec::new();
+        if query.len() < k || subject.len() < k {
+            return retval;
+        }
+        let mut seeds: HashMap<&[u8], Vec<usize>> = HashMap::new();
+        for i in 0..=query.len() - k {
+            seeds.entry(&query[i..i + k]).or_default().push(i);
+        }
+        for j in 0..=subject.len() - k {
+            let Some(positions) = seeds.get(&subject[j..j + k]) else {
+                continue;
+            };
+            for &i in positions {
+                // 同じ対角線上で左に伸ばせるシードは既に報告済み
+                if i > 0 && j > 0 && query[i - 1] == subject[j - 1] {
+                    continue;
+                }
+                let mut length = k;
+                while i + length < query.len()
+                    && j + length < subject.len()
+                    && query[i + length] == subject[j + length]
+                {
+                    length += 1;
+                }
+                retval.push((i, j, length));
+            }
+        }
+        retval
+    }
+}
+
+impl Aligner for LocalAligner {
+    fn name(&self) -> String {
+        format!("local word_size={} taxid_map={}", self.word_size, self.taxon_map_digest)
+    }
+
+    fn db_taxids(&self, db: &str) -> Result<HashSet<String>, String> {
+        Ok(self
+            .load_subjects(db)?
+            .into_iter()
+            .map(|s| s.taxon.taxid)
+            .collect())
+    }
+
+    fn search(
+        &self,
+        query_fasta: &str,
+        db: &str,
+        negative_taxids: &[String],
+        emit: &mut dyn FnMut(BlastHit) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let queries: Vec<(String, Vec<u8>)> = read_fasta(query_fasta)?;
+        let subjects: Vec<Subject> = self
+            .load_subjects(db)?
+            .into_iter()
+            .filter(|s| !negative_taxids.contains(&s.taxon.taxid))
+            .collect();
+        let db_length: usize = subjects.iter().map(|s| s.seq.len()).sum();
+        for (qseqid, query) in &queries {
+            let query_revcomp: Vec<u8> = reverse_complement(query);
+            let qlen = query.len();
+            for subject in &subjects {
+                let slen = subject.seq.len();
+                let mut push_hit = |qstart: usize, qend: usize, sstart: usize, send: usize, length: usize, qseq: &[u8]| {
+                    emit(BlastHit {
+                        qseqid: qseqid.clone(),
+                        sseqid: subject.id.clone(),
+                        sacc: subject.id.clone(),
+                        qlen,
+                        qstart,
+                        qend,
+                        slen,
+                        sstart,
+                        send,
+                        qseq: String::from_utf8_lossy(qseq).to_string(),
+                        sseq: String::from_utf8_lossy(qseq).to_string(),
+                        evalue: KARLIN_K
+                            * qlen as f64
+                            * db_length as f64
+                            * (-KARLIN_LAMBDA * length as f64).exp(),
+                        length,
+                        staxid: subject.taxon.taxid.clone(),
+                        staxids: subject.taxon.taxid.clone(),
+                        ssciname: subject.taxon.sciname.clone(),
+                        scomname: subject.taxon.comname.clone(),
+                    })
+                };
+                for (i, j, length) in self.exact_matches(query, &subject.seq) {
+                    push_hit(i + 1, i + length, j + 1, j + length, length, &query[i..i + length])?;
+                }
+                // マイナス鎖: 座標はBLASTに合わせてqueryは昇順、subjectは降順
+                for (i, j, length) in self.exact_matches(&query_revcomp, &subject.seq) {
+                    let qstart = qlen - (i + length) + 1;
+                    let qend = qlen - i;
+                    push_hit(qstart, qend, j + length, j + 1, length, &query[qstart - 1..qend])?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_fasta(path: &str) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let file = File::open(path).map_err(|e| format!("failed to open {}: {}", path, e))?;
+    let mut reader = faReader::new(file);
+    let mut record = faRecord::new();
+    let mut retval: Vec<(String, Vec<u8>)> = Vec::new();
+    loop {
+        reader
+            .read(&mut record)
+            .map_err(|e| format!("failed to read {}: {}", path, e))?;
+        if record.is_empty() {
+            break;
+        }
+        retval.push((record.id().to_string(), record.seq().to_ascii_uppercase()));
+    }
+    Ok(retval)
+}
+
+fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    seq.iter()
+        .rev()
+        .map(|b| match b {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            b'T' => b'A',
+            other => *other,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aligner_util::{Aligner, BlastHit, BlastnAligner, LocalAligner, TaxonEntry};
+    use ::function_name::named;
+    use std::collections::HashMap;
+    use std::io::Write;
+
+    fn write_fasta(records: &[(&str, &str)]) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        for (id, seq) in records {
+            writeln!(f, ">{}\n{}", id, seq).unwrap();
+        }
+        f.flush().unwrap();
+        f
+    }
+
+    fn search_all(aligner: &dyn Aligner, query: &str, db: &str, negative_taxids: &[String]) -> Result<Vec<BlastHit>, String> {
+        let mut hits: Vec<BlastHit> = Vec::new();
+        aligner.search(query, db, negative_taxids, &mut |hit| {
+            hits.push(hit);
+            Ok(())
+        })?;
+        Ok(hits)
+    }
+
+    fn taxon_map() -> HashMap<String, TaxonEntry> {
+        let mut m = HashMap::new();
+        for (id, taxid) in [("subject_1", "562"), ("subject_2", "9606")] {
+            m.insert(
+                id.to_string(),
+                TaxonEntry {
+                    taxid: taxid.to_string(),
+                    sciname: "N/A".to_string(),
+                    comname: "N/A".to_string(),
+                },
+            );
+        }
+        m
+    }
+
+    #[test]
+    #[named]
+    fn local_aligner_plus_and_minus_strand() {
+        // queryはsubject_1の6..26にそのまま、subject_2の逆相補鎖に含まれる
+        let query = write_fasta(&[("q1", "ACGTTGCAAGGCTTACCGTA")]);
+        let db = write_fasta(&[
+            ("subject_1", "TTTTTTACGTTGCAAGGCTTACCGTATTTTT"),
+            ("subject_2", "GGGGTACGGTAAGCCTTGCAACGTGGGG"),
+        ]);
+        let aligner = LocalAligner::new(15, taxon_map());
+        let hits: Vec<BlastHit> =
+            search_all(&aligner, query.path().to_str().unwrap(), db.path().to_str().unwrap(), &[]).unwrap();
+        assert!(hits.len() == 2, "{} failed: {:?}", function_name!(), hits);
+        let plus = &hits[0];
+        assert!(
+            plus.sseqid == "subject_1" && (plus.qstart, plus.qend, plus.sstart, plus.send) == (1, 20, 7, 26),
+            "{} failed: {:?}",
+            function_name!(),
+            plus
+        );
+        let minus = &hits[1];
+        assert!(
+            minus.sseqid == "subject_2" && (minus.qstart, minus.qend, minus.sstart, minus.send) == (1, 20, 24, 5),
+            "{} failed: {:?}",
+            function_name!(),
+            minus
+        );
+        assert!(minus.staxid == "9606", "{} failed", function_name!());
+    }
+
+    #[test]
+    #[named]
+    fn local_aligner_name_covers_taxid_map() {
+        let mut changed = taxon_map();
+        changed.get_mut("subject_1").unwrap().taxid = "561".to_string();
+        let names = [
+            LocalAligner::new(15, taxon_map()).name(),
+            LocalAligner::new(15, taxon_map()).name(),
+            LocalAligner::new(15, changed).name(),
+        ];
+        assert!(
+            names[0] == names[1] && names[0] != names[2],
+            "{} failed: {:?}",
+            function_name!(),
+            names
+        );
+    }
+
+    #[test]
+    #[named]
+    fn local_aligner_negative_taxids() {
+        let query = write_fasta(&[("q1", "ACGTTGCAAGGCTTACCGTA")]);
+        let db = write_fasta(&[("subject_1", "TTTTTTACGTTGCAAGGCTTACCGTATTTTT")]);
+        let aligner = LocalAligner::new(15, taxon_map());
+        let hits = search_all(
+            &aligner,
+            query.path().to_str().unwrap(),
+            db.path().to_str().unwrap(),
+            &["562".to_string()],
+        )
+        .unwrap();
+        assert!(hits.is_empty(), "{} failed", function_name!());
+    }
+
+    #[test]
+    #[named]
+    fn blast_hit_tsv_round_trip() {
+        let line = "q1\tsubject_1\tsubject_1\t20\t1\t20\t31\t7\t26\tACGT\tACGT\t1.20e-5\t20\t562\t562\tN/A\tN/A";
+        let hit = BlastHit::from_tsv_line(line).unwrap();
+        assert!(
+            BlastHit::from_tsv_line(&hit.to_tsv_line()).unwrap() == hit,
+            "{} failed",
+            function_name!()
+        );
+        assert!(BlastHit::from_tsv_line("q1\tsubject_1").is_err(), "{} failed", function_name!());
+    }
+
+    #[test]
+    #[named]
+    fn blastn_aligner_streams_hits() {
+        // blastnの代わりに2行のヒットを出してから失敗するスクリプト
+        let line = "q1\tsubject_1\tsubject_1\t20\t1\t20\t31\t7\t26\tACGT\tACGT\t1.20e-5\t20\t562\t562\tN/A\tN/A";
+        let mut script = tempfile::Builder::new().suffix(".sh").tempfile().unwrap();
+        writeln!(script, "#!/bin/sh\nprintf '%s\\n%s\\n' '{}' '{}'\necho broken db >&2\nexit 2", line, line).unwrap();
+        script.flush().unwrap();
+        let script_path = script.into_temp_path();
+        std::fs::set_permissions(&script_path, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+        let mut aligner = BlastnAligner::new(1);
+        aligner.blastn_path = script_path.to_str().unwrap().to_string();
+
+        let mut streamed: usize = 0;
+        let result = aligner.search("q.fa", "db", &[], &mut |hit| {
+            assert!(hit.sseqid == "subject_1", "{} failed: {:?}", function_name!(), hit);
+            streamed += 1;
+            Ok(())
+        });
+        assert!(
+            streamed == 2 && result.as_ref().is_err_and(|e| e.contains("broken db")),
+            "{} failed: {} {:?}",
+            function_name!(),
+            streamed,
+            result
+        );
+
+        let mut seen: usize = 0;
+        let stopped = aligner.search("q.fa", "db", &[], &mut |_| {
+            seen += 1;
+            Err("disk full".to_string())
+        });
+        assert!(
+            seen == 1 && stopped == Err("disk full".to_string()),
+            "{} failed: {:?}",
+            function_name!(),
+            stopped
+        );
+    }
+}
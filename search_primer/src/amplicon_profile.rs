@@ -0,0 +1,397 @@
+// プライマーペアごとに産物を星型アラインメントし、増幅領域のばらつきを調べる。
+// 最も多い配列を中心に、他の配列を中心へ大域アラインメントして列ごとに塩基を数える。
+// 中心に対する挿入は列を増やさず、直前の列の挿入数として数える。
+
+use crate::in_silico_pcr::{reverse_complement, PrimerSide};
+use crate::primer_set::{iupac_bases, iupac_code};
+use std::collections::HashMap;
+
+/// A, C, G, T and deletion, in this order.
+pub const COLUMN_SYMBOLS: [u8; 5] = [b'A', b'C', b'G', b'T', b'-'];
+
+fn symbol_index(base: u8) -> Option<usize> {
+    match base.to_ascii_uppercase() {
+        b'A' => Some(0),
+        b'C' => Some(1),
+        b'G' => Some(2),
+        b'T' => Some(3),
+        b'-' => Some(4),
+        _ => None,
+    }
+}
+
+/// Brings a product of extract_PCR_target_region to the left->right primer
+/// orientation. Only L-R and R-L products span the target region; the others
+/// give `None`.
+pub fn normalize_orientation(orientation: &str, sequence: &[u8]) -> Option<Vec<u8>> {
+    match orientation {
+        "L-R" => Some(sequence.to_ascii_uppercase()),
+        "R-L" => Some(reverse_complement(sequence)),
+        _ => None,
+    }
+}
+
+/// Global alignment of `query` to `center` by edit distance. Returns the query
+/// base on each center column (`None` for a deletion) and the number of query
+/// bases inserted after each column; insertions before the first column are
+/// counted on the first.
+pub fn align_to_center(center: &[u8], query: &[u8]) -> (Vec<Option<u8>>, Vec<usize>) {
+    let (n, m) = (center.len(), query.len());
+    let mut cost: Vec<Vec<u32>> = vec![vec![0; m + 1]; n + 1];
+    for (i, row) in cost.iter_mut().enumerate() {
+        row[0] = i as u32;
+    }
+    for (j, cell) in cost[0].iter_mut().enumerate() {
+        *cell = j as u32;
+    }
+    for i in 1..=n {
+        for j in 1..=m {
+            let substitution = cost[i - 1][j - 1] + (center[i - 1] != query[j - 1]) as u32;
+            cost[i][j] = substitution.min(cost[i - 1][j] + 1).min(cost[i][j - 1] + 1);
+        }
+    }
+
+    let mut aligned: Vec<Option<u8>> = vec![None; n];
+    let mut insertions: Vec<usize> = vec![0; n];
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        if i > 0
+            && j > 0
+            && cost[i][j] == cost[i - 1][j - 1] + (center[i - 1] != query[j - 1]) as u32
+        {
+            aligned[i - 1] = Some(query[j - 1]);
+            i -= 1;
+            j -= 1;
+        } else if i > 0 && cost[i][j] == cost[i - 1][j] + 1 {
+            i -= 1;
+        } else {
+            if n > 0 {
+                insertions[i.saturating_sub(1)] += 1;
+            }
+            j -= 1;
+        }
+    }
+    (aligned, insertions)
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ColumnProfile {
+    /// Counts of `COLUMN_SYMBOLS`; other bases such as N are not counted.
+    pub counts: [usize; 5],
+    /// Number of amplicons with an insertion after this column.
+    pub insertions: usize,
+}
+
+impl ColumnProfile {
+    pub fn depth(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// Shannon entropy (bits) over A, C, G, T and deletion.
+    pub fn entropy(&self) -> f64 {
+        let depth = self.depth() as f64;
+        self.counts
+            .iter()
+            .filter(|&&c| c > 0)
+            .map(|&c| {
+                let p = c as f64 / depth;
+                p * (1.0 / p).log2()
+            })
+            .sum()
+    }
+
+    /// IUPAC code of the bases seen in at least `min_fraction` of the bases, or
+    /// `-` when most amplicons have a deletion here.
+    pub fn consensus(&self, min_fraction: f64) -> u8 {
+        let bases: usize = self.counts[..4].iter().sum();
+        if bases == 0 || self.counts[4] * 2 > self.depth() {
+            return b'-';
+        }
+        let seen: Vec<u8> = (0..4)
+            .filter(|&k| self.counts[k] as f64 >= min_fraction * bases as f64)
+            .map(|k| COLUMN_SYMBOLS[k])
+            .collect();
+        iupac_code(&seen)
+    }
+
+    /// Fraction of the depth that does not match any base of the IUPAC code `expected`.
+    pub fn variant_frequency(&self, expected: u8) -> f64 {
+        let depth = self.depth();
+        if depth == 0 {
+            return 0.0;
+        }
+        let matched: usize = iupac_bases(expected)
+            .iter()
+            .filter_map(|&b| symbol_index(b))
+            .map(|k| self.counts[k])
+            .sum();
+        (depth - matched) as f64 / depth as f64
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AmpliconProfile {
+    pub primer_id: String,
+    pub amplicons: usize,
+    pub distinct: usize,
+    /// The most frequent sequence, which the others are aligned to.
+    pub center: Vec<u8>,
+    pub columns: Vec<ColumnProfile>,
+}
+
+impl AmpliconProfile {
+    /// Consensus with IUPAC codes; columns where most amplicons have a deletion are left out.
+    pub fn consensus(&self, min_fraction: f64) -> Vec<u8> {
+        self.columns
+            .iter()
+            .map(|c| c.consensus(min_fraction))
+            .filter(|&b| b != b'-')
+            .collect()
+    }
+
+    pub fn mean_entropy(&self) -> f64 {
+        if self.columns.is_empty() {
+            return 0.0;
+        }
+        self.columns.iter().map(|c| c.entropy()).sum::<f64>() / self.columns.len() as f64
+    }
+}
+
+/// Star alignment of the amplicons of one primer pair, all in the same orientation.
+/// Identical sequences are aligned once.
+pub fn profile_amplicons(primer_id: &str, sequences: &[Vec<u8>]) -> AmpliconProfile {
+    let mut distinct: HashMap<&[u8], usize> = HashMap::new();
+    for sequence in sequences {
+        *distinct.entry(sequence.as_slice()).or_insert(0) += 1;
+    }
+    // 同数なら辞書順で先のものを中心にして、結果を決定的にする
+    let center: Vec<u8> = distinct
+        .iter()
+        .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+        .map(|(s, _)| s.to_vec())
+        .unwrap_or_default();
+
+    let mut columns: Vec<ColumnProfile> = vec![ColumnProfile::default(); center.len()];
+    for (sequence, count) in &distinct {
+        let (aligned, insertions) = align_to_center(&center, sequence);
+        for (column, (base, inserted)) in columns.iter_mut().zip(aligned.iter().zip(insertions)) {
+            if let Some(k) = symbol_index(base.unwrap_or(b'-')) {
+                column.counts[k] += count;
+            }
+            if inserted > 0 {
+                column.insertions += count;
+            }
+        }
+    }
+    AmpliconProfile {
+        primer_id: primer_id.to_string(),
+        amplicons: sequences.len(),
+        distinct: distinct.len(),
+        center,
+        columns,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SiteColumn {
+    pub side: PrimerSide,
+    /// Column of the profile.
+    pub column: usize,
+    /// Distance from the 3' end of the primer, 0 for the 3'-terminal base.
+    pub from_3p: usize,
+    /// Primer base in the amplicon orientation (IUPAC).
+    pub expected: u8,
+    pub variant_frequency: f64,
+}
+
+fn complement_code(code: u8) -> u8 {
+    let bases: Vec<u8> = iupac_bases(code)
+        .iter()
+        .map(|&b| reverse_complement(&[b])[0])
+        .collect();
+    iupac_code(&bases)
+}
+
+/// Profile columns under the primers. The amplicon starts with `left` and ends
+/// with the reverse complement of `right`; empty when it is shorter than both.
+pub fn primer_site_columns(
+    profile: &AmpliconProfile,
+    left: &[u8],
+    right: &[u8],
+) -> Vec<SiteColumn> {
+    let length = profile.columns.len();
+    if length < left.len() + right.len() {
+        return Vec::new();
+    }
+    let mut sites: Vec<SiteColumn> = Vec::new();
+    for (i, &base) in left.iter().enumerate() {
+        sites.push(SiteColumn {
+            side: PrimerSide::Left,
+            column: i,
+            from_3p: left.len() - 1 - i,
+            expected: base.to_ascii_uppercase(),
+            variant_frequency: profile.columns[i].variant_frequency(base),
+        });
+    }
+    for (j, &base) in right.iter().rev().enumerate() {
+        let column = length - right.len() + j;
+        let expected = complement_code(base);
+        sites.push(SiteColumn {
+            side: PrimerSide::Right,
+            column,
+            from_3p: j,
+            expected,
+            variant_frequency: profile.columns[column].variant_frequency(expected),
+        });
+    }
+    sites
+}
+
+pub fn column_tsv_header() -> String {
+    "primer_id\tcolumn\tcenter\tconsensus\tA\tC\tG\tT\tdel\tins\tentropy\tsite\n".to_string()
+}
+
+/// One line per profile column; `site` marks the primer binding sites.
+pub fn column_tsv_lines(
+    profile: &AmpliconProfile,
+    sites: &[SiteColumn],
+    min_fraction: f64,
+) -> String {
+    let mut lines = String::new();
+    for (i, column) in profile.columns.iter().enumerate() {
+        let site = match sites.iter().find(|s| s.column == i) {
+            Some(s) => format!("{}{}", s.side.label(), s.from_3p),
+            None => ".".to_string(),
+        };
+        lines += &format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.3}\t{}\n",
+            profile.primer_id,
+            i + 1,
+            profile.center[i] as char,
+            column.consensus(min_fraction) as char,
+            column.counts[0],
+            column.counts[1],
+            column.counts[2],
+            column.counts[3],
+            column.counts[4],
+            column.insertions,
+            column.entropy(),
+            site
+        );
+    }
+    lines
+}
+
+pub fn site_tsv_header() -> String {
+    "primer_id\tamplicons\tdistinct\tlength\tmean_entropy\tleft_variant_sites\tright_variant_sites\tmax_3p_variant\tverdict\n".to_string()
+}
+
+/// Per-pair summary. A site counts as variant from `snp_frequency` on; the pair
+/// is rejected when one of them lies within `three_prime` bases of a 3' end.
+pub fn site_tsv_line(
+    profile: &AmpliconProfile,
+    sites: &[SiteColumn],
+    snp_frequency: f64,
+    three_prime: usize,
+) -> String {
+    let variant_sites = |side: PrimerSide| -> String {
+        let found: Vec<String> = sites
+            .iter()
+            .filter(|s| s.side == side && s.variant_frequency >= snp_frequency)
+            .map(|s| format!("{}:{:.3}", s.from_3p, s.variant_frequency))
+            .collect();
+        if found.is_empty() {
+            ".".to_string()
+        } else {
+            found.join(",")
+        }
+    };
+    let max_3p_variant: f64 = sites
+        .iter()
+        .filter(|s| s.from_3p < three_prime)
+        .map(|s| s.variant_frequency)
+        .fold(0.0, f64::max);
+    let verdict = if sites.is_empty() {
+        "no_site"
+    } else if max_3p_variant >= snp_frequency {
+        "reject"
+    } else {
+        "pass"
+    };
+    format!(
+        "{}\t{}\t{}\t{}\t{:.3}\t{}\t{}\t{:.3}\t{}\n",
+        profile.primer_id,
+        profile.amplicons,
+        profile.distinct,
+        profile.columns.len(),
+        profile.mean_entropy(),
+        variant_sites(PrimerSide::Left),
+        variant_sites(PrimerSide::Right),
+        max_3p_variant,
+        verdict
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::amplicon_profile::{align_to_center, primer_site_columns, profile_amplicons};
+    use crate::in_silico_pcr::PrimerSide;
+    use ::function_name::named;
+
+    #[test]
+    #[named]
+    fn align_to_center_test() {
+        let (aligned, insertions) = align_to_center(b"ACGTACGT", b"ACGTTACGA");
+        let aligned: Vec<u8> = aligned.iter().map(|b| b.unwrap_or(b'-')).collect();
+        assert!(
+            aligned == b"ACGTACGA".to_vec() && insertions.iter().sum::<usize>() == 1,
+            "{} failed: {:?} {:?}",
+            function_name!(),
+            String::from_utf8(aligned),
+            insertions
+        );
+        let (aligned, _) = align_to_center(b"ACGTACGT", b"ACGACGT");
+        assert!(
+            aligned.iter().filter(|b| b.is_none()).count() == 1,
+            "{} failed: {:?}",
+            function_name!(),
+            aligned
+        );
+    }
+
+    #[test]
+    #[named]
+    fn profile_amplicons_test() {
+        let sequences: Vec<Vec<u8>> = vec![
+            b"AACCGGTTAACC".to_vec(),
+            b"AACCGGTTAACC".to_vec(),
+            b"AACCGGTTAACC".to_vec(),
+            b"AACTGGTTAACC".to_vec(),
+        ];
+        let profile = profile_amplicons("p1", &sequences);
+        assert!(
+            profile.distinct == 2
+                && profile.consensus(0.2) == b"AACYGGTTAACC".to_vec()
+                && profile.consensus(0.3) == b"AACCGGTTAACC".to_vec()
+                && profile.columns[3].entropy() > 0.8
+                && profile.columns[0].entropy() == 0.0,
+            "{} failed: {:?}",
+            function_name!(),
+            profile
+        );
+        // 左プライマーAACC、右プライマーGGTT(産物の末端はAACC)
+        let sites = primer_site_columns(&profile, b"AACC", b"GGTT");
+        let snp = sites.iter().find(|s| s.variant_frequency > 0.0).unwrap();
+        assert!(
+            sites.len() == 8
+                && snp.side == PrimerSide::Left
+                && snp.from_3p == 0
+                && snp.variant_frequency == 0.25
+                && sites[4].expected == b'A'
+                && sites[4].from_3p == 0,
+            "{} failed: {:?}",
+            function_name!(),
+            sites
+        );
+    }
+}
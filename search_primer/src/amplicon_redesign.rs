@@ -0,0 +1,263 @@
+// extract_PCR_target_regionの産物から、プライマー+プローブを設計し直す。
+// 両端の配列が同じ産物をまとめ、グループ内で最も多くの産物に含まれる中央部のk-merをプローブ候補とする。
+
+use crate::amplicon_util::parse_amplicon_record;
+use crate::sequence_encoder_util::{decode_u128_2_dna_seq, DnaSequence};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+// (5'側, 3'側) -> (プライマーid, 産物)
+type FlankGroups = BTreeMap<(Vec<u8>, Vec<u8>), (String, Vec<Vec<u8>>)>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AmpliconGroup {
+    /// `{primer_id}_{n}`, n counts the groups of the same primer pair from 0.
+    pub id: String,
+    pub left: Vec<u8>,
+    pub right: Vec<u8>,
+    pub members: Vec<Vec<u8>>,
+}
+
+/// Groups amplicon sequences by their first and last `flank` bases. `amplicons`
+/// holds (FASTA header, sequence); sequences shorter than `2 * flank` are dropped.
+/// Groups come out largest first.
+pub fn group_by_flanks(amplicons: &[(String, Vec<u8>)], flank: usize) -> Vec<AmpliconGroup> {
+    let mut groups: FlankGroups = BTreeMap::new();
+    for (header, sequence) in amplicons {
+        if sequence.len() < 2 * flank {
+            continue;
+        }
+        let sequence: Vec<u8> = sequence.to_ascii_uppercase();
+        let left: Vec<u8> = sequence[..flank].to_vec();
+        let right: Vec<u8> = sequence[sequence.len() - flank..].to_vec();
+        groups
+            .entry((left, right))
+            .or_insert_with(|| (primer_id_of(header), Vec::new()))
+            .1
+            .push(sequence);
+    }
+    let mut groups: Vec<_> = groups.into_iter().collect();
+    groups.sort_by_key(|(_, (_, members))| std::cmp::Reverse(members.len()));
+
+    let mut serial: HashMap<String, usize> = HashMap::new();
+    groups
+        .into_iter()
+        .map(|((left, right), (primer_id, members))| {
+            let n = serial.entry(primer_id.clone()).or_insert(0);
+            let id = format!("{}_{}", primer_id, n);
+            *n += 1;
+            AmpliconGroup {
+                id,
+                left,
+                right,
+                members,
+            }
+        })
+        .collect()
+}
+
+// 見出しがextract_PCR_target_regionの形式でなければ、python版と同じく最初の'_'までをidとする
+fn primer_id_of(header: &str) -> String {
+    match parse_amplicon_record(&format!(">{}", header)) {
+        Some((primer_id, _, _)) => primer_id,
+        None => header.split('_').next().unwrap_or_default().to_string(),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConservedKmer {
+    pub sequence: Vec<u8>,
+    /// Number of group members containing the k-mer.
+    pub amplicons: usize,
+    /// Total number of occurrences in the group.
+    pub occurrences: usize,
+}
+
+/// The k-mer (k <= 64) between the flanks contained in the most members, ties
+/// broken by total occurrences and then by sequence. Members with bases other
+/// than ACGT are skipped.
+pub fn most_conserved_kmer(members: &[Vec<u8>], flank: usize, k: usize) -> Option<ConservedKmer> {
+    assert!(
+        (1..=64).contains(&k),
+        "most_conserved_kmer: k must be 1..=64, got {}",
+        k
+    );
+    let mut counts: HashMap<u128, (usize, usize)> = HashMap::new();
+    for member in members {
+        if member.len() < 2 * flank + k
+            || !member
+                .iter()
+                .all(|b| matches!(b, b'A' | b'C' | b'G' | b'T'))
+        {
+            continue;
+        }
+        let sequence = DnaSequence::new(member);
+        let mut seen: HashSet<u128> = HashSet::new();
+        for start in flank..=member.len() - flank - k {
+            let kmer: u128 = sequence.subsequence_as_u128(vec![[start, start + k]]);
+            let count = counts.entry(kmer).or_insert((0, 0));
+            if seen.insert(kmer) {
+                count.0 += 1;
+            }
+            count.1 += 1;
+        }
+    }
+    counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+        .map(|(kmer, (amplicons, occurrences))| ConservedKmer {
+            sequence: decode_u128_2_dna_seq(&kmer, k),
+            amplicons,
+            occurrences,
+        })
+}
+
+/// One primer3_core record for `group`: flanks and `probe` joined by `gap` Ns,
+/// the probe passed as the internal oligo and each primer confined to its flank.
+/// `config` lines are appended as they are; without it the primer3 defaults of
+/// `swordfish primer3` are used.
+pub fn primer3_input(
+    group: &AmpliconGroup,
+    probe: &[u8],
+    gap: usize,
+    config: Option<&str>,
+) -> String {
+    let n = "N".repeat(gap);
+    let template = format!(
+        "{}{}{}{}{}",
+        String::from_utf8_lossy(&group.left),
+        n,
+        String::from_utf8_lossy(probe),
+        n,
+        String::from_utf8_lossy(&group.right)
+    );
+    let r_offset = group.left.len() + 2 * gap + probe.len();
+    let config: String = match config {
+        // python版と同じく、プライマーのみのタスクはプローブも選ぶタスクに置き換える
+        Some(config) => format!("{}\n", config.trim_end())
+            .replace("=pick_pcr_primers\n", "=pick_pcr_primers_and_hyb_probe\n")
+            .trim_end()
+            .to_string(),
+        None => format!(
+            "PRIMER_TASK=generic\n\
+             PRIMER_PICK_LEFT_PRIMER=1\n\
+             PRIMER_PICK_INTERNAL_OLIGO=1\n\
+             PRIMER_PICK_RIGHT_PRIMER=1\n\
+             PRIMER_PRODUCT_SIZE_RANGE=1-{}\n\
+             P3_FILE_FLAG=0\n\
+             PRIMER_EXPLAIN_FLAG=1",
+            template.len()
+        ),
+    };
+    format!(
+        "SEQUENCE_ID={}\n\
+         SEQUENCE_TEMPLATE={}\n\
+         SEQUENCE_INTERNAL_OLIGO={}\n\
+         SEQUENCE_PRIMER_PAIR_OK_REGION_LIST=0,{},{},{}\n\
+         {}\n=\n",
+        group.id,
+        template,
+        String::from_utf8_lossy(probe),
+        group.left.len(),
+        r_offset,
+        group.right.len(),
+        config
+    )
+}
+
+/// Splits primer3_core output into its records, each a tag -> value map.
+pub fn parse_primer3_output(output: &str) -> Vec<HashMap<String, String>> {
+    let mut records: Vec<HashMap<String, String>> = Vec::new();
+    let mut record: HashMap<String, String> = HashMap::new();
+    for line in output.lines() {
+        if line == "=" {
+            records.push(std::mem::take(&mut record));
+        } else if let Some((tag, value)) = line.split_once('=') {
+            record.insert(tag.to_string(), value.to_string());
+        }
+    }
+    records
+}
+
+pub fn designed_set_tsv_header() -> String {
+    "group_id\tamplicons\tprobe_kmer\tkmer_amplicons\tleft\tprobe\tright\tproduct_size\tleft_tm\tprobe_tm\tright_tm\n".to_string()
+}
+
+/// The best primer+probe set of a primer3 record, NA where primer3 found none.
+pub fn designed_set_tsv_line(
+    group: &AmpliconGroup,
+    kmer: &ConservedKmer,
+    record: &HashMap<String, String>,
+) -> String {
+    let tag = |name: &str| -> &str { record.get(name).map(|v| v.as_str()).unwrap_or("NA") };
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+        group.id,
+        group.members.len(),
+        String::from_utf8_lossy(&kmer.sequence),
+        kmer.amplicons,
+        tag("PRIMER_LEFT_0_SEQUENCE"),
+        tag("PRIMER_INTERNAL_0_SEQUENCE"),
+        tag("PRIMER_RIGHT_0_SEQUENCE"),
+        tag("PRIMER_PAIR_0_PRODUCT_SIZE"),
+        tag("PRIMER_LEFT_0_TM"),
+        tag("PRIMER_INTERNAL_0_TM"),
+        tag("PRIMER_RIGHT_0_TM"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::amplicon_redesign::{
+        group_by_flanks, most_conserved_kmer, parse_primer3_output, primer3_input,
+    };
+    use ::function_name::named;
+
+    #[test]
+    #[named]
+    fn group_and_kmer_test() {
+        let amplicons: Vec<(String, Vec<u8>)> = vec![
+            ("p1L-R_20".to_string(), b"AAAACCGTACGTTTTT".to_vec()),
+            ("p1L-R_20".to_string(), b"AAAAGCGTACGTTTTT".to_vec()),
+            ("p1L-R_20".to_string(), b"GGGGCCGTACGTCCCC".to_vec()),
+            ("p1L-R_20".to_string(), b"AAAACGTN".to_vec()),
+        ];
+        let groups = group_by_flanks(&amplicons, 4);
+        assert!(
+            groups.len() == 3 && groups[0].id == "p1_0" && groups[0].members.len() == 2,
+            "{} failed: {:?}",
+            function_name!(),
+            groups
+        );
+        let kmer = most_conserved_kmer(&groups[0].members, 4, 6).unwrap();
+        assert!(
+            kmer.sequence == b"CGTACG".to_vec() && kmer.amplicons == 2,
+            "{} failed: {:?}",
+            function_name!(),
+            kmer
+        );
+    }
+
+    #[test]
+    #[named]
+    fn primer3_test() {
+        let amplicons = vec![("p1L-R_20".to_string(), b"AAAACCGTACGTTTTT".to_vec())];
+        let groups = group_by_flanks(&amplicons, 4);
+        let input = primer3_input(&groups[0], b"CGTACG", 2, None);
+        assert!(
+            input.contains("SEQUENCE_TEMPLATE=AAAANNCGTACGNNTTTT\n")
+                && input.contains("SEQUENCE_PRIMER_PAIR_OK_REGION_LIST=0,4,14,4\n")
+                && input.ends_with("\n=\n"),
+            "{} failed: {}",
+            function_name!(),
+            input
+        );
+        let records =
+            parse_primer3_output("SEQUENCE_ID=a\nPRIMER_LEFT_0_SEQUENCE=AC\n=\nSEQUENCE_ID=b\n=\n");
+        assert!(
+            records.len() == 2 && records[0]["PRIMER_LEFT_0_SEQUENCE"] == "AC",
+            "{} failed: {:?}",
+            function_name!(),
+            records
+        );
+    }
+}
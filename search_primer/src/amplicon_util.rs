@@ -0,0 +1,494 @@
+use crate::in_silico_pcr::{Amplicon, PrimerSide};
+use std::collections::BTreeMap;
+
+pub const ORIENTATIONS: [&str; 4] = ["L-L", "L-R", "R-L", "R-R"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AmpliconFormat {
+    Fasta,
+    Bed,
+    Gff3,
+}
+
+impl AmpliconFormat {
+    pub fn parse(name: &str) -> Result<AmpliconFormat, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "fasta" | "fa" => Ok(AmpliconFormat::Fasta),
+            "bed" => Ok(AmpliconFormat::Bed),
+            "gff" | "gff3" => Ok(AmpliconFormat::Gff3),
+            other => Err(format!(
+                "unknown output format: {} (fasta, bed or gff3)",
+                other
+            )),
+        }
+    }
+
+    pub fn header(&self) -> Option<&'static str> {
+        match self {
+            AmpliconFormat::Gff3 => Some("##gff-version 3\n"),
+            _ => None,
+        }
+    }
+}
+
+/// Strand of the amplified target relative to the template: `+` when the
+/// left primer primes the template as given, `-` when the right primer does.
+pub fn amplicon_strand(amplicon: &Amplicon) -> char {
+    match amplicon.forward.side {
+        PrimerSide::Left => '+',
+        PrimerSide::Right => '-',
+    }
+}
+
+/// One record of `amplicon` in `format`, including the trailing newline.
+///
+/// The FASTA ID keeps the historical `{primer_id}{orientation}_{length}` form so
+/// that downstream scripts splitting on `_` keep working; the source read and
+/// coordinates (1-based, inclusive) go into the description.
+pub fn format_amplicon(amplicon: &Amplicon, format: AmpliconFormat) -> String {
+    let orientation = amplicon.orientation();
+    let strand = amplicon_strand(amplicon);
+    let mismatches = amplicon.forward.mismatches + amplicon.reverse.mismatches;
+    match format {
+        AmpliconFormat::Fasta => format!(
+            ">{}{}_{} {}:{}-{}({})\n{}\n",
+            amplicon.pair_id,
+            orientation,
+            amplicon.len(),
+            amplicon.template_id,
+            amplicon.start + 1,
+            amplicon.end,
+            strand,
+            String::from_utf8_lossy(&amplicon.sequence)
+        ),
+        AmpliconFormat::Bed => format!(
+            "{}\t{}\t{}\t{}_{}\t{}\t{}\n",
+            amplicon.template_id,
+            amplicon.start,
+            amplicon.end,
+            amplicon.pair_id,
+            orientation,
+            mismatches,
+            strand
+        ),
+        AmpliconFormat::Gff3 => format!(
+            "{}\tswordfish\tPCR_product\t{}\t{}\t.\t{}\t.\tID={}_{}_{}_{};primer_id={};orientation={};length={};mismatches={}\n",
+            amplicon.template_id,
+            amplicon.start + 1,
+            amplicon.end,
+            strand,
+            amplicon.pair_id,
+            orientation,
+            amplicon.template_id,
+            amplicon.start + 1,
+            amplicon.pair_id,
+            orientation,
+            amplicon.len(),
+            mismatches
+        ),
+    }
+}
+
+/// Hit counts per orientation and product length distribution of one primer pair.
+#[derive(Clone, Debug, Default)]
+pub struct PrimerPairSummary {
+    pub hits: BTreeMap<String, usize>,
+    pub lengths: BTreeMap<usize, usize>,
+    pub orientation_lengths: BTreeMap<String, BTreeMap<usize, usize>>,
+}
+
+impl PrimerPairSummary {
+    pub fn add(&mut self, orientation: &str, length: usize) {
+        *self.hits.entry(orientation.to_string()).or_insert(0) += 1;
+        *self.lengths.entry(length).or_insert(0) += 1;
+        *self
+            .orientation_lengths
+            .entry(orientation.to_string())
+            .or_default()
+            .entry(length)
+            .or_insert(0) += 1;
+    }
+
+    pub fn total(&self) -> usize {
+        self.hits.values().sum()
+    }
+
+    /// Product length at quantile `q` (0.0..=1.0) over all hits.
+    pub fn length_quantile(&self, q: f64) -> Option<usize> {
+        length_quantile(&self.lengths, q)
+    }
+}
+
+/// Length at quantile `q` (0.0..=1.0) of a `length -> count` distribution.
+pub fn length_quantile(lengths: &BTreeMap<usize, usize>, q: f64) -> Option<usize> {
+    let total: usize = lengths.values().sum();
+    if total == 0 {
+        return None;
+    }
+    let rank: usize = ((total - 1) as f64 * q).round() as usize;
+    let mut seen: usize = 0;
+    for (&length, &count) in lengths {
+        seen += count;
+        if seen > rank {
+            return Some(length);
+        }
+    }
+    lengths.keys().next_back().copied()
+}
+
+/// Per-primer-pair summary of a set of amplicons, ordered by primer ID.
+pub fn summarize_amplicons<'a, I: IntoIterator<Item = &'a Amplicon>>(
+    amplicons: I,
+) -> BTreeMap<String, PrimerPairSummary> {
+    let mut retval: BTreeMap<String, PrimerPairSummary> = BTreeMap::new();
+    for amplicon in amplicons {
+        retval
+            .entry(amplicon.pair_id.clone())
+            .or_default()
+            .add(&amplicon.orientation(), amplicon.len());
+    }
+    retval
+}
+
+pub fn summary_tsv_header() -> String {
+    format!(
+        "primer_id\t{}\ttotal\tmin_length\tmedian_length\tmax_length\tlength_distribution\n",
+        ORIENTATIONS.join("\t")
+    )
+}
+
+/// `primer_id, hits per orientation, total, min/median/max length, length:count,...`
+pub fn summary_tsv_line(primer_id: &str, summary: &PrimerPairSummary) -> String {
+    let hits: Vec<String> = ORIENTATIONS
+        .iter()
+        .map(|o| summary.hits.get(*o).unwrap_or(&0).to_string())
+        .collect();
+    let distribution: Vec<String> = summary
+        .lengths
+        .iter()
+        .map(|(length, count)| format!("{}:{}", length, count))
+        .collect();
+    let quantile = |q: f64| {
+        summary
+            .length_quantile(q)
+            .map(|l| l.to_string())
+            .unwrap_or("NA".to_string())
+    };
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+        primer_id,
+        hits.join("\t"),
+        summary.total(),
+        quantile(0.0),
+        quantile(0.5),
+        quantile(1.0),
+        distribution.join(",")
+    )
+}
+
+/// Primer ID, orientation and product length of one record written by
+/// `extract_PCR_target_region` (FASTA header, BED or GFF3 line).
+/// Returns `None` for sequence lines, comments and anything else that is not a record.
+pub fn parse_amplicon_record(line: &str) -> Option<(String, String, usize)> {
+    if let Some(header) = line.strip_prefix('>') {
+        // >{primer_id}{orientation}_{length} ...
+        let id = header.split_whitespace().next()?;
+        let (id_orientation, length) = id.rsplit_once('_')?;
+        let orientation = ORIENTATIONS.iter().find(|o| id_orientation.ends_with(*o))?;
+        let primer_id = &id_orientation[..id_orientation.len() - orientation.len()];
+        return Some((
+            primer_id.to_string(),
+            orientation.to_string(),
+            length.parse().ok()?,
+        ));
+    }
+    if line.starts_with('#') {
+        return None;
+    }
+    let columns: Vec<&str> = line.split('\t').collect();
+    if columns.len() == 9 && columns[2] == "PCR_product" {
+        let mut primer_id: Option<&str> = None;
+        let mut orientation: Option<&str> = None;
+        let mut length: Option<usize> = None;
+        for attribute in columns[8].split(';') {
+            match attribute.split_once('=') {
+                Some(("primer_id", v)) => primer_id = Some(v),
+                Some(("orientation", v)) => orientation = Some(v),
+                Some(("length", v)) => length = v.parse().ok(),
+                _ => {}
+            }
+        }
+        return Some((primer_id?.to_string(), orientation?.to_string(), length?));
+    }
+    if columns.len() == 6 {
+        // BED: name is {primer_id}_{orientation}
+        let (primer_id, orientation) = columns[3].rsplit_once('_')?;
+        let start: usize = columns[1].parse().ok()?;
+        let end: usize = columns[2].parse().ok()?;
+        return Some((
+            primer_id.to_string(),
+            orientation.to_string(),
+            end.checked_sub(start)?,
+        ));
+    }
+    None
+}
+
+/// A group of product lengths that would run as one band on a gel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LengthBand {
+    /// Most frequent length within the band.
+    pub length: usize,
+    pub min_length: usize,
+    pub max_length: usize,
+    pub count: usize,
+}
+
+/// Clusters a `length -> count` distribution into bands.
+///
+/// Lengths no more than `tolerance` bases apart from the previous length are
+/// merged into the same band, and bands holding less than `min_fraction` of all
+/// products are dropped as noise. Bands are returned most abundant first.
+pub fn call_length_bands(
+    lengths: &BTreeMap<usize, usize>,
+    tolerance: usize,
+    min_fraction: f64,
+) -> Vec<LengthBand> {
+    let total: usize = lengths.values().sum();
+    let mut bands: Vec<LengthBand> = Vec::new();
+    for (&length, &count) in lengths {
+        match bands.last_mut() {
+            Some(band) if length - band.max_length <= tolerance => {
+                if count > *lengths.get(&band.length).unwrap_or(&0) {
+                    band.length = length;
+                }
+                band.max_length = length;
+                band.count += count;
+            }
+            _ => bands.push(LengthBand {
+                length,
+                min_length: length,
+                max_length: length,
+                count,
+            }),
+        }
+    }
+    bands.retain(|band| band.count as f64 >= total as f64 * min_fraction);
+    bands.sort_by(|a, b| b.count.cmp(&a.count).then(a.length.cmp(&b.length)));
+    bands
+}
+
+pub fn band_tsv_header() -> String {
+    "primer_id\torientation\thits\tmin_length\tmedian_length\tmax_length\tnumber_of_bands\tdominant_length\tdominant_fraction\tbands\tmulti_band\n".to_string()
+}
+
+/// `bands` is `length(min-max):count,...`, most abundant band first.
+pub fn band_tsv_line(
+    primer_id: &str,
+    orientation: &str,
+    lengths: &BTreeMap<usize, usize>,
+    bands: &[LengthBand],
+) -> String {
+    let total: usize = lengths.values().sum();
+    let quantile = |q: f64| {
+        length_quantile(lengths, q)
+            .map(|l| l.to_string())
+            .unwrap_or("NA".to_string())
+    };
+    let (dominant_length, dominant_fraction) = match bands.first() {
+        Some(band) => (
+            band.length.to_string(),
+            format!("{:.3}", band.count as f64 / total as f64),
+        ),
+        None => ("NA".to_string(), "NA".to_string()),
+    };
+    let band_list: Vec<String> = bands
+        .iter()
+        .map(|b| {
+            format!(
+                "{}({}-{}):{}",
+                b.length, b.min_length, b.max_length, b.count
+            )
+        })
+        .collect();
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+        primer_id,
+        orientation,
+        total,
+        quantile(0.0),
+        quantile(0.5),
+        quantile(1.0),
+        bands.len(),
+        dominant_length,
+        dominant_fraction,
+        band_list.join(","),
+        bands.len() > 1
+    )
+}
+
+/// Plain-text histogram of a `length -> count` distribution, `bin_width` bases
+/// per row and bars scaled so that the largest bin is `bar_width` characters.
+pub fn text_histogram(
+    lengths: &BTreeMap<usize, usize>,
+    bin_width: usize,
+    bar_width: usize,
+) -> String {
+    let bin_width = bin_width.max(1);
+    let mut bins: BTreeMap<usize, usize> = BTreeMap::new();
+    for (&length, &count) in lengths {
+        *bins.entry(length / bin_width).or_insert(0) += count;
+    }
+    let (first, last) = match (bins.keys().next(), bins.keys().next_back()) {
+        (Some(&first), Some(&last)) => (first, last),
+        _ => return String::new(),
+    };
+    let max_count: usize = *bins.values().max().unwrap_or(&1);
+    let mut retval = String::new();
+    for bin in first..=last {
+        let count: usize = *bins.get(&bin).unwrap_or(&0);
+        // 1件でもあれば最低1文字は表示する
+        let bar: usize = (count * bar_width).div_ceil(max_count);
+        retval.push_str(&format!(
+            "{:>6}-{:<6} {:>8} {}\n",
+            bin * bin_width,
+            (bin + 1) * bin_width - 1,
+            count,
+            "#".repeat(bar)
+        ));
+    }
+    retval
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::amplicon_util::{
+        call_length_bands, format_amplicon, parse_amplicon_record, summarize_amplicons,
+        summary_tsv_line, text_histogram, AmpliconFormat, LengthBand,
+    };
+    use crate::in_silico_pcr::{Amplicon, BindingSite, PrimerSide, Strand};
+    use ::function_name::named;
+
+    fn amplicon(forward: PrimerSide, reverse: PrimerSide, start: usize, end: usize) -> Amplicon {
+        let site = |side: PrimerSide, strand: Strand, start: usize, end: usize| BindingSite {
+            side,
+            strand,
+            start,
+            end,
+            mismatches: 0,
+            weighted_mismatches: 0.0,
+            mismatch_distances: Vec::new(),
+        };
+        Amplicon {
+            pair_id: "p1".to_string(),
+            template_id: "read1".to_string(),
+            forward: site(forward, Strand::Forward, start, start + 4),
+            reverse: site(reverse, Strand::Reverse, end - 4, end),
+            start,
+            end,
+            sequence: vec![b'A'; end - start],
+        }
+    }
+
+    #[test]
+    #[named]
+    fn format_amplicon_test() {
+        let a = amplicon(PrimerSide::Right, PrimerSide::Left, 10, 20);
+        assert!(
+            format_amplicon(&a, AmpliconFormat::Fasta) == ">p1R-L_10 read1:11-20(-)\nAAAAAAAAAA\n",
+            "{} failed",
+            function_name!()
+        );
+        assert!(
+            format_amplicon(&a, AmpliconFormat::Bed) == "read1\t10\t20\tp1_R-L\t0\t-\n",
+            "{} failed",
+            function_name!()
+        );
+        assert!(
+            format_amplicon(&a, AmpliconFormat::Gff3)
+                .starts_with("read1\tswordfish\tPCR_product\t11\t20\t.\t-\t.\t"),
+            "{} failed",
+            function_name!()
+        );
+    }
+
+    #[test]
+    #[named]
+    fn summary_test() {
+        let amplicons = vec![
+            amplicon(PrimerSide::Left, PrimerSide::Right, 0, 100),
+            amplicon(PrimerSide::Left, PrimerSide::Right, 0, 100),
+            amplicon(PrimerSide::Right, PrimerSide::Left, 0, 150),
+        ];
+        let summary = summarize_amplicons(&amplicons);
+        assert!(
+            summary_tsv_line("p1", &summary["p1"])
+                == "p1\t0\t2\t1\t0\t3\t100\t100\t150\t100:2,150:1\n",
+            "{} failed",
+            function_name!()
+        );
+    }
+
+    #[test]
+    #[named]
+    fn parse_amplicon_record_test() {
+        let a = amplicon(PrimerSide::Right, PrimerSide::Left, 10, 20);
+        let expected = Some(("p1".to_string(), "R-L".to_string(), 10));
+        for format in [
+            AmpliconFormat::Fasta,
+            AmpliconFormat::Bed,
+            AmpliconFormat::Gff3,
+        ] {
+            let record = format_amplicon(&a, format);
+            assert!(
+                parse_amplicon_record(record.lines().next().unwrap()) == expected,
+                "{} failed: {:?}",
+                function_name!(),
+                format
+            );
+        }
+        assert!(
+            parse_amplicon_record("ACGT").is_none()
+                && parse_amplicon_record("##gff-version 3").is_none(),
+            "{} failed",
+            function_name!()
+        );
+    }
+
+    #[test]
+    #[named]
+    fn call_length_bands_test() {
+        let lengths = [(98, 2), (100, 50), (101, 10), (150, 30), (300, 1)]
+            .into_iter()
+            .collect();
+        let bands = call_length_bands(&lengths, 5, 0.05);
+        assert!(
+            bands
+                == vec![
+                    LengthBand {
+                        length: 100,
+                        min_length: 98,
+                        max_length: 101,
+                        count: 62
+                    },
+                    LengthBand {
+                        length: 150,
+                        min_length: 150,
+                        max_length: 150,
+                        count: 30
+                    },
+                ],
+            "{} failed: {:?}",
+            function_name!(),
+            bands
+        );
+        let histogram = text_histogram(&lengths, 100, 10);
+        assert!(
+            histogram.lines().count() == 4
+                && histogram.lines().nth(1).unwrap().ends_with(&"#".repeat(10)),
+            "{} failed: {}",
+            function_name!(),
+            histogram
+        );
+    }
+}
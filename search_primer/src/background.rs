@@ -0,0 +1,127 @@
+// 背景(非標的ゲノムやリード)での出現回数を候補のLR-tupleについて正確に数え、多すぎるものを除く。
+// 背景はA, C, G, T以外の塩基で区切ってから数える。
+
+use crate::counting_bloomfilter_util::{add_count, count_background_lr_tuple};
+use crate::sequence_encoder_util::{acgt_segments, DnaSequence};
+use bio::io::fasta::FastaRead;
+use bio::io::fasta::Reader as faReader;
+use bio::io::fasta::Record as faRecord;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io;
+
+/// Records of a FASTA file split at every base other than A, C, G and T.
+pub fn load_acgt_segments(path: &str) -> io::Result<Vec<DnaSequence>> {
+    let with_path = |e: io::Error| io::Error::new(e.kind(), format!("{}: {}", path, e));
+    let mut reader = faReader::new(File::open(path).map_err(with_path)?);
+    let mut record: faRecord = faRecord::new();
+    let mut sequences: Vec<DnaSequence> = Vec::new();
+    loop {
+        reader.read(&mut record).map_err(with_path)?;
+        if record.is_empty() {
+            break;
+        }
+        for segment in acgt_segments(record.seq()) {
+            sequences.push(DnaSequence::new(&segment.to_vec()));
+        }
+    }
+    eprintln!("loading {:?} done: {} sequences", path, sequences.len());
+    Ok(sequences)
+}
+
+/// Removes the tuples counted more than `max_background` times over all
+/// `background_files`, and returns how many were removed.
+pub fn remove_background<T, F: Fn(&T) -> u128>(
+    tuples: &mut Vec<T>,
+    key: F,
+    background_files: &[String],
+    max_background: u16,
+    threads: usize,
+    margin: usize,
+) -> io::Result<usize> {
+    let candidates: HashSet<u128> = tuples.iter().map(&key).collect();
+    let mut background_counts: HashMap<u128, u16> = HashMap::new();
+    for background_file in background_files {
+        let background: Vec<DnaSequence> = load_acgt_segments(background_file)?;
+        let counts: HashMap<u128, u16> =
+            count_background_lr_tuple(&background, &candidates, threads, margin);
+        eprintln!(
+            "background {:?}: {} sequences, {} candidates found",
+            background_file,
+            background.len(),
+            counts.len()
+        );
+        for (tuple, count) in counts {
+            add_count(&mut background_counts, tuple, count);
+        }
+    }
+    let before: usize = tuples.len();
+    tuples.retain(|t| background_counts.get(&key(t)).copied().unwrap_or(0) <= max_background);
+    eprintln!(
+        "background removed {} of {} candidates (max_background: {}), {} remain",
+        before - tuples.len(),
+        before,
+        max_background,
+        tuples.len()
+    );
+    Ok(before - tuples.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::background::remove_background;
+    use crate::sequence_encoder_util::DnaSequence;
+    use ::function_name::named;
+    use std::io::Write;
+
+    #[test]
+    #[named]
+    fn remove_background_test() {
+        let target: Vec<u8> =
+            b"ACGTTGCAAGGCTTACCGTAGGATCCTTAGCAGTCAAGTCTTGACCATGCGATACGGTCAGTACCTGAAGCTTCGAT"
+                .to_vec();
+        let forward = DnaSequence::new(&target);
+        let head: u128 = forward.subsequence_as_u128(vec![[0, 32], [32, 64]]);
+        let tail: u128 = forward.subsequence_as_u128(vec![[13, 45], [45, 77]]);
+        // 背景には先頭64塩基だけがNを挟んで2回ある
+        let mut background = tempfile::NamedTempFile::new().unwrap();
+        writeln!(
+            background,
+            ">bg\n{}NNNN{}",
+            String::from_utf8_lossy(&target[..64]),
+            String::from_utf8_lossy(&target[..64])
+        )
+        .unwrap();
+        let files: Vec<String> = vec![background.path().to_str().unwrap().to_string()];
+        let mut tuples: Vec<u128> = vec![head, tail];
+        let removed = remove_background(&mut tuples, |t| *t, &files, 1, 2, 0).unwrap();
+        assert!(
+            removed == 1 && tuples == vec![tail],
+            "{} failed: {} {:?}",
+            function_name!(),
+            removed,
+            tuples
+        );
+        let mut tuples: Vec<u128> = vec![head, tail];
+        let removed = remove_background(&mut tuples, |t| *t, &files, 2, 2, 0).unwrap();
+        assert!(
+            removed == 0 && tuples.len() == 2,
+            "{} failed: {}",
+            function_name!(),
+            removed
+        );
+        let missing = remove_background(
+            &mut tuples,
+            |t| *t,
+            &["/nonexistent.fa".to_string()],
+            0,
+            2,
+            0,
+        );
+        assert!(
+            missing.is_err_and(|e| e.to_string().contains("/nonexistent.fa")),
+            "{} failed",
+            function_name!()
+        );
+    }
+}
@@ -0,0 +1,153 @@
+extern crate getopts;
+use getopts::Options;
+use search_primer::amplicon_util::{
+    band_tsv_header, band_tsv_line, call_length_bands, parse_amplicon_record, text_histogram,
+    PrimerPairSummary,
+};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::{env, process};
+
+fn print_usage(program: &str, opts: &Options) {
+    let brief = format!(
+        "Usage: {} -i EXTRACTED_PRODUCTS [options]\n\
+         EXTRACTED_PRODUCTS is the FASTA, BED or GFF3 output of extract_PCR_target_region.",
+        program
+    );
+    print!("{}", opts.usage(&brief));
+    process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let mut opts = Options::new();
+    opts.optopt(
+        "i",
+        "input",
+        "products written by extract_PCR_target_region.",
+        "FILE",
+    );
+    opts.optopt(
+        "o",
+        "output",
+        "set output file name. default value is amplicon_length_analysis.tsv",
+        "NAME",
+    );
+    opts.optopt(
+        "g",
+        "histogram",
+        "also write a plain-text length histogram per primer pair.",
+        "FILE",
+    );
+    opts.optopt(
+        "t",
+        "tolerance",
+        "lengths within this many bases are merged into one band. default value is 5.",
+        "BASES",
+    );
+    opts.optopt(
+        "m",
+        "min_fraction",
+        "bands with less than this fraction of the products are ignored. default value is 0.05.",
+        "FRACTION",
+    );
+    opts.optopt(
+        "b",
+        "bin",
+        "histogram bin width. default value is 10.",
+        "BASES",
+    );
+    opts.optflag("h", "help", "print this help menu");
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            eprintln!("{}", f);
+            print_usage(&program, &opts);
+            return;
+        }
+    };
+    if matches.opt_present("h") {
+        print_usage(&program, &opts);
+        return;
+    }
+    let input_file = match matches.opt_str("i") {
+        Some(i) => i,
+        None => {
+            print_usage(&program, &opts);
+            return;
+        }
+    };
+    let output_file = matches
+        .opt_str("o")
+        .unwrap_or_else(|| "amplicon_length_analysis.tsv".to_string());
+    let tolerance: usize = matches
+        .opt_str("t")
+        .unwrap_or("5".to_string())
+        .parse()
+        .unwrap();
+    let min_fraction: f64 = matches
+        .opt_str("m")
+        .unwrap_or("0.05".to_string())
+        .parse()
+        .unwrap();
+    let bin_width: usize = matches
+        .opt_str("b")
+        .unwrap_or("10".to_string())
+        .parse()
+        .unwrap();
+
+    let mut summaries: BTreeMap<String, PrimerPairSummary> = BTreeMap::new();
+    let reader = BufReader::new(File::open(&input_file).expect("Error during opening the file"));
+    let mut records: usize = 0;
+    for line in reader.lines() {
+        if let Some((primer_id, orientation, length)) = parse_amplicon_record(&line.unwrap()) {
+            summaries
+                .entry(primer_id)
+                .or_default()
+                .add(&orientation, length);
+            records += 1;
+        }
+    }
+    eprintln!("{} products of {} primer pairs", records, summaries.len());
+
+    // 向きごとの行と、全ての向きをまとめた"all"の行を出力する
+    let mut w = BufWriter::new(File::create(&output_file).unwrap());
+    w.write_all(band_tsv_header().as_bytes()).unwrap();
+    let mut multi_band_pairs: usize = 0;
+    for (primer_id, summary) in &summaries {
+        for (orientation, lengths) in &summary.orientation_lengths {
+            let bands = call_length_bands(lengths, tolerance, min_fraction);
+            w.write_all(band_tsv_line(primer_id, orientation, lengths, &bands).as_bytes())
+                .unwrap();
+        }
+        let bands = call_length_bands(&summary.lengths, tolerance, min_fraction);
+        if bands.len() > 1 {
+            multi_band_pairs += 1;
+        }
+        w.write_all(band_tsv_line(primer_id, "all", &summary.lengths, &bands).as_bytes())
+            .unwrap();
+    }
+    w.flush().unwrap();
+    eprintln!(
+        "{} of {} primer pairs yield multiple bands. output: {:?}",
+        multi_band_pairs,
+        summaries.len(),
+        output_file
+    );
+
+    if let Some(histogram_file) = matches.opt_str("g") {
+        let mut w = BufWriter::new(File::create(&histogram_file).unwrap());
+        for (primer_id, summary) in &summaries {
+            writeln!(w, "# {}\t{} products", primer_id, summary.total()).unwrap();
+            w.write_all(text_histogram(&summary.lengths, bin_width, 50).as_bytes())
+                .unwrap();
+            writeln!(w).unwrap();
+        }
+        w.flush().unwrap();
+        eprintln!("histogram: {:?}", histogram_file);
+    }
+}
@@ -0,0 +1,191 @@
+extern crate bio;
+extern crate getopts;
+use crate::bio::io::fasta::FastaRead;
+use bio::io::fasta::Reader as faReader;
+use bio::io::fasta::Record as faRecord;
+use getopts::Options;
+use search_primer::amplicon_profile::{
+    column_tsv_header, column_tsv_lines, normalize_orientation, primer_site_columns,
+    profile_amplicons, site_tsv_header, site_tsv_line, SiteColumn,
+};
+use search_primer::amplicon_util::parse_amplicon_record;
+use search_primer::in_silico_pcr::PrimerPair;
+use search_primer::primer_set::{load_primer_set, PrimerSetOptions};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::{env, process};
+
+fn print_usage(program: &str, opts: &Options) {
+    let brief = format!(
+        "Usage: {} -i EXTRACTED_PRODUCTS [options]\n\
+         EXTRACTED_PRODUCTS is the FASTA output of extract_PCR_target_region.",
+        program
+    );
+    print!("{}", opts.usage(&brief));
+    process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let mut opts = Options::new();
+    opts.optopt(
+        "i",
+        "input",
+        "amplicon FASTA written by extract_PCR_target_region.",
+        "FILE",
+    );
+    opts.optopt(
+        "p",
+        "primer",
+        "primer pairs (TSV, CSV or FASTA). without it the binding sites are not profiled.",
+        "TSV",
+    );
+    opts.optopt(
+        "o",
+        "output",
+        "output prefix; writes PREFIX.consensus.fa, PREFIX.columns.tsv and PREFIX.sites.tsv. default value is amplicon_profile",
+        "PREFIX",
+    );
+    opts.optopt(
+        "m",
+        "min_fraction",
+        "bases seen in at least this fraction of a column enter the IUPAC consensus. default value is 0.1.",
+        "FRACTION",
+    );
+    opts.optopt(
+        "s",
+        "snp_frequency",
+        "binding-site columns with at least this variant frequency are reported. default value is 0.05.",
+        "FRACTION",
+    );
+    opts.optopt(
+        "w",
+        "three_prime",
+        "pairs with a variant site within this many bases of a 3' end are rejected. default value is 5.",
+        "BASES",
+    );
+    opts.optflag("h", "help", "print this help menu");
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            eprintln!("{}", f);
+            print_usage(&program, &opts);
+            return;
+        }
+    };
+    if matches.opt_present("h") {
+        print_usage(&program, &opts);
+        return;
+    }
+    let input_file = match matches.opt_str("i") {
+        Some(i) => i,
+        None => {
+            print_usage(&program, &opts);
+            return;
+        }
+    };
+    let prefix = matches
+        .opt_str("o")
+        .unwrap_or_else(|| "amplicon_profile".to_string());
+    let min_fraction: f64 = matches
+        .opt_str("m")
+        .unwrap_or("0.1".to_string())
+        .parse()
+        .unwrap();
+    let snp_frequency: f64 = matches
+        .opt_str("s")
+        .unwrap_or("0.05".to_string())
+        .parse()
+        .unwrap();
+    let three_prime: usize = matches
+        .opt_str("w")
+        .unwrap_or("5".to_string())
+        .parse()
+        .unwrap();
+    let primers: HashMap<String, PrimerPair> = match matches.opt_str("p") {
+        Some(primer_file) => load_primer_set(&primer_file, &PrimerSetOptions::default())
+            .unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                process::exit(1);
+            })
+            .into_iter()
+            .map(|pair| (pair.id.clone(), pair))
+            .collect(),
+        None => HashMap::new(),
+    };
+
+    // L-RとR-Lの産物だけを左プライマー側から読む向きに揃え、プライマーペアごとにまとめる
+    eprintln!("loading {:?}", &input_file);
+    let mut reader: faReader<BufReader<File>> =
+        faReader::new(File::open(&input_file).expect("Error during opening the file"));
+    let mut record: faRecord = faRecord::new();
+    let mut amplicons: BTreeMap<String, Vec<Vec<u8>>> = BTreeMap::new();
+    let mut skipped: usize = 0;
+    'each_read: loop {
+        reader.read(&mut record).unwrap();
+        if record.is_empty() {
+            break 'each_read;
+        }
+        let normalized = parse_amplicon_record(&format!(">{}", record.id())).and_then(
+            |(primer_id, orientation, _)| {
+                normalize_orientation(&orientation, record.seq()).map(|s| (primer_id, s))
+            },
+        );
+        match normalized {
+            Some((primer_id, sequence)) => amplicons.entry(primer_id).or_default().push(sequence),
+            None => skipped += 1,
+        }
+    }
+    eprintln!(
+        "{} primer pairs, {} products skipped (not L-R or R-L)",
+        amplicons.len(),
+        skipped
+    );
+
+    let mut consensus_writer =
+        BufWriter::new(File::create(format!("{}.consensus.fa", prefix)).unwrap());
+    let mut column_writer =
+        BufWriter::new(File::create(format!("{}.columns.tsv", prefix)).unwrap());
+    let mut site_writer = BufWriter::new(File::create(format!("{}.sites.tsv", prefix)).unwrap());
+    column_writer
+        .write_all(column_tsv_header().as_bytes())
+        .unwrap();
+    site_writer.write_all(site_tsv_header().as_bytes()).unwrap();
+    for (primer_id, sequences) in &amplicons {
+        let profile = profile_amplicons(primer_id, sequences);
+        let sites: Vec<SiteColumn> = match primers.get(primer_id) {
+            Some(pair) => primer_site_columns(&profile, &pair.left, &pair.right),
+            None => Vec::new(),
+        };
+        writeln!(
+            consensus_writer,
+            ">{} amplicons={} distinct={}\n{}",
+            primer_id,
+            profile.amplicons,
+            profile.distinct,
+            String::from_utf8_lossy(&profile.consensus(min_fraction))
+        )
+        .unwrap();
+        column_writer
+            .write_all(column_tsv_lines(&profile, &sites, min_fraction).as_bytes())
+            .unwrap();
+        site_writer
+            .write_all(site_tsv_line(&profile, &sites, snp_frequency, three_prime).as_bytes())
+            .unwrap();
+        eprintln!(
+            "{}: {} amplicons, {} distinct, mean entropy {:.3}",
+            primer_id,
+            profile.amplicons,
+            profile.distinct,
+            profile.mean_entropy()
+        );
+    }
+    consensus_writer.flush().unwrap();
+    column_writer.flush().unwrap();
+    site_writer.flush().unwrap();
+    eprintln!("output: {}.{{consensus.fa,columns.tsv,sites.tsv}}", prefix);
+}
@@ -12,6 +12,7 @@ use search_primer::counting_bloomfilter_util::{
 use search_primer::counting_bloomfilter_util::{HASHSET_SIZE, L_LEN, R_LEN};
 use search_primer::sequence_encoder_util::decode_u128_2_dna_seq;
 use search_primer::sequence_encoder_util::DnaSequence;
+use search_primer::tuple_record::{write_tuple_records, LrTuple, TupleRecord};
 use std::cmp::{max, min};
 use std::collections::HashMap;
 use std::collections::HashSet;
@@ -232,8 +233,6 @@ fn main() {
 
     let mut previous_lr_tuple: u128 = 0;
     let mut cnt = 0;
-    let mut buf_array: [u8; 16] = [0; 16];
-    let mut buf_num: u128;
 
     if matches.opt_present("r") {
         eprintln!(
@@ -263,23 +262,11 @@ fn main() {
         for each_lr_tuple in &high_occurence_lr_tuple {
             if previous_lr_tuple != *each_lr_tuple {
                 cnt += 1;
-                buf_num = *each_lr_tuple;
-                for i in 0..16 {
-                    buf_array[15 - i] = u8::try_from(buf_num & 0xFF).unwrap();
-                    buf_num >>= 8;
-                }
-                w1.write(&buf_array).unwrap();
+                w1.write_all(&LrTuple(*each_lr_tuple).to_bytes()).unwrap();
             }
             previous_lr_tuple = *each_lr_tuple;
         }
-        for each_sorted_hs_list in &sorted_hs_list {
-            buf_num = *each_sorted_hs_list;
-            for i in 0..16 {
-                buf_array[15 - i] = u8::try_from(buf_num & 0xFF).unwrap();
-                buf_num >>= 8;
-            }
-            w2.write(&buf_array).unwrap();
-        }
+        write_tuple_records(&mut w2, sorted_hs_list.iter().map(|v| LrTuple(*v))).unwrap();
     }
 
     if !matches.opt_present("r") && !matches.opt_present("b") {
@@ -1,20 +1,18 @@
 extern crate bio;
 extern crate getopts;
-extern crate rdxsort;
 use crate::bio::io::fasta::FastaRead;
 use bio::io::fasta::Reader as faReader;
 use bio::io::fasta::Record as faRecord;
-use core::any::Any;
 use getopts::Options;
-use search_primer::counting_bloomfilter_util::aggregate_length_between_lr_tuple;
-use search_primer::sequence_encoder_util::DnaSequence;
-// use std::any::Any;
+use search_primer::amplicon_util::{
+    format_amplicon, summarize_amplicons, summary_tsv_header, summary_tsv_line, AmpliconFormat,
+};
+use search_primer::in_silico_pcr::{run_in_silico_pcr, Amplicon, PcrParams, PrimerPair};
+use search_primer::primer_set::{expand_primer_pair, load_primer_set, PrimerSetOptions, TrimRule};
 use std::env;
 use std::fs::File;
 use std::io::BufReader;
-use std::io::{BufRead, Write};
-use std::sync::Arc;
-use std::thread;
+use std::io::{BufWriter, Write};
 
 fn print_usage(program: &str, opts: &Options) {
     let brief = format!("Usage: {}", program);
@@ -27,7 +25,19 @@ fn main() -> Result<(), String> {
     let mut opts = Options::new();
     opts.optopt("o", "output", "set output file name", "NAME");
     opts.optopt("r", "read", "set NGS read file name", "READ");
-    opts.optopt("t", "tsv", "set TSV file name", "TSV");
+    opts.optopt(
+        "t",
+        "tsv",
+        "set primer pair file name (TSV, CSV or FASTA)",
+        "TSV",
+    );
+    opts.optopt(
+        "",
+        "trim_left",
+        "trimming rule for left primers: 3p:N keeps N bases at the 3' end, 5p:N removes N bases from the 5' end",
+        "RULE",
+    );
+    opts.optopt("", "trim_right", "trimming rule for right primers", "RULE");
 
     opts.optopt(
         "T",
@@ -36,6 +46,24 @@ fn main() -> Result<(), String> {
         "THREAD",
     );
     opts.optopt("l", "length", "maximum size of target region", "LENGTH");
+    opts.optopt(
+        "f",
+        "format",
+        "output format: fasta (default), bed or gff3",
+        "FORMAT",
+    );
+    opts.optopt(
+        "s",
+        "summary",
+        "write per-primer-pair hit counts and product length distribution (TSV)",
+        "SUMMARY",
+    );
+    opts.optopt(
+        "k",
+        "mismatch",
+        "maximum number of mismatches in each primer binding site. Default is 0.",
+        "K",
+    );
 
     opts.optflag("h", "help", "print this help menu");
 
@@ -72,6 +100,13 @@ fn main() -> Result<(), String> {
         .unwrap_or("200".to_string())
         .parse()
         .unwrap();
+    let output_format: AmpliconFormat =
+        AmpliconFormat::parse(&matches.opt_str("f").unwrap_or("fasta".to_string()))?;
+    let max_mismatches: u32 = matches
+        .opt_str("k")
+        .unwrap_or("0".to_string())
+        .parse()
+        .unwrap();
 
     eprintln!("Output file name: {:?}", output_file);
     eprintln!("NGS read file name: {:?}", ngsread_file);
@@ -81,123 +116,78 @@ fn main() -> Result<(), String> {
         "Maximum target region length: {:?}",
         max_offtarget_region_length
     );
+    eprintln!("Output format: {:?}", output_format);
 
-    let mut primer_tuple: Vec<(Vec<u8>, DnaSequence, DnaSequence)> = Vec::new();
-
-    let f: File = File::open(&tsv_file).unwrap();
-    let reader: BufReader<File> = BufReader::new(f);
-    let mut lines: std::io::Lines<BufReader<File>> = reader.lines();
-    lines.next(); // ヘッダー行をスキップ
-
-    lines.for_each(|line| {
-        //eprintln!("line: {:?}", line);
-        if let Ok(line) = line {
-            // eprintln!("line: {:?}", line);
-            let columns: Vec<&str> = line.split('\t').collect();
-            let primer_id: &[u8] = columns.get(0).unwrap_or(&"").as_bytes();
-            let left_primer_seq: &str = columns.get(1).unwrap_or(&"");
-            let right_primer_seq: &str = columns.get(2).unwrap_or(&"");
-            /*
-            入力TSVのLプライマーはリードと同じstrand、Rはrevcompとする。
-            R側のプライマーの読み込み時にrevcompに変換する。
-            リードの座標を基準に5to3か3to5か決める。
-            5'---> 3'
-             */
-            let left_primer_5to3: DnaSequence = DnaSequence::new(&left_primer_seq.into());
-            let right_primer_3to5: DnaSequence =
-                DnaSequence::new(&right_primer_seq.into()).reverse_complement();
-            let left_primer_3to5: DnaSequence = left_primer_5to3.reverse_complement();
-            let right_primer_5to3: DnaSequence = right_primer_3to5.reverse_complement();
-            let id_1: Vec<u8> = [&primer_id, &b"L-L"[..]].concat();
-            let id_2: Vec<u8> = [&primer_id, &b"L-R"[..]].concat();
-            let id_3: Vec<u8> = [&primer_id, &b"R-L"[..]].concat();
-            let id_4: Vec<u8> = [&primer_id, &b"R-R"[..]].concat();
-
-            primer_tuple.push((id_1, left_primer_5to3.clone(), left_primer_3to5.clone()));
-            primer_tuple.push((id_2, left_primer_5to3.clone(), right_primer_3to5.clone()));
-            primer_tuple.push((id_3, right_primer_5to3.clone(), left_primer_3to5.clone()));
-            primer_tuple.push((id_4, right_primer_5to3.clone(), right_primer_3to5.clone()));
-        }
-    });
-
-    eprintln!("Number of primer_tuple: {:?}", &primer_tuple.len());
     /*
-    for each_primer_tuple in &primer_tuple {
-        eprintln!(
-            "primer_tuple: {}",
-            String::from_utf8(each_primer_tuple.1.decode(0, each_primer_tuple.1.len())).unwrap()
-        );
-        eprintln!(
-            "primer_tuple: {}",
-            String::from_utf8(each_primer_tuple.2.decode(0, each_primer_tuple.2.len())).unwrap()
-        );
+    入力のLプライマーはリードと同じstrand、Rはrevcompとする。
+    in_silico_pcrがL/Rそれぞれの順鎖・逆鎖の組み合わせ(L-L, L-R, R-L, R-R)を全て探す。
+    縮重塩基を含むプライマーは展開してから探す。
+     */
+    let primer_set_options = PrimerSetOptions {
+        trim_left: TrimRule::parse(&matches.opt_str("trim_left").unwrap_or_default())?,
+        trim_right: TrimRule::parse(&matches.opt_str("trim_right").unwrap_or_default())?,
+        ..PrimerSetOptions::default()
+    };
+    let primer_pairs: Vec<PrimerPair> = load_primer_set(&tsv_file, &primer_set_options)?;
+    let mut search_pairs: Vec<PrimerPair> = Vec::new();
+    for pair in &primer_pairs {
+        search_pairs.extend(expand_primer_pair(pair)?);
     }
-    */
+
+    eprintln!(
+        "Number of primer pairs: {:?} ({:?} after expanding degenerate bases)",
+        &primer_pairs.len(),
+        &search_pairs.len()
+    );
+    let params = PcrParams {
+        max_mismatches,
+        max_weighted_mismatches: max_mismatches as f64,
+        max_product_size: max_offtarget_region_length,
+        ..PcrParams::default()
+    };
+
     let ngsread_file_obj: File = File::open(&ngsread_file).expect("Error during opening the file");
     eprintln!("loading {:?}", &ngsread_file);
     let mut reader: faReader<BufReader<File>> = faReader::new(ngsread_file_obj);
     let mut record: faRecord = faRecord::new();
-    let mut sequences: Vec<DnaSequence> = Vec::new();
-    eprintln!("loading {:?} done", &ngsread_file);
+    let mut sequences: Vec<(String, Vec<u8>)> = Vec::new();
     'each_read: loop {
         reader.read(&mut record).unwrap();
         if record.is_empty() {
             break 'each_read;
         }
-        let sequence_as_vec: Vec<u8> = record.seq().to_vec();
-        let current_sequence: DnaSequence = DnaSequence::new(&sequence_as_vec);
-        sequences.push(current_sequence);
+        sequences.push((record.id().to_string(), record.seq().to_ascii_uppercase()));
     }
-    let chunk_size: usize = sequences.len() / (threads - 1);
-    let sequences_ref: Arc<Vec<DnaSequence>> = Arc::new(sequences);
-    let primer_tuple_ref: Arc<Vec<(Vec<u8>, DnaSequence, DnaSequence)>> = Arc::new(primer_tuple);
-    //let mut cbf_oyadama: Vec<u32> = vec![0;BLOOMFILTER_TABLE_SIZE];
+    eprintln!("loading {:?} done", &ngsread_file);
+    // 縮重プライマーの展開で同じ産物が重複して見つかることがあるので、run_in_silico_pcrが1つにまとめる
+    let amplicons: Vec<Amplicon> = run_in_silico_pcr(&sequences, &search_pairs, &params, threads);
 
-    //let mut product_size_hashmap = HashMap::<u32, usize>::new();
-    thread::scope(|scope| {
-        let mut children_1: Vec<thread::ScopedJoinHandle<'_, Vec<u8>>> = Vec::new();
-        for i in 1..threads {
-            let sequences_ref: Arc<Vec<DnaSequence>> = Arc::clone(&sequences_ref);
-            let primer_tuple_ref: Arc<Vec<(Vec<u8>, DnaSequence, DnaSequence)>> =
-                Arc::clone(&primer_tuple_ref);
-            children_1.push(scope.spawn(move || {
-                let start_idx: usize = (i - 1) * chunk_size;
-                let end_idx: usize;
-                if i != threads - 1 {
-                    end_idx = i * chunk_size;
-                } else {
-                    end_idx = sequences_ref.len() - 1;
-                }
-                let primer_tuple_ref_mine: Vec<(Vec<u8>, DnaSequence, DnaSequence)> =
-                    (*primer_tuple_ref).clone();
-                let slice_sequences: Vec<DnaSequence> =
-                    Vec::from(sequences_ref[start_idx..end_idx].to_vec());
+    eprintln!("start  writing to output file: {:?}", &output_file);
+    let mut w = BufWriter::new(File::create(&output_file).unwrap());
+    if let Some(header) = output_format.header() {
+        w.write_all(header.as_bytes()).unwrap();
+    }
+    for amplicon in &amplicons {
+        w.write_all(format_amplicon(amplicon, output_format).as_bytes())
+            .unwrap();
+    }
+    w.flush().unwrap();
+    eprintln!("finish writing to output file: {:?}", &output_file);
 
-                eprintln!(
-                    "start calling aggregate_length_between_primer_tuple[{}], # of sequence: {}",
-                    i,
-                    &slice_sequences.len()
-                );
-                let cbf: Vec<u8> = aggregate_length_between_lr_tuple(
-                    &slice_sequences,
-                    i,
-                    &primer_tuple_ref_mine,
-                    max_offtarget_region_length,
-                );
-                eprintln!(
-                    "finish calling aggregate_length_between_primer_tuple[{}]",
-                    i
-                );
-                return cbf;
-            }))
-        }
-        eprintln!("start  writing to output file: {:?}", &output_file);
-        let mut file: File = File::create(&output_file).unwrap();
-        for child in children_1 {
-            let result: Result<Vec<u8>, Box<dyn Any + Send>> = child.join(); // Resultを返さず、直接戻り値を取得
-            file.write_all(&result.unwrap()).unwrap();
+    if let Some(summary_file) = matches.opt_str("s") {
+        let summary = summarize_amplicons(&amplicons);
+        let mut w = BufWriter::new(File::create(&summary_file).unwrap());
+        w.write_all(summary_tsv_header().as_bytes()).unwrap();
+        // ヒットしなかったプライマーも0件として出力する
+        for pair in &primer_pairs {
+            let line = match summary.get(&pair.id) {
+                Some(s) => summary_tsv_line(&pair.id, s),
+                None => summary_tsv_line(&pair.id, &Default::default()),
+            };
+            w.write_all(line.as_bytes()).unwrap();
         }
-        eprintln!("finish writing to output file: {:?}", &output_file);
-    });
+        w.flush().unwrap();
+        eprintln!("finish writing summary: {:?}", &summary_file);
+    }
     Ok(())
 }
@@ -0,0 +1,160 @@
+extern crate bio;
+extern crate getopts;
+use crate::bio::io::fasta::FastaRead;
+use bio::io::fasta::Reader as faReader;
+use bio::io::fasta::Record as faRecord;
+use getopts::Options;
+use search_primer::amplicon_util::{format_amplicon, AmpliconFormat};
+use search_primer::in_silico_pcr::{run_in_silico_pcr, Amplicon, PcrParams, PrimerPair};
+use search_primer::primer_set::{expand_primer_pair, load_primer_set, PrimerSetOptions};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::{env, process};
+
+fn print_usage(program: &str, opts: &Options) {
+    let brief = format!("Usage: {} -p PRIMER_TSV -r FASTA [options]", program);
+    print!("{}", opts.usage(&brief));
+    process::exit(1);
+}
+
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let (min, max) = range.split_once('-')?;
+    Some((min.parse().ok()?, max.parse().ok()?))
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let mut opts = Options::new();
+    opts.optopt("p", "primer", "primer pairs (TSV, CSV or FASTA; degenerate bases are expanded).", "TSV");
+    opts.optopt("r", "read", "reads or reference sequences (FASTA).", "FASTA");
+    opts.optopt("o", "output", "set output file name. default value is in_silico_pcr.tsv", "NAME");
+    opts.optopt("k", "mismatch", "maximum number of mismatches in a primer binding site. default value is 0.", "K");
+    opts.optopt(
+        "c",
+        "clamp",
+        "mismatches within this many bases from the 3' end are weighted by closeness to the 3' end. default value is 5.",
+        "CLAMP",
+    );
+    opts.optopt(
+        "w",
+        "max_weight",
+        "maximum sum of mismatch weights. default value is K.",
+        "WEIGHT",
+    );
+    opts.optopt("l", "product_size", "product size range. default value is 0-200.", "MIN-MAX");
+    opts.optopt("T", "thread", "number of threads to use. default value is 8.", "THREAD");
+    opts.optflag(
+        "f",
+        "fasta",
+        "write amplicons as FASTA instead of TSV, with the same headers as extract_PCR_target_region.",
+    );
+    opts.optflag("h", "help", "print this help menu");
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            eprintln!("{}", f);
+            print_usage(&program, &opts);
+            return;
+        }
+    };
+    if matches.opt_present("h") {
+        print_usage(&program, &opts);
+        return;
+    }
+    let (primer_file, fasta_file) = match (matches.opt_str("p"), matches.opt_str("r")) {
+        (Some(p), Some(r)) => (p, r),
+        _ => {
+            print_usage(&program, &opts);
+            return;
+        }
+    };
+    let output_file = matches
+        .opt_str("o")
+        .unwrap_or_else(|| "in_silico_pcr.tsv".to_string());
+    let max_mismatches: u32 = matches.opt_str("k").unwrap_or("0".to_string()).parse().unwrap();
+    let three_prime_clamp: usize = matches.opt_str("c").unwrap_or("5".to_string()).parse().unwrap();
+    let max_weighted_mismatches: f64 = matches
+        .opt_str("w")
+        .map(|w| w.parse().unwrap())
+        .unwrap_or(max_mismatches as f64);
+    let (min_product_size, max_product_size) = parse_range(&matches.opt_str("l").unwrap_or("0-200".to_string()))
+        .unwrap_or_else(|| {
+            eprintln!("product size range must be MIN-MAX");
+            process::exit(1);
+        });
+    let threads: usize = matches.opt_str("T").unwrap_or("8".to_string()).parse().unwrap();
+    let params = PcrParams {
+        max_mismatches,
+        three_prime_clamp,
+        max_weighted_mismatches,
+        min_product_size,
+        max_product_size,
+    };
+    eprintln!("{:?}", params);
+
+    let mut primer_pairs: Vec<PrimerPair> = Vec::new();
+    let loaded = load_primer_set(&primer_file, &PrimerSetOptions::default()).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    for pair in &loaded {
+        primer_pairs.extend(expand_primer_pair(pair).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        }));
+    }
+    eprintln!("Number of primer pairs: {}", primer_pairs.len());
+
+    let mut reader = faReader::new(File::open(&fasta_file).expect("Error during opening the file"));
+    let mut record = faRecord::new();
+    let mut templates: Vec<(String, Vec<u8>)> = Vec::new();
+    loop {
+        reader.read(&mut record).unwrap();
+        if record.is_empty() {
+            break;
+        }
+        templates.push((record.id().to_string(), record.seq().to_ascii_uppercase()));
+    }
+    eprintln!("loading {:?} done: {} sequences", fasta_file, templates.len());
+
+    // 縮重プライマーの展開で同じ産物が重複して見つかることがあるので、run_in_silico_pcrが1つにまとめる
+    let amplicons: Vec<Amplicon> = run_in_silico_pcr(&templates, &primer_pairs, &params, threads);
+    eprintln!("Number of amplicons: {}", amplicons.len());
+
+    let mut w = BufWriter::new(File::create(&output_file).unwrap());
+    if matches.opt_present("f") {
+        for a in &amplicons {
+            w.write_all(format_amplicon(a, AmpliconFormat::Fasta).as_bytes()).unwrap();
+        }
+    } else {
+        writeln!(
+            w,
+            "primer_id\torientation\ttemplate_id\tstart\tend\tlength\tforward_mismatches\treverse_mismatches\tforward_mismatch_distances\treverse_mismatch_distances\tsequence"
+        )
+        .unwrap();
+        let distances = |d: &[usize]| d.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(",");
+        for a in &amplicons {
+            writeln!(
+                w,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                a.pair_id,
+                a.orientation(),
+                a.template_id,
+                a.start + 1,
+                a.end,
+                a.len(),
+                a.forward.mismatches,
+                a.reverse.mismatches,
+                distances(&a.forward.mismatch_distances),
+                distances(&a.reverse.mismatch_distances),
+                String::from_utf8_lossy(&a.sequence)
+            )
+            .unwrap();
+        }
+    }
+    w.flush().unwrap();
+    eprintln!("finish writing to output file: {:?}", &output_file);
+}
@@ -0,0 +1,206 @@
+extern crate getopts;
+use getopts::Options;
+use search_primer::background::load_acgt_segments;
+use search_primer::count_matrix::{parse_labelled_file, CountMatrix, Sample};
+use search_primer::counting_bloomfilter_util::{
+    count_background_lr_tuple, count_candidate_lr_tuple, count_high_occurence_lr_tuple,
+    BLOOMFILTER_TABLE_SIZE,
+};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::{env, process};
+
+fn print_usage(program: &str, opts: &Options) {
+    let brief = format!(
+        "Usage: {} -t LABEL=FILE [-t LABEL=FILE ...] [-n LABEL=FILE ...] [options]\n\
+         Counts the lr-tuples passing the threshold in any target sample across all samples.\n\
+         PREFIX.bin holds one row per tuple: the tuple (16 bytes) and a u16 per sample in the\n\
+         column order of PREFIX.tsv, all big endian.",
+        program
+    );
+    print!("{}", opts.usage(&brief));
+    process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let mut opts = Options::new();
+    opts.optmulti(
+        "t",
+        "target",
+        "target read set as LABEL=FILE (FASTA). can be given more than once.",
+        "LABEL=FILE",
+    );
+    opts.optmulti(
+        "n",
+        "negative",
+        "negative (non-target) genomes or reads as LABEL=FILE (FASTA), scanned on both strands.",
+        "LABEL=FILE",
+    );
+    opts.optopt(
+        "o",
+        "output",
+        "output prefix; writes PREFIX.tsv and PREFIX.bin. default value is lr_tuple_count_matrix",
+        "PREFIX",
+    );
+    opts.optopt(
+        "T",
+        "thread",
+        "number of threads to use. default value is 8.",
+        "THREAD",
+    );
+    opts.optopt(
+        "a",
+        "threshold",
+        "threshold of occurence in a target sample. default value is 1000.",
+        "THRESHOLD",
+    );
+    opts.optopt(
+        "m",
+        "margin_size",
+        "margin between l and r segments. default value is 0.",
+        "MARGIN_SIZE",
+    );
+    opts.optopt(
+        "k",
+        "min_targets",
+        "keep tuples reaching the threshold in at least this many targets. default value is 1.",
+        "K",
+    );
+    opts.optopt(
+        "x",
+        "max_negative",
+        "keep tuples counted at most this often in every negative. default value is 0.",
+        "COUNT",
+    );
+    opts.optflag("h", "help", "print this help menu");
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            eprintln!("{}", f);
+            print_usage(&program, &opts);
+            return;
+        }
+    };
+    if matches.opt_present("h") || !matches.opt_present("t") {
+        print_usage(&program, &opts);
+        return;
+    }
+    let prefix = matches
+        .opt_str("o")
+        .unwrap_or_else(|| "lr_tuple_count_matrix".to_string());
+    let threads: usize = matches
+        .opt_str("T")
+        .unwrap_or("8".to_string())
+        .parse()
+        .unwrap();
+    let threshold: u16 = matches
+        .opt_str("a")
+        .unwrap_or("1000".to_string())
+        .parse()
+        .unwrap();
+    let margin: usize = matches
+        .opt_str("m")
+        .unwrap_or("0".to_string())
+        .parse()
+        .unwrap();
+    let min_targets: usize = matches
+        .opt_str("k")
+        .unwrap_or("1".to_string())
+        .parse()
+        .unwrap();
+    let max_negative: u16 = matches
+        .opt_str("x")
+        .unwrap_or("0".to_string())
+        .parse()
+        .unwrap();
+
+    let mut samples: Vec<Sample> = Vec::new();
+    let mut files: Vec<String> = Vec::new();
+    for (option, negative) in [("t", false), ("n", true)] {
+        for source in matches.opt_strs(option) {
+            let (label, file) = parse_labelled_file(&source);
+            if samples.iter().any(|s| s.label == label) {
+                eprintln!("duplicated sample label: {}", label);
+                process::exit(1);
+            }
+            samples.push(Sample { label, negative });
+            files.push(file);
+        }
+    }
+
+    // 標的サンプルごとに閾値を超えるLR-tupleを求め、その和集合を候補にする
+    let mut candidates: HashSet<u128> = HashSet::new();
+    for (sample, file) in samples.iter().zip(&files) {
+        if sample.negative {
+            continue;
+        }
+        let sequences = load_acgt_segments(file).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+        let passing = count_high_occurence_lr_tuple(
+            &sequences,
+            threshold,
+            threads,
+            margin,
+            BLOOMFILTER_TABLE_SIZE,
+        );
+        eprintln!(
+            "{}: {} lr-tuples reach the threshold {}",
+            sample.label,
+            passing.len(),
+            threshold
+        );
+        candidates.extend(passing.keys());
+    }
+    eprintln!("{} candidate lr-tuples over all targets", candidates.len());
+
+    // 全サンプルで候補を正確に数え直す。陰性は両鎖を数える
+    let mut counts: Vec<HashMap<u128, u16>> = Vec::new();
+    for (sample, file) in samples.iter().zip(&files) {
+        let sequences = load_acgt_segments(file).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+        let sample_counts = if sample.negative {
+            count_background_lr_tuple(&sequences, &candidates, threads, margin)
+        } else {
+            count_candidate_lr_tuple(&sequences, &candidates, threads, margin)
+        };
+        eprintln!(
+            "{}: {} candidates counted",
+            sample.label,
+            sample_counts.len()
+        );
+        counts.push(sample_counts);
+    }
+    let matrix = CountMatrix::new(samples, &counts);
+    let selected = matrix.select(threshold, min_targets, Some(max_negative));
+
+    let mut tsv_writer = BufWriter::new(File::create(format!("{}.tsv", prefix)).unwrap());
+    tsv_writer
+        .write_all(selected.tsv_header().as_bytes())
+        .unwrap();
+    for (tuple, row) in &selected.rows {
+        tsv_writer
+            .write_all(selected.tsv_line(threshold, *tuple, row).as_bytes())
+            .unwrap();
+    }
+    tsv_writer.flush().unwrap();
+    let mut binary_writer = BufWriter::new(File::create(format!("{}.bin", prefix)).unwrap());
+    selected.write_binary(&mut binary_writer).unwrap();
+    binary_writer.flush().unwrap();
+    eprintln!(
+        "{} of {} lr-tuples in at least {} targets and at most {} in every negative",
+        selected.rows.len(),
+        matrix.rows.len(),
+        min_targets,
+        max_negative
+    );
+    eprintln!("output: {}.{{tsv,bin}}", prefix);
+}
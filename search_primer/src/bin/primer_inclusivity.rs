@@ -0,0 +1,174 @@
+extern crate bio;
+extern crate getopts;
+use crate::bio::io::fasta::FastaRead;
+use bio::io::fasta::Reader as faReader;
+use bio::io::fasta::Record as faRecord;
+use getopts::Options;
+use search_primer::in_silico_pcr::{PcrParams, PrimerSide};
+use search_primer::primer_inclusivity::{tally_tsv_header, tally_tsv_line, PrimerTally};
+use search_primer::primer_set::{load_primer_set, PrimerSetOptions};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::{env, process, thread};
+
+fn print_usage(program: &str, opts: &Options) {
+    let brief = format!("Usage: {} -p PRIMERS -r READS [options]", program);
+    print!("{}", opts.usage(&brief));
+    process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let mut opts = Options::new();
+    opts.optopt(
+        "p",
+        "primer",
+        "designed primer pairs (TSV, CSV or FASTA).",
+        "TSV",
+    );
+    opts.optopt("r", "read", "read set (FASTA).", "FASTA");
+    opts.optopt(
+        "o",
+        "output",
+        "set output file name. default value is primer_inclusivity.tsv",
+        "NAME",
+    );
+    opts.optopt(
+        "k",
+        "mismatch",
+        "sites with up to this many mismatches are counted as carried by a read. default value is 6.",
+        "K",
+    );
+    opts.optopt(
+        "c",
+        "clamp",
+        "mismatches within this many bases of the 3' end weigh more. default value is 5.",
+        "BASES",
+    );
+    opts.optopt(
+        "w",
+        "binding_weight",
+        "a read is bound when its best site has at most this weighted mismatch count. default value is 2.",
+        "WEIGHT",
+    );
+    opts.optopt(
+        "T",
+        "thread",
+        "number of threads to use. default value is 8.",
+        "THREAD",
+    );
+    opts.optflag("h", "help", "print this help menu");
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            eprintln!("{}", f);
+            print_usage(&program, &opts);
+            return;
+        }
+    };
+    if matches.opt_present("h") {
+        print_usage(&program, &opts);
+        return;
+    }
+    let (primer_file, read_file) = match (matches.opt_str("p"), matches.opt_str("r")) {
+        (Some(p), Some(r)) => (p, r),
+        _ => {
+            print_usage(&program, &opts);
+            return;
+        }
+    };
+    let output_file = matches
+        .opt_str("o")
+        .unwrap_or_else(|| "primer_inclusivity.tsv".to_string());
+    let max_mismatches: u32 = matches
+        .opt_str("k")
+        .unwrap_or("6".to_string())
+        .parse()
+        .unwrap();
+    let three_prime_clamp: usize = matches
+        .opt_str("c")
+        .unwrap_or("5".to_string())
+        .parse()
+        .unwrap();
+    let binding_weight: f64 = matches
+        .opt_str("w")
+        .unwrap_or("2".to_string())
+        .parse()
+        .unwrap();
+    let threads: usize = matches
+        .opt_str("T")
+        .unwrap_or("8".to_string())
+        .parse()
+        .unwrap();
+    // 最もよく一致する窓を探すので、重みでは足切りしない
+    let params = PcrParams {
+        max_mismatches,
+        three_prime_clamp,
+        max_weighted_mismatches: f64::MAX,
+        ..PcrParams::default()
+    };
+
+    let primer_pairs =
+        load_primer_set(&primer_file, &PrimerSetOptions::default()).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+    let mut tallies: Vec<PrimerTally> = Vec::new();
+    for pair in &primer_pairs {
+        for side in [PrimerSide::Left, PrimerSide::Right] {
+            tallies.push(
+                PrimerTally::new(&pair.id, side, pair.primer(side)).unwrap_or_else(|e| {
+                    eprintln!("{}: {}", pair.id, e);
+                    process::exit(1);
+                }),
+            );
+        }
+    }
+    eprintln!("Number of primers: {}", tallies.len());
+
+    let mut reader = faReader::new(File::open(&read_file).expect("Error during opening the file"));
+    let mut record = faRecord::new();
+    let mut reads: Vec<Vec<u8>> = Vec::new();
+    loop {
+        reader.read(&mut record).unwrap();
+        if record.is_empty() {
+            break;
+        }
+        reads.push(record.seq().to_ascii_uppercase());
+    }
+    eprintln!("loading {:?} done: {} reads", read_file, reads.len());
+
+    let chunk_size: usize = reads.len().div_ceil(threads.max(1)).max(1);
+    thread::scope(|scope| {
+        let mut children = Vec::new();
+        for (i, chunk) in reads.chunks(chunk_size).enumerate() {
+            let mut local: Vec<PrimerTally> = tallies.clone();
+            let params = &params;
+            children.push(scope.spawn(move || {
+                for read in chunk {
+                    for tally in local.iter_mut() {
+                        tally.add_read(read, params, binding_weight);
+                    }
+                }
+                eprintln!("thread [{}]: {} reads done", i, chunk.len());
+                local
+            }));
+        }
+        for child in children {
+            for (tally, local) in tallies.iter_mut().zip(child.join().unwrap()) {
+                tally.merge(&local);
+            }
+        }
+    });
+
+    let mut w = BufWriter::new(File::create(&output_file).unwrap());
+    w.write_all(tally_tsv_header().as_bytes()).unwrap();
+    for tally in &tallies {
+        w.write_all(tally_tsv_line(tally).as_bytes()).unwrap();
+    }
+    w.flush().unwrap();
+    eprintln!("finish writing to output file: {:?}", &output_file);
+}
@@ -0,0 +1,224 @@
+extern crate bio;
+extern crate getopts;
+use crate::bio::io::fasta::FastaRead;
+use bio::io::fasta::Reader as faReader;
+use bio::io::fasta::Record as faRecord;
+use getopts::Options;
+use search_primer::amplicon_redesign::{
+    designed_set_tsv_header, designed_set_tsv_line, group_by_flanks, most_conserved_kmer,
+    parse_primer3_output, primer3_input, ConservedKmer,
+};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::process::{Command, Stdio};
+use std::{env, process, thread};
+
+fn print_usage(program: &str, opts: &Options) {
+    let brief = format!(
+        "Usage: {} -i EXTRACTED_PRODUCTS [options]\n\
+         EXTRACTED_PRODUCTS is the FASTA output of extract_PCR_target_region.",
+        program
+    );
+    print!("{}", opts.usage(&brief));
+    process::exit(1);
+}
+
+fn execute_primer3(formatted_string: String) -> String {
+    let mut process = match Command::new("primer3_core")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+    {
+        Err(why) => panic!("couldn't spawn primer3: {}", why),
+        Ok(process) => process,
+    };
+    // 出力を読みながら書き込まないと、入力が大きい時にパイプが詰まる
+    let mut stdin = process.stdin.take().unwrap();
+    let writer = thread::spawn(move || {
+        stdin
+            .write_all(formatted_string.as_bytes())
+            .expect("couldn't write to primer3_core stdin");
+    });
+    let output = process.wait_with_output().expect("Failed to wait on child");
+    writer.join().unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let mut opts = Options::new();
+    opts.optopt(
+        "i",
+        "input",
+        "amplicon FASTA written by extract_PCR_target_region.",
+        "FILE",
+    );
+    opts.optopt(
+        "o",
+        "output",
+        "set output file name. default value is redesign_primers_with_probe.p3in, or .tsv with -x",
+        "NAME",
+    );
+    opts.optopt(
+        "c",
+        "config",
+        "primer3_core settings appended to each record. PRIMER_TASK=pick_pcr_primers is replaced by pick_pcr_primers_and_hyb_probe.",
+        "CONFIG",
+    );
+    opts.optopt(
+        "f",
+        "flank",
+        "amplicons are grouped by this many bases at each end. default value is 24.",
+        "BASES",
+    );
+    opts.optopt(
+        "k",
+        "kmer",
+        "length of the probe k-mer (up to 64). default value is 40.",
+        "K",
+    );
+    opts.optopt(
+        "g",
+        "gap",
+        "number of Ns between the flanks and the probe in the primer3 template. default value is 20.",
+        "BASES",
+    );
+    opts.optopt(
+        "m",
+        "min_group",
+        "groups with fewer amplicons are skipped. default value is 1.",
+        "N",
+    );
+    opts.optflag(
+        "x",
+        "execute",
+        "run primer3_core and write the designed primer+probe sets (TSV) instead of primer3 input.",
+    );
+    opts.optflag("h", "help", "print this help menu");
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            eprintln!("{}", f);
+            print_usage(&program, &opts);
+            return;
+        }
+    };
+    if matches.opt_present("h") {
+        print_usage(&program, &opts);
+        return;
+    }
+    let input_file = match matches.opt_str("i") {
+        Some(i) => i,
+        None => {
+            print_usage(&program, &opts);
+            return;
+        }
+    };
+    let execute: bool = matches.opt_present("x");
+    let output_file = matches.opt_str("o").unwrap_or_else(|| {
+        if execute {
+            "redesign_primers_with_probe.tsv".to_string()
+        } else {
+            "redesign_primers_with_probe.p3in".to_string()
+        }
+    });
+    let flank: usize = matches
+        .opt_str("f")
+        .unwrap_or("24".to_string())
+        .parse()
+        .unwrap();
+    let k: usize = matches
+        .opt_str("k")
+        .unwrap_or("40".to_string())
+        .parse()
+        .unwrap();
+    if !(1..=64).contains(&k) {
+        eprintln!("k-mer length must be 1-64: {}", k);
+        process::exit(1);
+    }
+    let gap: usize = matches
+        .opt_str("g")
+        .unwrap_or("20".to_string())
+        .parse()
+        .unwrap();
+    let min_group: usize = matches
+        .opt_str("m")
+        .unwrap_or("1".to_string())
+        .parse()
+        .unwrap();
+    let config: Option<String> = matches.opt_str("c").map(|config_file| {
+        let mut config = String::new();
+        File::open(&config_file)
+            .expect("Error during opening the config file")
+            .read_to_string(&mut config)
+            .unwrap();
+        config
+    });
+
+    eprintln!("loading {:?}", &input_file);
+    let mut reader: faReader<BufReader<File>> =
+        faReader::new(File::open(&input_file).expect("Error during opening the file"));
+    let mut record: faRecord = faRecord::new();
+    let mut amplicons: Vec<(String, Vec<u8>)> = Vec::new();
+    'each_read: loop {
+        reader.read(&mut record).unwrap();
+        if record.is_empty() {
+            break 'each_read;
+        }
+        amplicons.push((record.id().to_string(), record.seq().to_vec()));
+    }
+    let groups = group_by_flanks(&amplicons, flank);
+    eprintln!(
+        "{} amplicons, {} groups by the first and last {} bases",
+        amplicons.len(),
+        groups.len(),
+        flank
+    );
+
+    // グループごとに中央部で最も保存されたk-merを選び、primer3の入力を作る
+    let mut designed: Vec<(usize, ConservedKmer)> = Vec::new();
+    let mut primer3_inputs = String::new();
+    for (index, group) in groups.iter().enumerate() {
+        if group.members.len() < min_group {
+            continue;
+        }
+        let kmer = match most_conserved_kmer(&group.members, flank, k) {
+            Some(kmer) => kmer,
+            None => {
+                eprintln!(
+                    "group {}: no {}-mer between the flanks in {} amplicons",
+                    group.id,
+                    k,
+                    group.members.len()
+                );
+                continue;
+            }
+        };
+        eprintln!(
+            "group {}: {} amplicons, probe {} in {} of them",
+            group.id,
+            group.members.len(),
+            String::from_utf8_lossy(&kmer.sequence),
+            kmer.amplicons
+        );
+        primer3_inputs += &primer3_input(group, &kmer.sequence, gap, config.as_deref());
+        designed.push((index, kmer));
+    }
+
+    let mut w = BufWriter::new(File::create(&output_file).unwrap());
+    if execute {
+        let records = parse_primer3_output(&execute_primer3(primer3_inputs));
+        w.write_all(designed_set_tsv_header().as_bytes()).unwrap();
+        for ((index, kmer), record) in designed.iter().zip(records.iter()) {
+            w.write_all(designed_set_tsv_line(&groups[*index], kmer, record).as_bytes())
+                .unwrap();
+        }
+    } else {
+        w.write_all(primer3_inputs.as_bytes()).unwrap();
+    }
+    w.flush().unwrap();
+    eprintln!("{} groups written to {:?}", designed.len(), &output_file);
+}
@@ -1,43 +1,185 @@
-use std::collections::HashSet;
+extern crate getopts;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use getopts::Options;
+use search_primer::aligner_util::{Aligner, BlastHit, BlastnAligner, LocalAligner};
+use sha2::{Digest, Sha256};
+use std::collections::{HashSet, VecDeque};
 use std::fs;
 use std::fs::File;
 use std::io::{BufRead, BufReader, BufWriter, Write};
-use std::path::Path;
-use std::process::Command;
-use std::sync::mpsc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::thread;
-use flate2::write::GzEncoder;
-use flate2::Compression;
+use std::{env, process};
+
+fn print_usage(program: &str, opts: &Options) {
+    let brief = format!(
+        "Usage: {} <tgt_db_list_filename> <taxon_id_to_be_ignored> <fasta_file> <slots> <output_dir> [options]",
+        program
+    );
+    print!("{}", opts.usage(&brief));
+    process::exit(1);
+}
+
+/// 1以上の数として読む。読めなければ使い方を表示して終了する
+fn parse_count(value: &str, name: &str, program: &str, opts: &Options) -> usize {
+    match value.parse::<usize>() {
+        Ok(n) if n > 0 => n,
+        _ => {
+            eprintln!("{} must be a positive integer: {:?}", name, value);
+            print_usage(program, opts);
+            process::exit(1);
+        }
+    }
+}
+
+/// 結果ファイルとそのキャッシュキー、または失敗の理由
+enum DbStatus {
+    Cached(PathBuf, String),
+    Done(PathBuf, String),
+    Failed(String),
+}
+
+/// クエリFASTAの中身・alignerの設定・無視するtaxidから決まるキャッシュキー
+fn cache_key(fasta_file: &str, aligner: &dyn Aligner, negative_taxon_ids: &HashSet<String>) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(fs::read(fasta_file).expect("failed to read query fasta"));
+    hasher.update(aligner.name().as_bytes());
+    let mut taxids: Vec<&String> = negative_taxon_ids.iter().collect();
+    taxids.sort();
+    for taxid in taxids {
+        hasher.update(taxid.as_bytes());
+        hasher.update(b"\n");
+    }
+    let digest = format!("{:x}", hasher.finalize());
+    digest[..16].to_string()
+}
+
+fn db_label(db: &str) -> String {
+    let base = Path::new(db)
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| db.to_string());
+    base.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// データベースのフルパスと、それを構成するファイルの大きさ・更新時刻を`key`に足したキー。
+/// blastnのデータベースは`nt`のような接頭辞なので、`nt`そのものと`nt.*`のファイルをすべて見る。
+fn db_cache_key(key: &str, db: &str) -> Result<String, String> {
+    let path = Path::new(db);
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let dir = fs::canonicalize(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let base = path
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .ok_or_else(|| format!("{}: not a database path", db))?;
+    let mut files: Vec<(String, u64, u128)> = Vec::new();
+    for entry in fs::read_dir(&dir).map_err(|e| format!("{}: {}", dir.display(), e))? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name != base && !name.starts_with(&format!("{}.", base)) {
+            continue;
+        }
+        let metadata = fs::metadata(entry.path()).map_err(|e| format!("{}: {}", name, e))?;
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_nanos());
+        files.push((name, metadata.len(), mtime));
+    }
+    if files.is_empty() {
+        return Err(format!("{}: no database files found", db));
+    }
+    files.sort();
+    let mut hasher = Sha256::new();
+    hasher.update(key.as_bytes());
+    hasher.update(dir.join(&base).to_string_lossy().as_bytes());
+    hasher.update(b"\n");
+    for (name, size, mtime) in files {
+        hasher.update(format!("{}\t{}\t{}\n", name, size, mtime).as_bytes());
+    }
+    let digest = format!("{:x}", hasher.finalize());
+    Ok(digest[..16].to_string())
+}
+
+fn search_one_db(
+    aligner: &dyn Aligner,
+    db: &str,
+    fasta_file: &str,
+    negative_taxon_ids: &HashSet<String>,
+    result_file: &Path,
+    exec_cmd_logfile: &Path,
+) -> Result<usize, String> {
+    let taxon_ids_in_db = aligner.db_taxids(db)?;
+    let mut negative_taxon_ids_for_this_db: Vec<String> = taxon_ids_in_db
+        .intersection(negative_taxon_ids)
+        .cloned()
+        .collect();
+    negative_taxon_ids_for_this_db.sort();
+
+    // 実行コマンドのログ
+    let mut f = File::create(exec_cmd_logfile).map_err(|e| e.to_string())?;
+    writeln!(
+        f,
+        "{}\tquery: {}\tdb: {}\tnegative_taxids: {}",
+        aligner.name(),
+        fasta_file,
+        db,
+        negative_taxon_ids_for_this_db.join(",")
+    )
+    .map_err(|e| e.to_string())?;
 
-fn get_db_taxids(db: &str) -> HashSet<String> {
-    let output = Command::new("blastdbcmd")
-        .args(&["-db", db, "-entry", "all", "-outfmt", "%T"])
-        .output()
-        .expect("failed to execute process");
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    stdout.lines().map(|s| s.to_string()).collect()
+    // ヒットは見つかった順に.partialへ書き、書き終わってからrenameするので、中断された結果が完了扱いされることはない
+    let partial_file = result_file.with_extension("partial");
+    let mut hits: usize = 0;
+    {
+        let f_out = File::create(&partial_file).map_err(|e| e.to_string())?;
+        let mut w = GzEncoder::new(BufWriter::new(f_out), Compression::default());
+        aligner.search(fasta_file, db, &negative_taxon_ids_for_this_db, &mut |hit: BlastHit| {
+            hits += 1;
+            writeln!(w, "{}", hit.to_tsv_line()).map_err(|e| e.to_string())
+        })?;
+        w.finish()
+            .and_then(|mut inner| inner.flush())
+            .map_err(|e| e.to_string())?;
+    }
+    fs::rename(&partial_file, result_file).map_err(|e| e.to_string())?;
+    Ok(hits)
 }
 
 fn call(
+    aligner: Arc<dyn Aligner>,
     tgt_db_list_filename: &str,
     taxon_id_to_be_ignored: &str,
     fasta_file: &str,
     output_dir: &str,
-    slots: usize,
-) {
+    jobs: usize,
+) -> bool {
     // negative_taxon_idsの読み込み
     let negative_taxon_ids: HashSet<String> = BufReader::new(File::open(taxon_id_to_be_ignored).unwrap())
         .lines()
         .map(|l| l.unwrap().trim().to_string())
+        .filter(|l| !l.is_empty())
         .collect();
 
-    // 出力ディレクトリとログディレクトリの作成
+    // 出力ディレクトリ・ログディレクトリ・データベースごとの結果ディレクトリの作成
     fs::create_dir_all(output_dir).expect("failed to create output directory");
     let log_dir = Path::new(output_dir).join("logs");
     fs::create_dir_all(&log_dir).expect("failed to create log directory");
+    let per_db_dir = Path::new(output_dir).join("per_db");
+    fs::create_dir_all(&per_db_dir).expect("failed to create per-database directory");
 
     // 出力ファイルの設定
     let output_file_path = Path::new(output_dir).join("blastn_results.gz");
+    let manifest_path = Path::new(output_dir).join("manifest.tsv");
 
     // データベースリストの読み込み
     let db_list: Vec<String> = BufReader::new(File::open(tgt_db_list_filename).unwrap())
@@ -52,72 +194,62 @@ fn call(
         })
         .collect();
 
-    // スレッドプール
-    let (tx, rx) = mpsc::channel();
-    let mut handles = vec![];
+    let key: String = cache_key(fasta_file, aligner.as_ref(), &negative_taxon_ids);
+    eprintln!("cache key: {}\taligner: {}", key, aligner.name());
+
+    // ワーカーはキューから1つずつデータベースを取り出す
+    let queue: Arc<Mutex<VecDeque<usize>>> = Arc::new(Mutex::new((0..db_list.len()).collect()));
+    let statuses: Arc<Mutex<Vec<Option<DbStatus>>>> =
+        Arc::new(Mutex::new((0..db_list.len()).map(|_| None).collect()));
+    let db_list: Arc<Vec<String>> = Arc::new(db_list);
+    let negative_taxon_ids: Arc<HashSet<String>> = Arc::new(negative_taxon_ids);
 
-    // 各データベースに対してblastnを実行
-    for (i, db) in db_list.iter().enumerate() {
-        let tx = tx.clone();
-        let db = db.clone();
+    let mut handles = vec![];
+    let jobs: usize = jobs.min(db_list.len()).max(1);
+    for worker_id in 0..jobs {
+        let queue = Arc::clone(&queue);
+        let statuses = Arc::clone(&statuses);
+        let db_list = Arc::clone(&db_list);
+        let negative_taxon_ids = Arc::clone(&negative_taxon_ids);
+        let aligner = Arc::clone(&aligner);
         let fasta_file = fasta_file.to_string();
-        let output_dir = output_dir.to_string();
         let log_dir = log_dir.clone();
-        let negative_taxon_ids = negative_taxon_ids.clone();
-
-        let handle = thread::spawn(move || {
-            // ログファイルの設定
-            let log_file = log_dir.join(format!("blastn_{}.log", i));
-            let exec_cmd_logfile = log_dir.join(format!("blastn_{}_exec_cmd.txt", i));
-
-            let temp_output = Path::new(&output_dir).join(format!("{}.temp.gz", db));
-
-            let taxon_ids_in_db = get_db_taxids(&db);
-            let negative_taxon_ids_for_this_db: Vec<String> = taxon_ids_in_db
-                .intersection(&negative_taxon_ids)
-                .cloned()
-                .collect();
-
-            let negative_taxids_arg = if !negative_taxon_ids_for_this_db.is_empty() {
-                format!("-negative_taxids {}", negative_taxon_ids_for_this_db.join(","))
-            } else {
-                "".to_string()
+        let per_db_dir = per_db_dir.clone();
+        let key = key.clone();
+        handles.push(thread::spawn(move || loop {
+            let i: usize = match queue.lock().unwrap().pop_front() {
+                Some(i) => i,
+                None => break,
             };
-
-            let execute_cmd = format!(
-                r#"echo {negative_taxids_arg} > {log_file:?}; \
-                /home/harazono/miniconda3/bin/blastn \
-                -task blastn-short \
-                -query {fasta_file:?} \
-                -db {db:?} \
-                -outfmt "6 qseqid sseqid sacc qlen qstart qend slen sstart send qseq sseq evalue length staxid staxids ssciname scomname" \
-                -num_threads {slots} \
-                -dust no \
-                -soft_masking false \
-                -word_size 15 \
-                -best_hit_overhang 0.01 \
-                -best_hit_score_edge 0.49 \
-                -perc_identity 100 \
-                {negative_taxids_arg} \
-                2>> {log_file:?} | gzip > {temp_output:?}
-            "#
-            );
-
-            // 実行コマンドのログ
-            let mut f = File::create(exec_cmd_logfile).unwrap();
-            writeln!(f, "{}", execute_cmd).unwrap();
-
-            // blastnの実行
-            let output = Command::new("sh")
-                .arg("-c")
-                .arg(execute_cmd)
-                .output()
-                .expect("failed to execute process");
-
-            // blastnの出力
-            tx.send((i, output)).unwrap();
-        });
-        handles.push(handle);
+            let db = &db_list[i];
+            let label = db_label(db);
+            let outcome: Result<DbStatus, String> = db_cache_key(&key, db).and_then(|db_key| {
+                let result_file = per_db_dir.join(format!("{}.{}.tsv.gz", label, db_key));
+                if result_file.exists() {
+                    eprintln!("worker[{:02}] {}: cached ({:?})", worker_id, db, result_file);
+                    return Ok(DbStatus::Cached(result_file, db_key));
+                }
+                eprintln!("worker[{:02}] {}: start", worker_id, db);
+                let exec_cmd_logfile = log_dir.join(format!("{}.{}_exec_cmd.txt", label, db_key));
+                let hit_count = search_one_db(
+                    aligner.as_ref(),
+                    db,
+                    &fasta_file,
+                    &negative_taxon_ids,
+                    &result_file,
+                    &exec_cmd_logfile,
+                )?;
+                eprintln!("worker[{:02}] {}: {} hits", worker_id, db, hit_count);
+                Ok(DbStatus::Done(result_file, db_key))
+            });
+            let status = outcome.unwrap_or_else(|e| {
+                eprintln!("worker[{:02}] {}: failed: {}", worker_id, db, e);
+                let err_logfile = log_dir.join(format!("{}.{}.err", label, key));
+                let _ = fs::write(err_logfile, &e);
+                DbStatus::Failed(e)
+            });
+            statuses.lock().unwrap()[i] = Some(status);
+        }));
     }
 
     // 各スレッドの終了を待つ
@@ -125,48 +257,289 @@ fn call(
         handle.join().unwrap();
     }
 
-    // blastnの結果を結合
-    let f_out = File::create(output_file_path).unwrap();
-    let mut f_out = BufWriter::new(GzEncoder::new(f_out, Compression::default()));
-    for _ in 0..db_list.len() {
-        let (i, output) = rx.recv().unwrap();
-        println!("{} in {}", i, db_list.len());
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            panic!("blastn failed: {}", stderr);
+    // 成功したデータベースの結果を結合し、manifestを書く
+    let f_out = File::create(&output_file_path).unwrap();
+    let mut f_out = GzEncoder::new(BufWriter::new(f_out), Compression::default());
+    let mut manifest = BufWriter::new(File::create(&manifest_path).unwrap());
+    writeln!(manifest, "db\tstatus\thits\tresult_file\tcache_key\terror").unwrap();
+    let mut failed: usize = 0;
+    for (db, status) in db_list.iter().zip(statuses.lock().unwrap().iter()) {
+        match status.as_ref().expect("database was never processed") {
+            DbStatus::Cached(path, db_key) | DbStatus::Done(path, db_key) => {
+                let mut hits: usize = 0;
+                let reader = BufReader::new(MultiGzDecoder::new(File::open(path).unwrap()));
+                for line in reader.lines() {
+                    writeln!(f_out, "{}", line.unwrap()).unwrap();
+                    hits += 1;
+                }
+                let state = if matches!(status, Some(DbStatus::Cached(..))) { "cached" } else { "done" };
+                writeln!(manifest, "{}\t{}\t{}\t{}\t{}\t", db, state, hits, path.display(), db_key).unwrap();
+            }
+            DbStatus::Failed(e) => {
+                failed += 1;
+                writeln!(
+                    manifest,
+                    "{}\tfailed\t0\t\t{}\t{}",
+                    db,
+                    key,
+                    e.replace(['\t', '\n'], " ")
+                )
+                .unwrap();
+            }
         }
-
-        // 一時ファイルの削除
-        let temp_output = Path::new(output_dir).join(format!("{}.temp.gz", db_list[i]));
-        let mut f_in = BufReader::new(File::open(temp_output.clone()).unwrap());
-        std::io::copy(&mut f_in, &mut f_out).unwrap();
-        fs::remove_file(temp_output).unwrap();
     }
+    f_out.finish().unwrap().flush().unwrap();
+    manifest.flush().unwrap();
+    eprintln!(
+        "{} databases, {} failed. merged: {:?}, manifest: {:?}",
+        db_list.len(),
+        failed,
+        output_file_path,
+        manifest_path
+    );
+    failed == 0
 }
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
 
-    if args.len() != 6 {
-        eprintln!(
-            "Usage: {} <tgt_db_list_filename> <taxon_id_to_be_ignored> <fasta_file> <slots> <output_dir>",
-            args[0]
-        );
-        std::process::exit(1);
+    let mut opts = Options::new();
+    opts.optopt(
+        "a",
+        "aligner",
+        "aligner backend: blastn (default) or local. local treats each database line as a FASTA file.",
+        "ALIGNER",
+    );
+    opts.optopt(
+        "x",
+        "taxid_map",
+        "seqid<TAB>taxid mapping for the local aligner.",
+        "TSV",
+    );
+    opts.optopt(
+        "w",
+        "word_size",
+        "seed length of the local aligner. default value is 15.",
+        "WORD_SIZE",
+    );
+    opts.optopt(
+        "j",
+        "jobs",
+        "number of databases searched at the same time. default value is the number of CPUs divided by slots (at least 1).",
+        "JOBS",
+    );
+    opts.optopt("", "blastn", "path to blastn.", "PATH");
+    opts.optflag("h", "help", "print this help menu");
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            eprintln!("{}", f);
+            print_usage(&program, &opts);
+            return;
+        }
+    };
+    if matches.opt_present("h") || matches.free.len() != 5 {
+        print_usage(&program, &opts);
+        return;
     }
 
-    let tgt_db_list_filename = &args[1];
-    let taxon_id_to_be_ignored = &args[2];
-    let fasta_file = &args[3];
-    let slots: usize = args[4].parse().unwrap();
-    let output_dir = &args[5];
+    let tgt_db_list_filename = &matches.free[0];
+    let taxon_id_to_be_ignored = &matches.free[1];
+    let fasta_file = &matches.free[2];
+    let slots: usize = parse_count(&matches.free[3], "slots", &program, &opts);
+    let output_dir = &matches.free[4];
+    let word_size: usize = matches
+        .opt_str("w")
+        .map_or(15, |w| parse_count(&w, "--word_size", &program, &opts));
+    // 指定がなければ、各blastnがslotsスレッドを使ってもCPUの数を超えない数のデータベースを同時に検索する
+    let jobs: usize = matches.opt_str("j").map_or_else(
+        || (thread::available_parallelism().map_or(1, |n| n.get()) / slots).max(1),
+        |j| parse_count(&j, "--jobs", &program, &opts),
+    );
+
+    let aligner: Arc<dyn Aligner> = match matches.opt_str("a").as_deref().unwrap_or("blastn") {
+        "blastn" => {
+            let mut blastn = BlastnAligner::new(slots);
+            blastn.word_size = word_size;
+            if let Some(path) = matches.opt_str("blastn") {
+                blastn.blastn_path = path;
+            }
+            Arc::new(blastn)
+        }
+        "local" => {
+            let taxid_map = matches.opt_str("x").unwrap_or_else(|| {
+                eprintln!("--taxid_map is required for the local aligner");
+                process::exit(1);
+            });
+            Arc::new(LocalAligner::from_taxid_map_file(word_size, &taxid_map).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                process::exit(1);
+            }))
+        }
+        other => {
+            eprintln!("unknown aligner: {}", other);
+            process::exit(1);
+        }
+    };
 
-    call(
+    let all_done = call(
+        aligner,
         tgt_db_list_filename,
         taxon_id_to_be_ignored,
         fasta_file,
         output_dir,
-        slots,
+        jobs,
     );
-}
\ No newline at end of file
+    if !all_done {
+        process::exit(2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{cache_key, call, db_cache_key};
+    use ::function_name::named;
+    use flate2::read::MultiGzDecoder;
+    use search_primer::aligner_util::LocalAligner;
+    use std::collections::{HashMap, HashSet};
+    use std::fs;
+    use std::fs::File;
+    use std::io::{BufRead, BufReader};
+    use std::sync::Arc;
+
+    const QUERY: &str = ">q1\nACGTTGCAAGGCTTACCGTA\n";
+    const SUBJECT: &str = ">subject_1\nTTTTTTACGTTGCAAGGCTTACCGTATTTTT\n";
+
+    #[test]
+    #[named]
+    fn cache_key_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let query = dir.path().join("query.fa");
+        let query = query.to_str().unwrap();
+        fs::write(query, QUERY).unwrap();
+        let aligner = LocalAligner::new(15, HashMap::new());
+        let taxids: HashSet<String> = ["9606".to_string()].into_iter().collect();
+        let key = cache_key(query, &aligner, &taxids);
+        assert!(key == cache_key(query, &aligner, &taxids), "{} failed", function_name!());
+        // taxid、alignerの設定、クエリのどれが変わってもキーが変わる
+        let other_taxids = cache_key(query, &aligner, &HashSet::new());
+        let other_aligner = cache_key(query, &LocalAligner::new(12, HashMap::new()), &taxids);
+        fs::write(query, ">q1\nACGTTGCAAGGCTTACCGTT\n").unwrap();
+        let other_query = cache_key(query, &aligner, &taxids);
+        assert!(
+            key != other_taxids && key != other_aligner && key != other_query,
+            "{} failed: {} {} {} {}",
+            function_name!(),
+            key,
+            other_taxids,
+            other_aligner,
+            other_query
+        );
+    }
+
+    #[test]
+    #[named]
+    fn db_cache_key_test() {
+        let dirs = [tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap()];
+        let dbs: Vec<String> = dirs
+            .iter()
+            .map(|dir| {
+                let db = dir.path().join("nt");
+                fs::write(&db, SUBJECT).unwrap();
+                db.to_str().unwrap().to_string()
+            })
+            .collect();
+        let key = db_cache_key("query", &dbs[0]).unwrap();
+        // 同じ名前・中身でもパスが違えば別のデータベース
+        let other_path = db_cache_key("query", &dbs[1]).unwrap();
+        let other_query = db_cache_key("other", &dbs[0]).unwrap();
+        fs::write(format!("{}.00.nsq", dbs[0]), "volume").unwrap();
+        let with_volume = db_cache_key("query", &dbs[0]).unwrap();
+        assert!(
+            key != other_path && key != other_query && key != with_volume,
+            "{} failed: {} {} {} {}",
+            function_name!(),
+            key,
+            other_path,
+            other_query,
+            with_volume
+        );
+        let missing = db_cache_key("query", &format!("{}/missing", dirs[0].path().display()));
+        assert!(
+            missing.as_ref().is_err_and(|e| e.contains("no database files found")),
+            "{} failed: {:?}",
+            function_name!(),
+            missing
+        );
+    }
+
+    #[test]
+    #[named]
+    fn call_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        fs::write(path("query.fa"), QUERY).unwrap();
+        fs::write(path("db.fa"), SUBJECT).unwrap();
+        fs::write(path("taxids.txt"), "9606\n").unwrap();
+        fs::write(path("dbs.txt"), format!("{}\n{}\n", path("db.fa"), path("missing.fa"))).unwrap();
+        let run = |db_list: &str| {
+            call(
+                Arc::new(LocalAligner::new(15, HashMap::new())),
+                &path(db_list),
+                &path("taxids.txt"),
+                &path("query.fa"),
+                &path("out"),
+                2,
+            )
+        };
+        let manifest = || -> Vec<Vec<String>> {
+            fs::read_to_string(path("out/manifest.tsv"))
+                .unwrap()
+                .lines()
+                .map(|l| l.split('\t').map(|c| c.to_string()).collect())
+                .collect()
+        };
+        let merged = || -> usize {
+            BufReader::new(MultiGzDecoder::new(File::open(path("out/blastn_results.gz")).unwrap()))
+                .lines()
+                .count()
+        };
+
+        // 存在しないデータベースは失敗として記録し、ほかの結果は結合する
+        let all_done = run("dbs.txt");
+        let first = manifest();
+        assert!(
+            !all_done
+                && first.len() == 3
+                && first[1][..3] == [path("db.fa"), "done".to_string(), "1".to_string()]
+                && first[2][..3] == [path("missing.fa"), "failed".to_string(), "0".to_string()]
+                && first[2][5].contains("no database files found")
+                && merged() == 1,
+            "{} failed: {:?}",
+            function_name!(),
+            first
+        );
+        let err_logs = fs::read_dir(path("out/logs"))
+            .unwrap()
+            .filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().ends_with(".err"))
+            .count();
+        assert!(err_logs == 1, "{} failed: {}", function_name!(), err_logs);
+
+        // 2回目は結果ファイルが残っているので検索せずに使う
+        fs::write(path("dbs_ok.txt"), format!("{}\n", path("db.fa"))).unwrap();
+        let all_done = run("dbs_ok.txt");
+        let second = manifest();
+        assert!(
+            all_done
+                && second.len() == 2
+                && second[1][1] == "cached"
+                && second[1][3] == first[1][3]
+                && merged() == 1,
+            "{} failed: {:?}",
+            function_name!(),
+            second
+        );
+    }
+}
@@ -0,0 +1,210 @@
+// 複数サンプル(標的と陰性)のLR-tupleの出現回数を、tupleごとの行列にまとめる。
+// 行は少なくとも一つの標的サンプルで閾値を超えたtuple、列はサンプル。
+// バイナリは1行ごとにtuple(16 byte, big endian)とサンプル順のu16(big endian)を並べる。
+
+use crate::counting_bloomfilter_util::{L_LEN, R_LEN};
+use crate::sequence_encoder_util::decode_u128_2_dna_seq;
+use std::collections::{BTreeSet, HashMap};
+use std::io::{Read, Write};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub label: String,
+    /// Negative samples are the ones a marker must be absent from.
+    pub negative: bool,
+}
+
+/// Parses `LABEL=FILE`; a bare `FILE` is labelled with its file stem.
+pub fn parse_labelled_file(source: &str) -> (String, String) {
+    match source.split_once('=') {
+        Some((label, file)) if !label.is_empty() => (label.to_string(), file.to_string()),
+        _ => {
+            let stem = std::path::Path::new(source)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| source.to_string());
+            (stem, source.to_string())
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CountMatrix {
+    pub samples: Vec<Sample>,
+    /// Sorted by tuple; counts are in the order of `samples`.
+    pub rows: Vec<(u128, Vec<u16>)>,
+}
+
+impl CountMatrix {
+    /// Builds the matrix over every tuple counted in any sample. `counts` is
+    /// parallel to `samples`; tuples missing from a sample count as 0.
+    pub fn new(samples: Vec<Sample>, counts: &[HashMap<u128, u16>]) -> CountMatrix {
+        assert!(samples.len() == counts.len());
+        let tuples: BTreeSet<u128> = counts.iter().flat_map(|c| c.keys().copied()).collect();
+        let rows = tuples
+            .into_iter()
+            .map(|tuple| {
+                let row = counts
+                    .iter()
+                    .map(|c| c.get(&tuple).copied().unwrap_or(0))
+                    .collect();
+                (tuple, row)
+            })
+            .collect();
+        CountMatrix { samples, rows }
+    }
+
+    /// Number of target samples in which `row` reaches `threshold`.
+    pub fn targets_present(&self, row: &[u16], threshold: u16) -> usize {
+        self.samples
+            .iter()
+            .zip(row)
+            .filter(|(sample, &count)| !sample.negative && count >= threshold)
+            .count()
+    }
+
+    /// Largest count of `row` over the negative samples (0 without negatives).
+    pub fn negative_max(&self, row: &[u16]) -> u16 {
+        self.samples
+            .iter()
+            .zip(row)
+            .filter(|(sample, _)| sample.negative)
+            .map(|(_, &count)| count)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Keeps the tuples reaching `threshold` in at least `min_targets` target
+    /// samples and, with `max_negative`, counted at most that often in every negative.
+    pub fn select(
+        &self,
+        threshold: u16,
+        min_targets: usize,
+        max_negative: Option<u16>,
+    ) -> CountMatrix {
+        let rows = self
+            .rows
+            .iter()
+            .filter(|(_, row)| {
+                self.targets_present(row, threshold) >= min_targets
+                    && max_negative.is_none_or(|max| self.negative_max(row) <= max)
+            })
+            .cloned()
+            .collect();
+        CountMatrix {
+            samples: self.samples.clone(),
+            rows,
+        }
+    }
+
+    pub fn tsv_header(&self) -> String {
+        let labels: Vec<&str> = self.samples.iter().map(|s| s.label.as_str()).collect();
+        format!(
+            "lr_tuple\t{}\ttargets_present\tnegative_max\n",
+            labels.join("\t")
+        )
+    }
+
+    pub fn tsv_line(&self, threshold: u16, tuple: u128, row: &[u16]) -> String {
+        let counts: Vec<String> = row.iter().map(|c| c.to_string()).collect();
+        format!(
+            "{}\t{}\t{}\t{}\n",
+            String::from_utf8(decode_u128_2_dna_seq(&tuple, L_LEN + R_LEN)).unwrap(),
+            counts.join("\t"),
+            self.targets_present(row, threshold),
+            self.negative_max(row)
+        )
+    }
+
+    pub fn write_binary<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        for (tuple, row) in &self.rows {
+            w.write_all(&tuple.to_be_bytes())?;
+            for count in row {
+                w.write_all(&count.to_be_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads rows written by `write_binary` for the given samples.
+    pub fn read_binary<R: Read>(samples: Vec<Sample>, r: &mut R) -> Result<CountMatrix, String> {
+        let mut rows: Vec<(u128, Vec<u16>)> = Vec::new();
+        let mut tuple_buf: [u8; 16] = [0; 16];
+        let mut count_buf: [u8; 2] = [0; 2];
+        loop {
+            match r.read_exact(&mut tuple_buf) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.to_string()),
+            }
+            let mut row: Vec<u16> = Vec::with_capacity(samples.len());
+            for _ in 0..samples.len() {
+                r.read_exact(&mut count_buf)
+                    .map_err(|e| format!("row {}: {}", rows.len() + 1, e))?;
+                row.push(u16::from_be_bytes(count_buf));
+            }
+            rows.push((u128::from_be_bytes(tuple_buf), row));
+        }
+        Ok(CountMatrix { samples, rows })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::count_matrix::{parse_labelled_file, CountMatrix, Sample};
+    use ::function_name::named;
+    use std::collections::HashMap;
+
+    #[test]
+    #[named]
+    fn count_matrix_test() {
+        let samples = vec![
+            Sample {
+                label: "strain_a".to_string(),
+                negative: false,
+            },
+            Sample {
+                label: "strain_b".to_string(),
+                negative: false,
+            },
+            Sample {
+                label: "other".to_string(),
+                negative: true,
+            },
+        ];
+        let counts = vec![
+            HashMap::from([(1u128, 20u16), (2, 20), (3, 20)]),
+            HashMap::from([(1u128, 30u16), (3, 5)]),
+            HashMap::from([(2u128, 1u16)]),
+        ];
+        let matrix = CountMatrix::new(samples.clone(), &counts);
+        // 1は両株・陰性なし、2は陰性に出現、3は片方の株だけで閾値超え
+        let shared = matrix.select(10, 2, Some(0));
+        let specific = matrix.select(10, 1, Some(0));
+        assert!(
+            matrix.rows[1] == (2, vec![20, 0, 1])
+                && shared.rows.iter().map(|r| r.0).collect::<Vec<_>>() == vec![1]
+                && specific.rows.iter().map(|r| r.0).collect::<Vec<_>>() == vec![1, 3]
+                && matrix.select(10, 1, None).rows.len() == 3,
+            "{} failed: {:?}",
+            function_name!(),
+            matrix
+        );
+        let mut binary: Vec<u8> = Vec::new();
+        matrix.write_binary(&mut binary).unwrap();
+        let restored = CountMatrix::read_binary(samples.clone(), &mut binary.as_slice()).unwrap();
+        assert!(
+            binary.len() == 3 * (16 + 2 * 3) && restored == matrix,
+            "{} failed: {:?}",
+            function_name!(),
+            restored
+        );
+        assert!(
+            CountMatrix::read_binary(samples, &mut &binary[..20]).is_err()
+                && parse_labelled_file("a=x/b.fa") == ("a".to_string(), "x/b.fa".to_string())
+                && parse_labelled_file("x/b.fa") == ("b".to_string(), "x/b.fa".to_string()),
+            "{} failed",
+            function_name!()
+        );
+    }
+}
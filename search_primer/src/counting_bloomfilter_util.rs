@@ -9,12 +9,13 @@ use sha2::Digest;
 use sha2::Sha256;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::thread;
 use std::time::Instant;
 
 //全てのL, Rと、hash値を出力する
 //部分配列のdecoderを書き、テストする
-pub fn build_counting_bloom_filter(
-    sequences: &Vec<DnaSequence>,
+pub fn build_counting_bloom_filter<S: AsRef<DnaSequence>>(
+    sequences: &[S],
     start_idx: usize,
     end_idx: usize,
     cbf_size: usize,
@@ -39,7 +40,7 @@ pub fn build_counting_bloom_filter(
     let start_time = Instant::now();
     let mut previous_time = start_time.elapsed();
 
-    'each_read: for current_sequence in sequences[start_idx..end_idx].iter() {
+    'each_read: for current_sequence in sequences[start_idx..end_idx].iter().map(AsRef::as_ref) {
         let mut add_bloom_filter_cnt: usize = 0;
         let mut l_window_cnt: usize = 0;
         loop_cnt += 1;
@@ -140,15 +141,17 @@ pub fn build_counting_bloom_filter(
 //BLOOMFILTER_TABLE_SIZEの範囲内で柔軟にhash値を返すようにする。
 
 pub fn hash_from_u128(source: u128, table_size: usize) -> [u32; 8] {
+    // u128を[u8; 16]に変換
+    hash_from_bytes(&source.to_le_bytes(), table_size)
+}
+
+// counting bloom filterのhash関数族。sourceのsha256の32 byteを4 byteずつbig endianのu32にし、
+// table_sizeで割った余りを8個の添字にする。どのtupleもこの関数を通せば表の大きさを揃えられる。
+pub fn hash_from_bytes(source: &[u8], table_size: usize) -> [u32; 8] {
     let mut ret_val: [u32; 8] = [0; 8];
     let mut hasher = Sha256::new();
-    // u128を[u8; 16]に変換
-    let u8_array: [u8; 16] = source.to_le_bytes();
-    // eprintln!("{:?}", u8_array);
-    hasher.update(u8_array);
+    hasher.update(source);
     let result = hasher.finalize();
-    // eprintln!("{:?}", result);
-    // ここから疑惑
     for i in 0..8 {
         ret_val[i] = ((result[i * 4] as u32) << 24)
             | ((result[i * 4 + 1] as u32) << 16)
@@ -186,7 +189,7 @@ pub fn hash_from_u128_old(source: u128) -> [u32; 8] {
 }
 
 pub fn count_occurence_from_counting_bloomfilter_table(
-    counting_bloomfilter_table: &Vec<u16>,
+    counting_bloomfilter_table: &[u16],
     indice: [u32; 8],
 ) -> u16 {
     let mut retval: u16 = u16::MAX;
@@ -198,9 +201,9 @@ pub fn count_occurence_from_counting_bloomfilter_table(
     return retval;
 }
 
-pub fn number_of_high_occurence_lr_tuple(
-    source_table: &Vec<u16>,
-    sequences: &Vec<DnaSequence>,
+pub fn number_of_high_occurence_lr_tuple<S: AsRef<DnaSequence>>(
+    source_table: &[u16],
+    sequences: &[S],
     start_idx: usize,
     end_idx: usize,
     hash_size: usize,
@@ -218,7 +221,7 @@ pub fn number_of_high_occurence_lr_tuple(
     let start: Instant = Instant::now();
     let mut previous_time: std::time::Duration = start.elapsed();
     let mut loop_cnt: usize = 0;
-    'each_read: for current_sequence in sequences[start_idx..end_idx].iter() {
+    'each_read: for current_sequence in sequences[start_idx..end_idx].iter().map(AsRef::as_ref) {
         let mut add_bloom_filter_cnt: usize = 0;
         let mut l_window_cnt: usize = 0;
         loop_cnt += 1;
@@ -433,8 +436,82 @@ pub fn aggregate_length_between_lr_tuple(
     return ret_array;
 }
 
-pub fn count_lr_tuple_with_hashtable(
-    sequences: &Vec<DnaSequence>,
+/// Adds `value` to the count of `key`, saturating at `u16::MAX` instead of wrapping.
+pub fn add_count(counts: &mut HashMap<u128, u16>, key: u128, value: u16) {
+    let count = counts.entry(key).or_insert(0);
+    *count = count.saturating_add(value);
+}
+
+/// Every lr-tuple of a sequence, as the tuple with the start of its L window
+/// and the end of its R window. Windows with a repeat are skipped and the R
+/// window ends at most `CHUNK_MAX - R_LEN + margin` bases after the L window starts.
+pub struct LrTupleWindows<'a> {
+    sequence: &'a DnaSequence,
+    margin: usize,
+    l_window_start_idx: usize,
+    r_window_start_idx: Option<usize>,
+}
+
+impl<'a> LrTupleWindows<'a> {
+    pub fn new(sequence: &'a DnaSequence, margin: usize) -> LrTupleWindows<'a> {
+        LrTupleWindows {
+            sequence,
+            margin,
+            l_window_start_idx: 0,
+            r_window_start_idx: None,
+        }
+    }
+}
+
+impl Iterator for LrTupleWindows<'_> {
+    type Item = (u128, usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let l_window_end_idx: usize = self.l_window_start_idx + L_LEN;
+            if l_window_end_idx > self.sequence.len() {
+                return None;
+            }
+            let r_window_start_idx: usize = match self.r_window_start_idx {
+                Some(idx) => idx,
+                None => {
+                    let (l_has_repeat_bool, l_has_repeat_offset) = self
+                        .sequence
+                        .has_repeat(self.l_window_start_idx, l_window_end_idx);
+                    if l_has_repeat_bool {
+                        self.l_window_start_idx += l_has_repeat_offset + 1;
+                        continue;
+                    }
+                    l_window_end_idx + self.margin
+                }
+            };
+            let r_window_end_idx: usize = r_window_start_idx + R_LEN;
+            if r_window_end_idx > self.sequence.len()
+                || r_window_end_idx - self.l_window_start_idx > CHUNK_MAX - R_LEN + self.margin
+            {
+                self.l_window_start_idx += 1;
+                self.r_window_start_idx = None;
+                continue;
+            }
+            let (r_has_repeat_bool, r_has_repeat_offset) = self
+                .sequence
+                .has_repeat(r_window_start_idx, r_window_end_idx);
+            if r_has_repeat_bool {
+                self.r_window_start_idx = Some(r_window_start_idx + r_has_repeat_offset + 1);
+                continue;
+            }
+            self.r_window_start_idx = Some(r_window_start_idx + 1);
+            let lmr_string: u128 = self.sequence.subsequence_as_u128(vec![
+                [self.l_window_start_idx, l_window_end_idx],
+                [r_window_start_idx, r_window_end_idx],
+            ]);
+            return Some((lmr_string, self.l_window_start_idx, r_window_end_idx));
+        }
+    }
+}
+
+pub fn count_lr_tuple_with_hashtable<S: AsRef<DnaSequence>>(
+    sequences: &[S],
     start_idx: usize,
     end_idx: usize,
     high_occurence_lr_tuple: &HashSet<u128>,
@@ -451,95 +528,25 @@ pub fn count_lr_tuple_with_hashtable(
         "thread [{:02}] finish Allocating HashMap<u128, u16> where hash_size_to_allocate = {}",
         thread_id, hash_size_to_allocate
     );
-    let mut l_window_start_idx: usize;
-    let mut l_window_end_idx: usize;
-    let mut r_window_start_idx: usize;
-    let mut r_window_end_idx: usize;
 
     let start_time: Instant = Instant::now();
     let mut previous_time: std::time::Duration = start_time.elapsed();
     let mut loop_cnt: usize = 0;
 
-    'each_read: for current_sequence in sequences[start_idx..end_idx].iter() {
+    'each_read: for current_sequence in sequences[start_idx..end_idx].iter().map(AsRef::as_ref) {
         let mut add_hashmap_cnt: usize = 0;
-        let mut l_window_cnt: usize = 0;
         loop_cnt += 1;
-        l_window_start_idx = 0;
-        if current_sequence.len() < L_LEN || current_sequence.len() < R_LEN {
-            continue 'each_read;
-        }
-        'each_l_window: loop {
-            l_window_end_idx = l_window_start_idx + L_LEN;
-            if l_window_end_idx >= current_sequence.len() + 1 {
-                let end: std::time::Duration = start_time.elapsed();
-                eprintln!("hs loop[{:02}]({:05}-{:05},length is {})\t{:05?}({:.4}%)\tlength: {}\tsec: {}.{:03}\tadd_hashmap_cnt: {}\tl_window_cnt: {}, lr_tuple_hashmap.len():{}",
-                    thread_id,
-                    start_idx,
-                    end_idx,
-                    end_idx - start_idx,
-                    loop_cnt,
-                    loop_cnt as f64 / (end_idx - start_idx) as f64 * 100f64,
-                    current_sequence.len(),
-                    end.as_secs() - previous_time.as_secs(),
-                    end.subsec_millis() - previous_time.subsec_millis(),
-                    add_hashmap_cnt,
-                    l_window_cnt,
-                    lr_tuple_hashmap.len()
-                );
-                previous_time = end;
-                continue 'each_read;
+        for (lmr_string, _, _) in LrTupleWindows::new(current_sequence, margin) {
+            add_hashmap_cnt += 1;
+            if lr_tuple_hashmap.len() > high_occurence_lr_tuple.len() {
+                break 'each_read;
             }
-            l_window_cnt += 1;
-            let (l_has_repeat_bool, l_has_repeat_offset) =
-                current_sequence.has_repeat(l_window_start_idx, l_window_end_idx);
-            if l_has_repeat_bool {
-                l_window_start_idx += l_has_repeat_offset + 1;
-                continue 'each_l_window;
-            }
-            r_window_start_idx = l_window_end_idx + margin;
-            'each_r_window: loop {
-                r_window_end_idx = r_window_start_idx + R_LEN;
-                if r_window_end_idx > current_sequence.len() {
-                    l_window_start_idx += 1;
-                    continue 'each_l_window;
-                }
-                if r_window_end_idx - l_window_start_idx > CHUNK_MAX - R_LEN + margin {
-                    break 'each_r_window;
-                }
-                let (r_has_repeat_bool, r_has_repeat_offset) =
-                    current_sequence.has_repeat(r_window_start_idx, r_window_end_idx);
-                if r_has_repeat_bool {
-                    r_window_start_idx += r_has_repeat_offset + 1;
-                    continue 'each_r_window;
-                }
-                add_hashmap_cnt += 1;
-                let lmr_string: u128 = current_sequence.subsequence_as_u128(vec![
-                    [l_window_start_idx, l_window_end_idx],
-                    [r_window_start_idx, r_window_end_idx],
-                ]);
-                /*                 eprintln!(
-                                   "lr_tuple_hashmap.len(): {}\t(HASHSET_SIZE as f32 * 0.9).round() as usize: {}",
-                                    lr_tuple_hashmap.len(),
-                                   (HASHSET_SIZE as f32 * 0.9).round() as usize
-                                );
-                */
-                if lr_tuple_hashmap.len() > high_occurence_lr_tuple.len() {
-                    break 'each_read;
-                }
-                if high_occurence_lr_tuple.contains(&lmr_string) {
-                    *lr_tuple_hashmap.entry(lmr_string).or_insert(0) += 1;
-                }
-                /*                 eprintln!(
-                                    "lr_tuple_hashmap.entry(lmr_string):{}\tthreshold: {}",
-                                    lr_tuple_hashmap[&lmr_string], threshold
-                                );
-                */
-                r_window_start_idx += 1;
+            if high_occurence_lr_tuple.contains(&lmr_string) {
+                add_count(&mut lr_tuple_hashmap, lmr_string, 1);
             }
-            l_window_start_idx += 1;
         }
         let end: std::time::Duration = start_time.elapsed();
-        eprintln!("hs loop[{:02}]({:05}-{:05},length is {})\t{:05?}({:.4}%)\tlength: {}\tsec: {}.{:03}\tadd_hashmap_cnt: {}\tl_window_cnt: {}, lr_tuple_hashmap.len():{}",
+        eprintln!("hs loop[{:02}]({:05}-{:05},length is {})\t{:05?}({:.4}%)\tlength: {}\tsec: {}.{:03}\tadd_hashmap_cnt: {}\tlr_tuple_hashmap.len():{}",
             thread_id,
             start_idx,
             end_idx,
@@ -550,10 +557,314 @@ pub fn count_lr_tuple_with_hashtable(
             end.as_secs() - previous_time.as_secs(),
             end.subsec_millis() - previous_time.subsec_millis(),
             add_hashmap_cnt,
-            l_window_cnt,
             lr_tuple_hashmap.len()
         );
         previous_time = end;
     }
     return lr_tuple_hashmap;
 }
+
+/// Runs the whole counting pipeline of `search_primer` on one read set with
+/// `threads` threads: a CBF of `cbf_size` counters, the tuples the CBF puts at or
+/// above `threshold`, then exact counts that drop the CBF false positives.
+pub fn count_high_occurence_lr_tuple<S: AsRef<DnaSequence> + Sync>(
+    sequences: &[S],
+    threshold: u16,
+    threads: usize,
+    margin: usize,
+    cbf_size: usize,
+) -> HashMap<u128, u16> {
+    if sequences.is_empty() {
+        return HashMap::new();
+    }
+    let chunk_size: usize = sequences.len().div_ceil(threads.max(1));
+    let mut cbf: Vec<u16> = vec![0; cbf_size];
+    thread::scope(|scope| {
+        let mut children = Vec::new();
+        for (i, chunk) in sequences.chunks(chunk_size).enumerate() {
+            children.push(scope.spawn(move || {
+                build_counting_bloom_filter(chunk, 0, chunk.len(), cbf_size, i, margin)
+            }));
+        }
+        for child in children {
+            cbf.iter_mut()
+                .zip(child.join().unwrap())
+                .for_each(|(x, y)| *x = x.saturating_add(y));
+        }
+    });
+    let mut high_occurence_lr_tuple: HashSet<u128> = HashSet::new();
+    let cbf_ref: &[u16] = &cbf;
+    thread::scope(|scope| {
+        let mut children = Vec::new();
+        for (i, chunk) in sequences.chunks(chunk_size).enumerate() {
+            children.push(scope.spawn(move || {
+                number_of_high_occurence_lr_tuple(
+                    cbf_ref,
+                    chunk,
+                    0,
+                    chunk.len(),
+                    cbf_size / 2,
+                    threshold,
+                    cbf_size,
+                    i,
+                    margin,
+                )
+            }));
+        }
+        for child in children {
+            high_occurence_lr_tuple.extend(child.join().unwrap());
+        }
+    });
+    drop(cbf);
+    let mut counts: HashMap<u128, u16> =
+        count_candidate_lr_tuple(sequences, &high_occurence_lr_tuple, threads, margin);
+    counts.retain(|_, count| *count >= threshold);
+    counts
+}
+
+/// Exact counts of the `candidates` on the given strand of every sequence,
+/// as `search_primer` counts reads. Counts saturate at `u16::MAX`.
+pub fn count_candidate_lr_tuple<S: AsRef<DnaSequence> + Sync>(
+    sequences: &[S],
+    candidates: &HashSet<u128>,
+    threads: usize,
+    margin: usize,
+) -> HashMap<u128, u16> {
+    let mut counts: HashMap<u128, u16> = HashMap::new();
+    if sequences.is_empty() || candidates.is_empty() {
+        return counts;
+    }
+    let chunk_size: usize = sequences.len().div_ceil(threads.max(1));
+    thread::scope(|scope| {
+        let mut children = Vec::new();
+        for (i, chunk) in sequences.chunks(chunk_size).enumerate() {
+            children.push(scope.spawn(move || {
+                count_lr_tuple_with_hashtable(chunk, 0, chunk.len(), candidates, i, margin)
+            }));
+        }
+        for child in children {
+            for (key, value) in child.join().unwrap() {
+                add_count(&mut counts, key, value);
+            }
+        }
+    });
+    counts
+}
+
+/// Every occurrence of the `candidates` in `sequence`, as the tuple with the
+/// start of its L window and the end of its R window.
+pub fn locate_lr_tuple(
+    sequence: &DnaSequence,
+    candidates: &HashSet<u128>,
+    margin: usize,
+) -> Vec<(u128, usize, usize)> {
+    LrTupleWindows::new(sequence, margin)
+        .filter(|(lmr_string, _, _)| candidates.contains(lmr_string))
+        .collect()
+}
+
+/// Exact counts of the `candidates` in a background set. Both strands of every
+/// sequence are scanned, so a candidate is found whichever strand a genome or
+/// read carries it on. Counts saturate at `u16::MAX`.
+pub fn count_background_lr_tuple(
+    sequences: &[DnaSequence],
+    candidates: &HashSet<u128>,
+    threads: usize,
+    margin: usize,
+) -> HashMap<u128, u16> {
+    let mut counts: HashMap<u128, u16> = HashMap::new();
+    if sequences.is_empty() || candidates.is_empty() {
+        return counts;
+    }
+    let chunk_size: usize = sequences.len().div_ceil(threads.max(1));
+    thread::scope(|scope| {
+        let mut children = Vec::new();
+        for (i, chunk) in sequences.chunks(chunk_size).enumerate() {
+            children.push(scope.spawn(move || {
+                let reverse: Vec<DnaSequence> =
+                    chunk.iter().map(|s| s.reverse_complement()).collect();
+                let mut local: HashMap<u128, u16> =
+                    count_lr_tuple_with_hashtable(chunk, 0, chunk.len(), candidates, i, margin);
+                for (key, value) in
+                    count_lr_tuple_with_hashtable(&reverse, 0, reverse.len(), candidates, i, margin)
+                {
+                    add_count(&mut local, key, value);
+                }
+                local
+            }));
+        }
+        for child in children {
+            for (key, value) in child.join().unwrap() {
+                add_count(&mut counts, key, value);
+            }
+        }
+    });
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::counting_bloomfilter_util::{
+        add_count, count_background_lr_tuple, count_high_occurence_lr_tuple, hash_from_bytes,
+        hash_from_u128, locate_lr_tuple, LrTupleWindows,
+    };
+    use crate::sequence_encoder_util::DnaSequence;
+    use ::function_name::named;
+    use std::collections::{HashMap, HashSet};
+
+    #[test]
+    #[named]
+    fn hash_from_bytes_distribution_test() {
+        // eval_cbfと同じく0..limitを表に入れ、各値の出現数が1からずれる割合を見る
+        let limit: usize = 10_000;
+        let table_size: usize = 1 << 20;
+        let mut table: Vec<u16> = vec![0; table_size];
+        for source in 0..limit {
+            for index in hash_from_u128(source as u128, table_size) {
+                table[index as usize] += 1;
+            }
+        }
+        let not_one: usize = (0..limit)
+            .filter(|&source| {
+                hash_from_u128(source as u128, table_size)
+                    .iter()
+                    .map(|&index| table[index as usize])
+                    .min()
+                    != Some(1)
+            })
+            .count();
+        assert!(
+            not_one * 1000 < limit,
+            "{} failed: {} of {}",
+            function_name!(),
+            not_one,
+            limit
+        );
+
+        // 下位bitも使われている(最後の1 byteを落とすと256で割った余りが常に0になる)
+        let buckets: usize = 256;
+        let mut counts: Vec<usize> = vec![0; buckets];
+        for source in 0..limit {
+            for index in hash_from_bytes(&(source as u64).to_be_bytes(), buckets) {
+                counts[index as usize] += 1;
+            }
+        }
+        let expected: f64 = (limit * 8) as f64 / buckets as f64;
+        let chi_square: f64 = counts
+            .iter()
+            .map(|&c| (c as f64 - expected).powi(2) / expected)
+            .sum();
+        // 自由度255のカイ二乗分布の99.9%点はおよそ330
+        assert!(
+            chi_square < 330.0,
+            "{} failed: {} {:?}",
+            function_name!(),
+            chi_square,
+            counts
+        );
+        assert!(
+            hash_from_u128(5, 1 << 30) == hash_from_bytes(&5u128.to_le_bytes(), 1 << 30),
+            "{} failed",
+            function_name!()
+        );
+    }
+
+    #[test]
+    #[named]
+    fn count_background_lr_tuple_test() {
+        let target: Vec<u8> =
+            b"ACGTTGCAAGGCTTACCGTAGGATCCTTAGCAGTCAAGTCTTGACCATGCGATACGGTCAGTACCTGAAGCTTCGAT"
+                .to_vec();
+        let forward = DnaSequence::new(&target);
+        let candidate: u128 = forward.subsequence_as_u128(vec![[0, 32], [32, 64]]);
+        let absent: u128 = forward.subsequence_as_u128(vec![[1, 33], [40, 72]]) ^ 1;
+        let candidates: HashSet<u128> = HashSet::from([candidate, absent]);
+        // 1本は同じ鎖、1本は逆鎖に候補を持つ
+        let background: Vec<DnaSequence> = vec![
+            forward.clone(),
+            forward.reverse_complement(),
+            DnaSequence::new(&b"ACGT".repeat(30)),
+        ];
+        let counts = count_background_lr_tuple(&background, &candidates, 2, 0);
+        assert!(
+            counts.get(&candidate) == Some(&2) && !counts.contains_key(&absent),
+            "{} failed: {:?}",
+            function_name!(),
+            counts
+        );
+    }
+
+    #[test]
+    #[named]
+    fn count_high_occurence_lr_tuple_test() {
+        let target: Vec<u8> =
+            b"ACGTTGCAAGGCTTACCGTAGGATCCTTAGCAGTCAAGTCTTGACCATGCGATACGGTCAGTACCTGAAGCTTCGAT"
+                .to_vec();
+        let forward = DnaSequence::new(&target);
+        // 先頭64塩基だけを3回、全体を1回含む
+        let mut sequences: Vec<DnaSequence> = vec![DnaSequence::new(&target[..64].to_vec()); 3];
+        sequences.push(forward.clone());
+        let counts = count_high_occurence_lr_tuple(&sequences, 3, 2, 0, 1 << 16);
+        let head: u128 = forward.subsequence_as_u128(vec![[0, 32], [32, 64]]);
+        assert!(
+            counts.len() == 1 && counts.get(&head) == Some(&4),
+            "{} failed: {:?}",
+            function_name!(),
+            counts
+        );
+    }
+
+    #[test]
+    #[named]
+    fn lr_tuple_windows_test() {
+        let target: Vec<u8> =
+            b"ACGTTGCAAGGCTTACCGTAGGATCCTTAGCAGTCAAGTCTTGACCATGCGATACGGTCAGTACCTGAAGCTTCGAT"
+                .to_vec();
+        let forward = DnaSequence::new(&target);
+        let windows: Vec<(u128, usize, usize)> = LrTupleWindows::new(&forward, 0).collect();
+        let head: u128 = forward.subsequence_as_u128(vec![[0, 32], [32, 64]]);
+        // 各tupleは位置どおりのL, Rからなり、最後のR windowは配列の末尾まで届く
+        assert!(
+            windows.first() == Some(&(head, 0, 64))
+                && windows.last().map(|w| w.2) == Some(target.len())
+                && windows.iter().all(|&(tuple, start, end)| tuple
+                    == forward.subsequence_as_u128(vec![[start, start + 32], [end - 32, end]])),
+            "{} failed: {:?}",
+            function_name!(),
+            windows
+        );
+        let located = locate_lr_tuple(&forward, &HashSet::from([head]), 0);
+        assert!(
+            located == vec![(head, 0, 64)],
+            "{} failed: {:?}",
+            function_name!(),
+            located
+        );
+        // marginの分だけLとRの間が空く
+        let with_margin: Vec<(u128, usize, usize)> = LrTupleWindows::new(&forward, 10).collect();
+        assert!(
+            !with_margin.is_empty() && with_margin.iter().all(|&(_, start, end)| end - start >= 74),
+            "{} failed: {:?}",
+            function_name!(),
+            with_margin
+        );
+    }
+
+    #[test]
+    #[named]
+    fn add_count_saturates_test() {
+        // 1スレッドでu16::MAXを超える数だけ数えても、折り返さずu16::MAXで止まる
+        let mut counts: HashMap<u128, u16> = HashMap::new();
+        for _ in 0..u16::MAX as usize + 10 {
+            add_count(&mut counts, 7, 1);
+        }
+        add_count(&mut counts, 8, u16::MAX - 1);
+        add_count(&mut counts, 8, 2);
+        assert!(
+            counts.get(&7) == Some(&u16::MAX) && counts.get(&8) == Some(&u16::MAX),
+            "{} failed: {:?}",
+            function_name!(),
+            counts
+        );
+    }
+}
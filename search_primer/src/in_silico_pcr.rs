@@ -0,0 +1,398 @@
+// 2bit表現したプライマーとテンプレートの窓をXORし、ミスマッチ位置を数える。
+// ミスマッチは3'末端からの距離で重み付けし、3'側のミスマッチほど伸長しにくいとみなす。
+
+use std::collections::HashSet;
+use std::thread;
+
+const EVEN_BITS: u128 = 0x5555_5555_5555_5555_5555_5555_5555_5555;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PrimerSide {
+    Left,
+    Right,
+}
+
+impl PrimerSide {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PrimerSide::Left => "L",
+            PrimerSide::Right => "R",
+        }
+    }
+}
+
+/// Strand of the template the primer sequence is found on.
+/// `Forward`: the window equals the primer (3' end on the right, extends rightwards).
+/// `Reverse`: the window equals the reverse complement (3' end on the left, extends leftwards).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Strand {
+    Forward,
+    Reverse,
+}
+
+impl Strand {
+    pub fn symbol(&self) -> char {
+        match self {
+            Strand::Forward => '+',
+            Strand::Reverse => '-',
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PrimerPair {
+    pub id: String,
+    pub left: Vec<u8>,
+    pub right: Vec<u8>,
+}
+
+impl PrimerPair {
+    pub fn primer(&self, side: PrimerSide) -> &[u8] {
+        match side {
+            PrimerSide::Left => &self.left,
+            PrimerSide::Right => &self.right,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PcrParams {
+    /// Maximum number of mismatching bases in a binding site.
+    pub max_mismatches: u32,
+    /// Mismatches within this many bases of the 3' end are weighted more heavily:
+    /// the 3'-terminal base weighs `three_prime_clamp`, the next one less, and so on down to 1.
+    pub three_prime_clamp: usize,
+    /// Maximum sum of mismatch weights.
+    pub max_weighted_mismatches: f64,
+    pub min_product_size: usize,
+    pub max_product_size: usize,
+}
+
+impl Default for PcrParams {
+    fn default() -> Self {
+        PcrParams {
+            max_mismatches: 0,
+            three_prime_clamp: 5,
+            max_weighted_mismatches: 0.0,
+            min_product_size: 0,
+            max_product_size: 200,
+        }
+    }
+}
+
+impl PcrParams {
+    /// Weight of a mismatch `distance` bases away from the 3' end (0 = terminal base).
+    pub fn mismatch_weight(&self, distance: usize) -> f64 {
+        if distance < self.three_prime_clamp {
+            (self.three_prime_clamp - distance) as f64
+        } else {
+            1.0
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BindingSite {
+    pub side: PrimerSide,
+    pub strand: Strand,
+    /// 0-based, half-open window on the template.
+    pub start: usize,
+    pub end: usize,
+    pub mismatches: u32,
+    pub weighted_mismatches: f64,
+    /// Distances of each mismatch from the primer's 3' end.
+    pub mismatch_distances: Vec<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Amplicon {
+    pub pair_id: String,
+    pub template_id: String,
+    pub forward: BindingSite,
+    pub reverse: BindingSite,
+    /// 0-based, half-open product coordinates on the template.
+    pub start: usize,
+    pub end: usize,
+    pub sequence: Vec<u8>,
+}
+
+impl Amplicon {
+    /// `L-R`, `R-L`, ... : sides of the forward and reverse primers, as in `extract_PCR_target_region`.
+    pub fn orientation(&self) -> String {
+        format!("{}-{}", self.forward.side.label(), self.reverse.side.label())
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.end == self.start
+    }
+}
+
+fn base_code(base: u8) -> Option<u128> {
+    match base {
+        b'A' | b'a' => Some(0),
+        b'C' | b'c' => Some(1),
+        b'G' | b'g' => Some(2),
+        b'T' | b't' => Some(3),
+        _ => None,
+    }
+}
+
+pub fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    seq.iter()
+        .rev()
+        .map(|b| match b {
+            b'A' | b'a' => b'T',
+            b'C' | b'c' => b'G',
+            b'G' | b'g' => b'C',
+            b'T' | b't' => b'A',
+            _ => b'N',
+        })
+        .collect()
+}
+
+/// Encodes a primer into right-aligned 2-bit codes plus a mask of bases that are
+/// not A/C/G/T (those always count as mismatches).
+fn encode_primer(primer: &[u8]) -> (u128, u128) {
+    assert!(
+        !primer.is_empty() && primer.len() <= 64,
+        "in_silico_pcr: primer length must be 1..=64, got {}",
+        primer.len()
+    );
+    let mut code: u128 = 0;
+    let mut invalid: u128 = 0;
+    for &b in primer {
+        code <<= 2;
+        invalid <<= 2;
+        match base_code(b) {
+            Some(c) => code |= c,
+            None => invalid |= 1,
+        }
+    }
+    (code, invalid)
+}
+
+/// All windows of `template` within the mismatch limits of `primer`, on both strands.
+pub fn find_binding_sites(template: &[u8], primer: &[u8], side: PrimerSide, params: &PcrParams) -> Vec<BindingSite> {
+    let len = primer.len();
+    let mut retval: Vec<BindingSite> = Vec::new();
+    if template.len() < len {
+        return retval;
+    }
+    let forward = encode_primer(primer);
+    let reverse = encode_primer(&reverse_complement(primer));
+    let window_mask: u128 = if len == 64 { u128::MAX } else { (1u128 << (2 * len)) - 1 };
+
+    let mut window: u128 = 0;
+    let mut invalid: u128 = 0;
+    for (i, &b) in template.iter().enumerate() {
+        window = (window << 2) & window_mask;
+        invalid = (invalid << 2) & window_mask;
+        match base_code(b) {
+            Some(c) => window |= c,
+            None => invalid |= 1,
+        }
+        if i + 1 < len {
+            continue;
+        }
+        let start = i + 1 - len;
+        for (strand, (code, primer_invalid)) in [(Strand::Forward, forward), (Strand::Reverse, reverse)] {
+            let x: u128 = window ^ code;
+            let mismatch_bits: u128 = ((x | (x >> 1)) & EVEN_BITS) | invalid | primer_invalid;
+            let mismatches: u32 = mismatch_bits.count_ones();
+            if mismatches > params.max_mismatches {
+                continue;
+            }
+            // bit pair k (右から) は窓の右からk番目の塩基
+            let mut distances: Vec<usize> = Vec::with_capacity(mismatches as usize);
+            let mut bits = mismatch_bits;
+            while bits != 0 {
+                let k = bits.trailing_zeros() as usize / 2;
+                distances.push(match strand {
+                    Strand::Forward => k,
+                    Strand::Reverse => len - 1 - k,
+                });
+                bits &= bits - 1;
+            }
+            let weighted: f64 = distances.iter().map(|&d| params.mismatch_weight(d)).sum();
+            if weighted > params.max_weighted_mismatches {
+                continue;
+            }
+            distances.sort();
+            retval.push(BindingSite {
+                side,
+                strand,
+                start,
+                end: start + len,
+                mismatches,
+                weighted_mismatches: weighted,
+                mismatch_distances: distances,
+            });
+        }
+    }
+    retval
+}
+
+/// Runs the primer pair against one template in all four orientation
+/// combinations (L-L, L-R, R-L, R-R) and returns the products whose size
+/// falls in `min_product_size..=max_product_size`.
+pub fn in_silico_pcr(template_id: &str, template: &[u8], pair: &PrimerPair, params: &PcrParams) -> Vec<Amplicon> {
+    let mut forward_sites: Vec<BindingSite> = Vec::new();
+    let mut reverse_sites: Vec<BindingSite> = Vec::new();
+    for side in [PrimerSide::Left, PrimerSide::Right] {
+        for site in find_binding_sites(template, pair.primer(side), side, params) {
+            match site.strand {
+                Strand::Forward => forward_sites.push(site),
+                Strand::Reverse => reverse_sites.push(site),
+            }
+        }
+    }
+    forward_sites.sort_by_key(|s| (s.side, s.start));
+    reverse_sites.sort_by_key(|s| s.end);
+
+    let mut retval: Vec<Amplicon> = Vec::new();
+    for f in &forward_sites {
+        for r in &reverse_sites {
+            if r.end <= f.end || r.start < f.start {
+                continue;
+            }
+            let size = r.end - f.start;
+            if size > params.max_product_size {
+                break;
+            }
+            if size < params.min_product_size {
+                continue;
+            }
+            retval.push(Amplicon {
+                pair_id: pair.id.clone(),
+                template_id: template_id.to_string(),
+                forward: f.clone(),
+                reverse: r.clone(),
+                start: f.start,
+                end: r.end,
+                sequence: template[f.start..r.end].to_vec(),
+            });
+        }
+    }
+    retval
+}
+
+/// Runs every pair against every read with `threads` threads. Degenerate
+/// expansions of one pair can find the same product; those are reported once.
+pub fn run_in_silico_pcr(reads: &[(String, Vec<u8>)], pairs: &[PrimerPair], params: &PcrParams, threads: usize) -> Vec<Amplicon> {
+    let mut amplicons: Vec<Amplicon> = Vec::new();
+    if reads.is_empty() {
+        return amplicons;
+    }
+    let chunk_size: usize = reads.len().div_ceil(threads.max(1));
+    thread::scope(|scope| {
+        let mut children = Vec::new();
+        for (i, chunk) in reads.chunks(chunk_size).enumerate() {
+            children.push(scope.spawn(move || {
+                eprintln!("start calling in_silico_pcr[{}], # of sequence: {}", i, chunk.len());
+                let mut found: Vec<Amplicon> = Vec::new();
+                for (read_id, sequence) in chunk {
+                    for pair in pairs {
+                        found.extend(in_silico_pcr(read_id, sequence, pair, params));
+                    }
+                }
+                eprintln!("finish calling in_silico_pcr[{}]", i);
+                found
+            }));
+        }
+        for child in children {
+            amplicons.extend(child.join().unwrap());
+        }
+    });
+    let mut seen: HashSet<(String, String, usize, usize, String)> = HashSet::new();
+    amplicons.retain(|a| seen.insert((a.pair_id.clone(), a.template_id.clone(), a.start, a.end, a.orientation())));
+    amplicons
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::in_silico_pcr::{find_binding_sites, in_silico_pcr, reverse_complement, PcrParams, PrimerPair, PrimerSide, Strand};
+    use ::function_name::named;
+
+    const LEFT: &[u8] = b"ACGTTGCAAGGCTTACCGTA";
+    const RIGHT: &[u8] = b"GGATCCTTAGCAGTCAAGTC";
+
+    fn template_with(left: &[u8], right: &[u8]) -> Vec<u8> {
+        // L + 60塩基 + revcomp(R)
+        let mut t: Vec<u8> = b"TTTTT".to_vec();
+        t.extend_from_slice(left);
+        t.extend_from_slice(&b"CAGT".repeat(15));
+        t.extend_from_slice(&reverse_complement(right));
+        t.extend_from_slice(b"TTTTT");
+        t
+    }
+
+    #[test]
+    #[named]
+    fn exact_product_l_r() {
+        let pair = PrimerPair { id: "p1".to_string(), left: LEFT.to_vec(), right: RIGHT.to_vec() };
+        let template = template_with(LEFT, RIGHT);
+        let products = in_silico_pcr("read1", &template, &pair, &PcrParams::default());
+        assert!(products.len() == 1, "{} failed: {:?}", function_name!(), products);
+        assert!(products[0].orientation() == "L-R", "{} failed", function_name!());
+        assert!((products[0].start, products[0].end) == (5, 105), "{} failed", function_name!());
+        assert!(products[0].sequence == template[5..105], "{} failed", function_name!());
+    }
+
+    #[test]
+    #[named]
+    fn minus_strand_template_gives_r_l() {
+        let pair = PrimerPair { id: "p1".to_string(), left: LEFT.to_vec(), right: RIGHT.to_vec() };
+        let template = reverse_complement(&template_with(LEFT, RIGHT));
+        let products = in_silico_pcr("read1", &template, &pair, &PcrParams::default());
+        assert!(
+            products.len() == 1 && products[0].orientation() == "R-L",
+            "{} failed: {:?}",
+            function_name!(),
+            products
+        );
+    }
+
+    #[test]
+    #[named]
+    fn mismatch_weighted_by_distance_from_3prime() {
+        let params = PcrParams { max_mismatches: 1, max_weighted_mismatches: 1.0, ..PcrParams::default() };
+        // 5'末端のミスマッチは許容される
+        let mut far = LEFT.to_vec();
+        far[0] = b'T';
+        let sites = find_binding_sites(&far, LEFT, PrimerSide::Left, &params);
+        assert!(
+            sites.len() == 1 && sites[0].mismatch_distances == vec![LEFT.len() - 1],
+            "{} failed: {:?}",
+            function_name!(),
+            sites
+        );
+        // 3'末端のミスマッチは重み5で棄却される
+        let mut near = LEFT.to_vec();
+        near[LEFT.len() - 1] = b'C';
+        assert!(find_binding_sites(&near, LEFT, PrimerSide::Left, &params).is_empty(), "{} failed", function_name!());
+        // 逆鎖でも3'末端からの距離で数える
+        let rc = reverse_complement(&far);
+        let sites = find_binding_sites(&rc, LEFT, PrimerSide::Left, &params);
+        assert!(
+            sites.len() == 1 && sites[0].strand == Strand::Reverse && sites[0].mismatch_distances == vec![LEFT.len() - 1],
+            "{} failed: {:?}",
+            function_name!(),
+            sites
+        );
+    }
+
+    #[test]
+    #[named]
+    fn product_size_range() {
+        let pair = PrimerPair { id: "p1".to_string(), left: LEFT.to_vec(), right: RIGHT.to_vec() };
+        let template = template_with(LEFT, RIGHT);
+        let params = PcrParams { max_product_size: 99, ..PcrParams::default() };
+        assert!(in_silico_pcr("read1", &template, &pair, &params).is_empty(), "{} failed", function_name!());
+        let params = PcrParams { min_product_size: 101, max_product_size: 300, ..PcrParams::default() };
+        assert!(in_silico_pcr("read1", &template, &pair, &params).is_empty(), "{} failed", function_name!());
+    }
+}
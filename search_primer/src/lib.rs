@@ -1,4 +1,15 @@
+pub mod aligner_util;
+pub mod amplicon_profile;
+pub mod amplicon_redesign;
+pub mod amplicon_util;
+pub mod background;
+pub mod count_matrix;
 pub mod counting_bloomfilter_util;
+pub mod in_silico_pcr;
+pub mod primer_inclusivity;
+pub mod primer_set;
+pub mod probe_rules;
+pub mod reference_loci;
 pub mod sequence_encoder_util;
-
-
+pub mod tuple_id;
+pub mod tuple_record;
@@ -5,14 +5,19 @@ use crate::bio::io::fasta::FastaRead;
 use bio::io::fasta::Reader as faReader;
 use bio::io::fasta::Record as faRecord;
 use getopts::Options;
+use search_primer::background::remove_background;
 use search_primer::counting_bloomfilter_util::{
     build_counting_bloom_filter, count_lr_tuple_with_hashtable, number_of_high_occurence_lr_tuple,
 };
 use search_primer::counting_bloomfilter_util::{
     BLOOMFILTER_TABLE_SIZE, HASHSET_SIZE, L_LEN, R_LEN,
 };
+use search_primer::reference_loci::{
+    load_reference, reference_lr_tuple_loci, write_locus_tsv, Locus, ReferenceSegment,
+};
 use search_primer::sequence_encoder_util::decode_u128_2_dna_seq;
 use search_primer::sequence_encoder_util::DnaSequence;
+use search_primer::tuple_record::{write_tuple_records, LrTuple};
 // use sha2::digest::typenum::Le;
 use std::collections::HashMap;
 use std::collections::HashSet;
@@ -31,6 +36,76 @@ fn print_usage(program: &str, opts: &Options) {
     process::exit(0);
 }
 
+// アセンブリを入力とするモード。コンティグの両鎖を数え、異なる座位の数がthreshold以上のtupleを座位とともに書き出す
+#[allow(clippy::too_many_arguments)]
+fn reference_mode(
+    input_file: &str,
+    output_file: &str,
+    w: &mut BufWriter<File>,
+    threshold: u16,
+    threads: usize,
+    margin: usize,
+    background_files: &[String],
+    max_background: u16,
+    matches: &getopts::Matches,
+) {
+    let (contig_names, segments): (Vec<String>, Vec<ReferenceSegment>) =
+        load_reference(input_file).unwrap();
+
+    // 両鎖の出現回数で候補を絞り、座位を数えてコピー数とする
+    let mut loci: Vec<(u128, Vec<Locus>)> = reference_lr_tuple_loci(
+        &segments,
+        threshold,
+        threads,
+        margin,
+        BLOOMFILTER_TABLE_SIZE,
+    );
+    drop(segments);
+    let target_candidates: usize = loci.len();
+    let mut removed_by_background: usize = 0;
+    if !background_files.is_empty() {
+        removed_by_background = remove_background(
+            &mut loci,
+            |l| l.0,
+            background_files,
+            max_background,
+            threads,
+            margin,
+        )
+        .unwrap();
+    }
+
+    if matches.opt_present("r") {
+        writeln!(
+            w,
+            "lr_tuple count: {}\tthreshold: {}\tinput file {:?}\treference: true",
+            loci.len(),
+            threshold,
+            input_file
+        )
+        .unwrap();
+        if !background_files.is_empty() {
+            writeln!(
+                w,
+                "removed by background: {}\tof: {}\tmax_background: {}\tbackground files {:?}",
+                removed_by_background, target_candidates, max_background, background_files
+            )
+            .unwrap();
+        }
+        return;
+    }
+    if matches.opt_present("b") {
+        write_tuple_records(w, loci.iter().map(|(v, _)| LrTuple(*v))).unwrap();
+        let loci_file: String = format!("{}.loci.tsv", output_file);
+        let mut loci_writer: BufWriter<File> = BufWriter::new(File::create(&loci_file).unwrap());
+        write_locus_tsv(&mut loci_writer, &loci, &contig_names).unwrap();
+        loci_writer.flush().unwrap();
+        eprintln!("finish writing loci to {:?}", &loci_file);
+    } else {
+        write_locus_tsv(w, &loci, &contig_names).unwrap();
+    }
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
     let program = args[0].clone();
@@ -55,6 +130,23 @@ fn main() {
         "margin between l and r segments. default value is 0.",
         "MARGIN_SIZE",
     );
+    opts.optmulti(
+        "B",
+        "background",
+        "background (non-target) genomes or reads in FASTA. can be given more than once.",
+        "FILE",
+    );
+    opts.optopt(
+        "x",
+        "max_background",
+        "lr-tuples occurring more often than this in the background sets are removed. default value is 0.",
+        "COUNT",
+    );
+    opts.optflag(
+        "g",
+        "reference",
+        "FILE is an assembly: lr-tuples are enumerated on both strands of each contig and threshold is the number of distinct loci. writes lr-tuples with their loci (TSV).",
+    );
     opts.optflag("b", "binary", "outputs binary file");
     opts.optflag("r", "only-num", "outputs only total number of lr-tuple.");
     opts.optflag("h", "help", "print this help menu");
@@ -95,6 +187,13 @@ fn main() {
         1000
     };
 
+    let background_files: Vec<String> = matches.opt_strs("B");
+    let max_background: u16 = if matches.opt_present("x") {
+        matches.opt_str("x").unwrap().parse::<u16>().unwrap()
+    } else {
+        0
+    };
+
     let output_file: String = if matches.opt_present("o") {
         matches.opt_str("o").unwrap()
     } else {
@@ -104,6 +203,21 @@ fn main() {
         )
     };
     let mut w: BufWriter<File> = BufWriter::new(fs::File::create(&output_file).unwrap());
+    if matches.opt_present("g") {
+        reference_mode(
+            &input_file,
+            &output_file,
+            &mut w,
+            threshold,
+            threads,
+            mergin_size,
+            &background_files,
+            max_background,
+            &matches,
+        );
+        w.flush().unwrap();
+        return;
+    }
 
     eprintln!("input  file: {:?}", input_file);
     let file: File = File::open(&input_file).expect("Error during opening the file");
@@ -285,8 +399,20 @@ fn main() {
         .collect();
     sorted_hs_list.sort();
 
-    let mut buf_array: [u8; 16] = [0; 16];
-    let mut buf_num: u128;
+    // 背景配列(非標的ゲノムなど)での出現回数を候補について正確に数え、多すぎるものを除く
+    let target_candidates: usize = sorted_hs_list.len();
+    let mut removed_by_background: usize = 0;
+    if !background_files.is_empty() {
+        removed_by_background = remove_background(
+            &mut sorted_hs_list,
+            |t| *t,
+            &background_files,
+            max_background,
+            threads,
+            mergin_size,
+        )
+        .unwrap();
+    }
 
     if matches.opt_present("r") {
         eprintln!(
@@ -302,6 +428,14 @@ fn main() {
             &input_file
         )
         .unwrap();
+        if !background_files.is_empty() {
+            writeln!(
+                &mut w,
+                "removed by background: {}\tof: {}\tmax_background: {}\tbackground files {:?}",
+                removed_by_background, target_candidates, max_background, &background_files
+            )
+            .unwrap();
+        }
     }
     if !matches.opt_present("r") && matches.opt_present("b") {
         eprintln!(
@@ -309,14 +443,7 @@ fn main() {
             matches.opt_present("r"),
             matches.opt_present("b")
         );
-        for each_lr_tuple in &sorted_hs_list {
-            buf_num = *each_lr_tuple;
-            for i in 0..16 {
-                buf_array[15 - i] = u8::try_from(buf_num & 0xFF).unwrap();
-                buf_num >>= 8;
-            }
-            w.write(&buf_array).unwrap();
-        }
+        write_tuple_records(&mut w, sorted_hs_list.iter().map(|v| LrTuple(*v))).unwrap();
     }
     if !matches.opt_present("r") && !matches.opt_present("b") {
         eprintln!(
@@ -343,4 +470,10 @@ fn main() {
         sorted_hs_list.len(),
     );
     eprintln!("threads: {}\tinput file {:?}", threads, &input_file);
+    if !background_files.is_empty() {
+        eprintln!(
+            "removed by background: {}\tmax_background: {}\tbackground files {:?}",
+            removed_by_background, max_background, &background_files
+        );
+    }
 }
@@ -0,0 +1,189 @@
+// 設計したプライマーが、リード集団のどれだけに結合できるかを数える。
+// リードごとにプライマーと最もよく一致する窓をin_silico_pcrと同じ2bitのXORで探し、
+// ミスマッチ数(0, 1, 2, 3以上)と、3'末端からの位置ごとのミスマッチ数を集計する。
+
+use crate::in_silico_pcr::{find_binding_sites, BindingSite, PcrParams, PrimerSide};
+use crate::primer_set::expand_degenerate;
+
+/// Best binding site of `primer` (any expansion of its degenerate bases) on
+/// either strand of `read`: fewest mismatches, then lowest weighted mismatches.
+/// Windows with more than `params.max_mismatches` mismatches are not considered.
+pub fn best_binding_site(
+    read: &[u8],
+    primer_variants: &[Vec<u8>],
+    side: PrimerSide,
+    params: &PcrParams,
+) -> Option<BindingSite> {
+    primer_variants
+        .iter()
+        .flat_map(|primer| find_binding_sites(read, primer, side, params))
+        .min_by(|a, b| {
+            a.mismatches
+                .cmp(&b.mismatches)
+                .then(a.weighted_mismatches.total_cmp(&b.weighted_mismatches))
+        })
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrimerTally {
+    pub primer_id: String,
+    pub side: PrimerSide,
+    pub primer: Vec<u8>,
+    /// Expansions of the degenerate bases, searched as one primer.
+    pub variants: Vec<Vec<u8>>,
+    pub reads: usize,
+    /// Reads whose best site has 0, 1, 2 and 3 or more mismatches.
+    pub by_mismatches: [usize; 4],
+    /// Mismatches at each distance from the 3' end (index 0 = 3'-terminal base), over the best sites.
+    pub mismatches_from_3p: Vec<usize>,
+    /// Reads whose best site is within the `binding_weight` of `add_read`.
+    pub binding: usize,
+}
+
+impl PrimerTally {
+    pub fn new(primer_id: &str, side: PrimerSide, primer: &[u8]) -> Result<PrimerTally, String> {
+        Ok(PrimerTally {
+            primer_id: primer_id.to_string(),
+            side,
+            primer: primer.to_vec(),
+            variants: expand_degenerate(primer)?,
+            reads: 0,
+            by_mismatches: [0; 4],
+            mismatches_from_3p: vec![0; primer.len()],
+            binding: 0,
+        })
+    }
+
+    /// Tallies the best site of this primer in `read`. `binding_weight` is the
+    /// largest weighted mismatch count that still counts as binding.
+    pub fn add_read(&mut self, read: &[u8], params: &PcrParams, binding_weight: f64) {
+        self.reads += 1;
+        let site = match best_binding_site(read, &self.variants, self.side, params) {
+            Some(site) => site,
+            None => return,
+        };
+        self.by_mismatches[(site.mismatches as usize).min(3)] += 1;
+        for &d in &site.mismatch_distances {
+            self.mismatches_from_3p[d] += 1;
+        }
+        if site.weighted_mismatches <= binding_weight {
+            self.binding += 1;
+        }
+    }
+
+    /// Adds the counts of another tally of the same primer.
+    pub fn merge(&mut self, other: &PrimerTally) {
+        self.reads += other.reads;
+        self.binding += other.binding;
+        for (a, b) in self.by_mismatches.iter_mut().zip(other.by_mismatches) {
+            *a += b;
+        }
+        for (a, b) in self
+            .mismatches_from_3p
+            .iter_mut()
+            .zip(&other.mismatches_from_3p)
+        {
+            *a += b;
+        }
+    }
+
+    /// Reads carrying the site within the scanned number of mismatches.
+    pub fn carrying(&self) -> usize {
+        self.by_mismatches.iter().sum()
+    }
+
+    /// Fraction of all reads that carry the site.
+    pub fn coverage(&self) -> f64 {
+        if self.reads == 0 {
+            return 0.0;
+        }
+        self.carrying() as f64 / self.reads as f64
+    }
+
+    /// Fraction of the reads carrying the site that the primer binds.
+    pub fn inclusivity(&self) -> f64 {
+        if self.carrying() == 0 {
+            return 0.0;
+        }
+        self.binding as f64 / self.carrying() as f64
+    }
+}
+
+pub fn tally_tsv_header() -> String {
+    "primer_id\tside\tprimer\treads\tcarrying\tmm0\tmm1\tmm2\tmm3+\tbinding\tcoverage\tinclusivity\tmismatches_from_3p\n".to_string()
+}
+
+pub fn tally_tsv_line(tally: &PrimerTally) -> String {
+    let from_3p: Vec<String> = tally
+        .mismatches_from_3p
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.4}\t{:.4}\t{}\n",
+        tally.primer_id,
+        tally.side.label(),
+        String::from_utf8_lossy(&tally.primer),
+        tally.reads,
+        tally.carrying(),
+        tally.by_mismatches[0],
+        tally.by_mismatches[1],
+        tally.by_mismatches[2],
+        tally.by_mismatches[3],
+        tally.binding,
+        tally.coverage(),
+        tally.inclusivity(),
+        from_3p.join(",")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::in_silico_pcr::{reverse_complement, PcrParams, PrimerSide};
+    use crate::primer_inclusivity::PrimerTally;
+    use ::function_name::named;
+
+    #[test]
+    #[named]
+    fn tally_test() {
+        let params = PcrParams {
+            max_mismatches: 4,
+            max_weighted_mismatches: f64::MAX,
+            ..PcrParams::default()
+        };
+        let primer = b"ACGTTGCAAGGCTTACCGTA";
+        let reads: Vec<Vec<u8>> = vec![
+            // 完全一致
+            [&b"TTTT"[..], primer, b"GGGG"].concat(),
+            // 逆鎖、5'側に1ミスマッチ
+            reverse_complement(&[&b"TTTT"[..], b"TCGTTGCAAGGCTTACCGTA", b"GGGG"].concat()),
+            // 3'末端に1ミスマッチ
+            [&b"TTTT"[..], b"ACGTTGCAAGGCTTACCGTT", b"GGGG"].concat(),
+            // 5ミスマッチ
+            [&b"TTTT"[..], b"TGCAAGCAAGGCTTACCGTA", b"GGGG"].concat(),
+        ];
+        let mut tally = PrimerTally::new("p1", PrimerSide::Left, primer).unwrap();
+        for read in &reads {
+            tally.add_read(read, &params, 2.0);
+        }
+        assert!(
+            tally.by_mismatches == [1, 2, 0, 0]
+                && tally.binding == 2
+                && tally.mismatches_from_3p[0] == 1
+                && tally.mismatches_from_3p[19] == 1
+                && tally.coverage() == 0.75,
+            "{} failed: {:?}",
+            function_name!(),
+            tally
+        );
+        let mut degenerate =
+            PrimerTally::new("p1", PrimerSide::Left, b"ACGTTGCAAGGCTTACCGTW").unwrap();
+        degenerate.add_read(&reads[2], &params, 2.0);
+        assert!(
+            degenerate.by_mismatches[0] == 1,
+            "{} failed: {:?}",
+            function_name!(),
+            degenerate
+        );
+    }
+}
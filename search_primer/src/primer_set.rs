@@ -0,0 +1,606 @@
+// プライマーペアの入力(TSV/CSV/FASTA)。
+// search_probe・extract_PCR_target_regionなどで共通に使う。
+
+use crate::in_silico_pcr::PrimerPair;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+const ID_COLUMNS: [&str; 5] = ["primer_id", "id", "name", "pair_id", "sequence_id"];
+const LEFT_COLUMNS: [&str; 6] = [
+    "left_primer",
+    "left",
+    "forward_primer",
+    "forward",
+    "fwd",
+    "primer_left_sequence",
+];
+const RIGHT_COLUMNS: [&str; 6] = [
+    "right_primer",
+    "right",
+    "reverse_primer",
+    "reverse",
+    "rev",
+    "primer_right_sequence",
+];
+const LEFT_SUFFIXES: [&str; 4] = ["_L", "_F", "_left", "_forward"];
+const RIGHT_SUFFIXES: [&str; 4] = ["_R", "_RC", "_right", "_reverse"];
+/// Upper bound on the number of plain sequences one degenerate primer may expand to.
+pub const MAX_DEGENERATE_EXPANSION: usize = 1024;
+/// Upper bound on the number of plain primer pairs one degenerate pair may expand to.
+pub const MAX_PRIMER_PAIR_EXPANSION: usize = 4096;
+
+/// How a primer is shortened before use. Primers shorter than the rule asks for are kept whole,
+/// but a rule that leaves no base is an error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TrimRule {
+    #[default]
+    None,
+    /// Keep this many bases at the 3' end.
+    Keep3Prime(usize),
+    /// Remove this many bases from the 5' end.
+    Remove5Prime(usize),
+}
+
+impl TrimRule {
+    /// `none`, `3p:N` (keep N bases at the 3' end) or `5p:N` (remove N bases from the 5' end).
+    /// A bare number is read as `3p:N`.
+    pub fn parse(rule: &str) -> Result<TrimRule, String> {
+        let rule = rule.trim();
+        if rule.is_empty() || rule == "none" {
+            return Ok(TrimRule::None);
+        }
+        let (kind, n) = rule.split_once(':').unwrap_or(("3p", rule));
+        let n: usize = n
+            .parse()
+            .map_err(|_| format!("invalid trimming rule: {}", rule))?;
+        match kind {
+            "3p" if n == 0 => Err(format!("trimming rule {} keeps no bases", rule)),
+            "3p" => Ok(TrimRule::Keep3Prime(n)),
+            "5p" => Ok(TrimRule::Remove5Prime(n)),
+            _ => Err(format!("invalid trimming rule: {}", rule)),
+        }
+    }
+
+    pub fn apply(&self, primer: &[u8]) -> Result<Vec<u8>, String> {
+        let trimmed = match *self {
+            TrimRule::None => primer.to_vec(),
+            TrimRule::Keep3Prime(n) => primer[primer.len().saturating_sub(n)..].to_vec(),
+            TrimRule::Remove5Prime(n) => primer[n.min(primer.len())..].to_vec(),
+        };
+        if trimmed.is_empty() {
+            return Err(format!(
+                "trimming rule {:?} leaves no bases of the {} base primer",
+                self,
+                primer.len()
+            ));
+        }
+        Ok(trimmed)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PrimerSetOptions {
+    /// Column names to use instead of the auto-detected ones.
+    pub id_column: Option<String>,
+    pub left_column: Option<String>,
+    pub right_column: Option<String>,
+    pub trim_left: TrimRule,
+    pub trim_right: TrimRule,
+}
+
+fn normalize_column_name(name: &str) -> String {
+    name.trim()
+        .trim_matches('"')
+        .to_ascii_lowercase()
+        .replace([' ', '-'], "_")
+}
+
+fn is_iupac(base: u8) -> bool {
+    matches!(
+        base,
+        b'A' | b'C'
+            | b'G'
+            | b'T'
+            | b'R'
+            | b'Y'
+            | b'S'
+            | b'W'
+            | b'K'
+            | b'M'
+            | b'B'
+            | b'D'
+            | b'H'
+            | b'V'
+            | b'N'
+    )
+}
+
+/// Plain bases an IUPAC code stands for.
+pub fn iupac_bases(base: u8) -> &'static [u8] {
+    match base.to_ascii_uppercase() {
+        b'A' => b"A",
+        b'C' => b"C",
+        b'G' => b"G",
+        b'T' | b'U' => b"T",
+        b'R' => b"AG",
+        b'Y' => b"CT",
+        b'S' => b"CG",
+        b'W' => b"AT",
+        b'K' => b"GT",
+        b'M' => b"AC",
+        b'B' => b"CGT",
+        b'D' => b"AGT",
+        b'H' => b"ACT",
+        b'V' => b"ACG",
+        b'N' => b"ACGT",
+        _ => b"",
+    }
+}
+
+/// IUPAC code standing for exactly `bases` (any order); N when none of them is ACGT.
+pub fn iupac_code(bases: &[u8]) -> u8 {
+    let mut set: Vec<u8> = bases
+        .iter()
+        .map(|b| b.to_ascii_uppercase())
+        .filter(|b| b"ACGT".contains(b))
+        .collect();
+    set.sort_unstable();
+    set.dedup();
+    b"ACGTRYSWKMBDHVN"
+        .iter()
+        .copied()
+        .find(|&code| iupac_bases(code) == set.as_slice())
+        .unwrap_or(b'N')
+}
+
+pub fn is_degenerate(primer: &[u8]) -> bool {
+    primer
+        .iter()
+        .any(|&b| !matches!(b, b'A' | b'C' | b'G' | b'T'))
+}
+
+/// All plain sequences a degenerate primer stands for, in lexicographic order of the codes.
+pub fn expand_degenerate(primer: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let mut retval: Vec<Vec<u8>> = vec![Vec::with_capacity(primer.len())];
+    for &base in primer {
+        let bases = iupac_bases(base);
+        if bases.is_empty() {
+            return Err(format!("invalid base '{}'", base as char));
+        }
+        if retval.len() * bases.len() > MAX_DEGENERATE_EXPANSION {
+            return Err(format!(
+                "{} expands to more than {} sequences",
+                String::from_utf8_lossy(primer),
+                MAX_DEGENERATE_EXPANSION
+            ));
+        }
+        retval = retval
+            .iter()
+            .flat_map(|prefix| {
+                bases.iter().map(move |&b| {
+                    let mut v = prefix.clone();
+                    v.push(b);
+                    v
+                })
+            })
+            .collect();
+    }
+    Ok(retval)
+}
+
+/// Every combination of plain left and right primers of `pair`, keeping its ID.
+pub fn expand_primer_pair(pair: &PrimerPair) -> Result<Vec<PrimerPair>, String> {
+    let lefts = expand_degenerate(&pair.left).map_err(|e| format!("{}: {}", pair.id, e))?;
+    let rights = expand_degenerate(&pair.right).map_err(|e| format!("{}: {}", pair.id, e))?;
+    if lefts.len() * rights.len() > MAX_PRIMER_PAIR_EXPANSION {
+        return Err(format!(
+            "{}: {} left x {} right primers is more than {} primer pairs",
+            pair.id,
+            lefts.len(),
+            rights.len(),
+            MAX_PRIMER_PAIR_EXPANSION
+        ));
+    }
+    Ok(lefts
+        .iter()
+        .flat_map(|left| {
+            rights.iter().map(move |right| PrimerPair {
+                id: pair.id.clone(),
+                left: left.clone(),
+                right: right.clone(),
+            })
+        })
+        .collect())
+}
+
+fn validate_primer(primer: &str, line_number: usize, what: &str) -> Result<Vec<u8>, String> {
+    let primer: Vec<u8> = primer
+        .trim()
+        .trim_matches('"')
+        .as_bytes()
+        .to_ascii_uppercase()
+        .iter()
+        .map(|&b| if b == b'U' { b'T' } else { b })
+        .collect();
+    if primer.is_empty() {
+        return Err(format!("line {}: {} primer is empty", line_number, what));
+    }
+    if let Some(&b) = primer.iter().find(|&&b| !is_iupac(b)) {
+        return Err(format!(
+            "line {}: {} primer contains invalid base '{}'",
+            line_number, what, b as char
+        ));
+    }
+    Ok(primer)
+}
+
+fn find_column(header: &[String], explicit: &Option<String>, candidates: &[&str]) -> Option<usize> {
+    match explicit {
+        Some(name) => {
+            let name = normalize_column_name(name);
+            header.iter().position(|h| *h == name)
+        }
+        None => candidates
+            .iter()
+            .find_map(|c| header.iter().position(|h| h == c)),
+    }
+}
+
+/// Primer pairs from a TSV or CSV table. The first line is taken as a header when it
+/// names the ID, left and right primer columns; otherwise the columns are ID, left, right.
+/// Every malformed row is reported with its line number.
+pub fn parse_primer_table(
+    contents: &str,
+    delimiter: char,
+    options: &PrimerSetOptions,
+) -> Result<Vec<PrimerPair>, String> {
+    let mut lines = contents
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .peekable();
+    let first: Vec<String> = match lines.peek() {
+        Some((_, line)) => line.split(delimiter).map(normalize_column_name).collect(),
+        None => return Ok(Vec::new()),
+    };
+    let id_col = find_column(&first, &options.id_column, &ID_COLUMNS);
+    let left_col = find_column(&first, &options.left_column, &LEFT_COLUMNS);
+    let right_col = find_column(&first, &options.right_column, &RIGHT_COLUMNS);
+    let explicit = options.id_column.is_some()
+        || options.left_column.is_some()
+        || options.right_column.is_some();
+    let (id_col, left_col, right_col, trim_cols) = match (id_col, left_col, right_col) {
+        (Some(i), Some(l), Some(r)) => {
+            lines.next();
+            let trim_cols = (
+                first.iter().position(|h| h == "left_trim"),
+                first.iter().position(|h| h == "right_trim"),
+            );
+            (i, l, r, trim_cols)
+        }
+        _ if explicit => {
+            return Err(format!(
+                "line 1: header does not have the requested columns: {}",
+                first.join(", ")
+            ))
+        }
+        // primer3系の出力でヘッダーがない場合は id, left, right の順とみなす
+        _ => (0, 1, 2, (None, None)),
+    };
+    let needed = id_col.max(left_col).max(right_col) + 1;
+
+    let mut retval: Vec<PrimerPair> = Vec::new();
+    let mut errors: Vec<String> = Vec::new();
+    for (line_number, line) in lines {
+        let fields: Vec<&str> = line.split(delimiter).collect();
+        if fields.len() < needed {
+            errors.push(format!(
+                "line {}: expected at least {} columns, found {}",
+                line_number,
+                needed,
+                fields.len()
+            ));
+            continue;
+        }
+        let trim = |col: Option<usize>, default: TrimRule| -> Result<TrimRule, String> {
+            match col.and_then(|c| fields.get(c)) {
+                Some(rule) if !rule.trim().is_empty() => {
+                    TrimRule::parse(rule).map_err(|e| format!("line {}: {}", line_number, e))
+                }
+                _ => Ok(default),
+            }
+        };
+        let parsed = (|| -> Result<PrimerPair, String> {
+            let id = fields[id_col].trim().trim_matches('"').to_string();
+            if id.is_empty() {
+                return Err(format!("line {}: primer ID is empty", line_number));
+            }
+            let left = validate_primer(fields[left_col], line_number, "left")?;
+            let right = validate_primer(fields[right_col], line_number, "right")?;
+            let apply = |rule: TrimRule, primer: &[u8], what: &str| {
+                rule.apply(primer)
+                    .map_err(|e| format!("line {}: {} primer: {}", line_number, what, e))
+            };
+            Ok(PrimerPair {
+                id,
+                left: apply(trim(trim_cols.0, options.trim_left)?, &left, "left")?,
+                right: apply(trim(trim_cols.1, options.trim_right)?, &right, "right")?,
+            })
+        })();
+        match parsed {
+            Ok(pair) => retval.push(pair),
+            Err(e) => errors.push(e),
+        }
+    }
+    if errors.is_empty() {
+        Ok(retval)
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+/// Header line and sequence of one primer of a FASTA pair.
+type FastaMate = (usize, Vec<u8>);
+
+/// Primer pairs from FASTA, mates named `{id}_L`/`{id}_R` (also `_F`/`_RC`,
+/// `_left`/`_right`, `_forward`/`_reverse`), pairs in order of first appearance.
+pub fn parse_primer_fasta(
+    contents: &str,
+    options: &PrimerSetOptions,
+) -> Result<Vec<PrimerPair>, String> {
+    // (header line, name, sequence)
+    let mut records: Vec<(usize, String, String)> = Vec::new();
+    let mut errors: Vec<String> = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('>') {
+            let name = header.split_whitespace().next().unwrap_or("").to_string();
+            records.push((i + 1, name, String::new()));
+        } else if !line.is_empty() {
+            match records.last_mut() {
+                Some(record) => record.2.push_str(line),
+                None => errors.push(format!("line {}: sequence before the first header", i + 1)),
+            }
+        }
+    }
+
+    let mut order: Vec<String> = Vec::new();
+    let mut mates: BTreeMap<String, [Option<FastaMate>; 2]> = BTreeMap::new();
+    for (line_number, name, sequence) in records {
+        let side = LEFT_SUFFIXES
+            .iter()
+            .find_map(|s| name.strip_suffix(s).map(|id| (id, 0)))
+            .or_else(|| {
+                RIGHT_SUFFIXES
+                    .iter()
+                    .find_map(|s| name.strip_suffix(s).map(|id| (id, 1)))
+            });
+        let (id, side) = match side {
+            Some(found) => found,
+            None => {
+                errors.push(format!(
+                    "line {}: {} does not end with a left/right suffix ({} / {})",
+                    line_number,
+                    name,
+                    LEFT_SUFFIXES.join(","),
+                    RIGHT_SUFFIXES.join(",")
+                ));
+                continue;
+            }
+        };
+        let what = if side == 0 { "left" } else { "right" };
+        let primer = match validate_primer(&sequence, line_number, what) {
+            Ok(primer) => primer,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
+        let entry = mates.entry(id.to_string()).or_insert_with(|| {
+            order.push(id.to_string());
+            [None, None]
+        });
+        if entry[side].is_some() {
+            errors.push(format!(
+                "line {}: duplicated {} primer of {}",
+                line_number, what, id
+            ));
+        }
+        entry[side] = Some((line_number, primer));
+    }
+
+    let mut retval: Vec<PrimerPair> = Vec::new();
+    for id in order {
+        match &mates[&id] {
+            [Some((left_line, left)), Some((right_line, right))] => {
+                match (
+                    options.trim_left.apply(left),
+                    options.trim_right.apply(right),
+                ) {
+                    (Ok(left), Ok(right)) => retval.push(PrimerPair { id, left, right }),
+                    (left, right) => {
+                        if let Err(e) = left {
+                            errors.push(format!("line {}: left primer: {}", left_line, e));
+                        }
+                        if let Err(e) = right {
+                            errors.push(format!("line {}: right primer: {}", right_line, e));
+                        }
+                    }
+                }
+            }
+            [Some((line_number, _)), None] | [None, Some((line_number, _))] => {
+                errors.push(format!("line {}: {} has no mate primer", line_number, id))
+            }
+            [None, None] => {}
+        }
+    }
+    if errors.is_empty() {
+        Ok(retval)
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+/// Primer pairs from `path`: FASTA when the first non-empty line starts with `>`,
+/// CSV for `.csv` files, TSV otherwise.
+pub fn load_primer_set<P: AsRef<Path>>(
+    path: P,
+    options: &PrimerSetOptions,
+) -> Result<Vec<PrimerPair>, String> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let first_line = contents
+        .lines()
+        .find(|l| !l.trim().is_empty())
+        .unwrap_or("");
+    let result = if first_line.starts_with('>') {
+        parse_primer_fasta(&contents, options)
+    } else if path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("csv"))
+    {
+        parse_primer_table(&contents, ',', options)
+    } else {
+        parse_primer_table(&contents, '\t', options)
+    };
+    result.map_err(|e| format!("{}:\n{}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::in_silico_pcr::PrimerPair;
+    use crate::primer_set::{
+        expand_degenerate, expand_primer_pair, parse_primer_fasta, parse_primer_table,
+        PrimerSetOptions, TrimRule,
+    };
+    use ::function_name::named;
+
+    #[test]
+    #[named]
+    fn parse_primer_table_test() {
+        // search_probeに渡していたprimer3_caller由来のTSV
+        let tsv = "primer id\tleft primer\tright primer\tprimer left Tm\n\
+                   p1\tAGGTGGTTAGTATAGGGATGGCAC\tGCGGTTAGTCGACGCGCTTGAC\t61.490\n\
+                   p2\tTAGGGTGGATAGCTTAGACGATGTCGG\tCTTAACGGCGCGGTTAGTCGAC\t65.125\n";
+        let pairs = parse_primer_table(tsv, '\t', &PrimerSetOptions::default()).unwrap();
+        assert!(
+            pairs.len() == 2 && pairs[1].id == "p2" && pairs[0].right == b"GCGGTTAGTCGACGCGCTTGAC",
+            "{} failed",
+            function_name!()
+        );
+
+        // 列の順番が違うCSV、行ごとのトリミング
+        let csv = "Right,Name,Left,left_trim\nGGCCAATT,a,ACGTACGTAC,3p:4\nTTAACCGG,b,ACGTRCGTAC,\n";
+        let options = PrimerSetOptions {
+            trim_right: TrimRule::Remove5Prime(2),
+            ..PrimerSetOptions::default()
+        };
+        let pairs = parse_primer_table(csv, ',', &options).unwrap();
+        assert!(
+            pairs[0].left == b"GTAC"
+                && pairs[0].right == b"CCAATT"
+                && pairs[1].left == b"ACGTRCGTAC",
+            "{} failed: {:?}",
+            function_name!(),
+            pairs
+        );
+
+        // ヘッダーなし、行番号つきのエラー
+        let bad = "p1\tACGT\tACGT\np2\tACGT\n\np3\tACXT\tACGT\n";
+        let err = parse_primer_table(bad, '\t', &PrimerSetOptions::default()).unwrap_err();
+        assert!(
+            err.contains("line 2: expected at least 3 columns")
+                && err.contains("line 4: left primer contains invalid base 'X'"),
+            "{} failed: {}",
+            function_name!(),
+            err
+        );
+    }
+
+    #[test]
+    #[named]
+    fn parse_primer_fasta_test() {
+        let fasta = ">p1_L\nACGTAC\nGT\n>p1_R\nTTTTCC\n>p2_F\nAAAA\n";
+        let err = parse_primer_fasta(fasta, &PrimerSetOptions::default()).unwrap_err();
+        assert!(
+            err == "line 6: p2 has no mate primer",
+            "{} failed: {}",
+            function_name!(),
+            err
+        );
+        let pairs = parse_primer_fasta(
+            ">p1_L\nACGTAC\nGT\n>p1_R\nTTTTCC\n",
+            &PrimerSetOptions::default(),
+        )
+        .unwrap();
+        assert!(
+            pairs.len() == 1 && pairs[0].left == b"ACGTACGT" && pairs[0].right == b"TTTTCC",
+            "{} failed: {:?}",
+            function_name!(),
+            pairs
+        );
+    }
+
+    #[test]
+    #[named]
+    fn degenerate_and_trim_test() {
+        let expanded = expand_degenerate(b"ARN").unwrap();
+        assert!(
+            expanded.len() == 8 && expanded[0] == b"AAA" && expanded[7] == b"AGT",
+            "{} failed",
+            function_name!()
+        );
+        assert!(
+            expand_degenerate(&[b'N'; 10]).is_err(),
+            "{} failed",
+            function_name!()
+        );
+        assert!(
+            TrimRule::parse("15").unwrap() == TrimRule::Keep3Prime(15)
+                && TrimRule::Keep3Prime(15).apply(b"ACGT").unwrap() == b"ACGT"
+                && TrimRule::Remove5Prime(3).apply(b"ACGT").unwrap() == b"T"
+                && TrimRule::Remove5Prime(4).apply(b"ACGT").is_err()
+                && TrimRule::Keep3Prime(0).apply(b"ACGT").is_err()
+                && TrimRule::parse("3p:0").is_err()
+                && TrimRule::parse("0").is_err(),
+            "{} failed",
+            function_name!()
+        );
+
+        // トリミングで空になるプライマーは行番号つきのエラー
+        let options = PrimerSetOptions {
+            trim_left: TrimRule::Remove5Prime(6),
+            ..PrimerSetOptions::default()
+        };
+        let table_err =
+            parse_primer_table("p1\tACGTACGT\tACGT\np2\tACGT\tACGT\n", '\t', &options).unwrap_err();
+        let fasta_err = parse_primer_fasta(">p1_L\nACGT\n>p1_R\nACGT\n", &options).unwrap_err();
+        assert!(
+            table_err.starts_with("line 2: left primer: trimming rule")
+                && !table_err.contains("line 1")
+                && fasta_err.starts_with("line 1: left primer: trimming rule"),
+            "{} failed: {} / {}",
+            function_name!(),
+            table_err,
+            fasta_err
+        );
+
+        let pair = |left: &[u8], right: &[u8]| PrimerPair {
+            id: "p1".to_string(),
+            left: left.to_vec(),
+            right: right.to_vec(),
+        };
+        let too_many = expand_primer_pair(&pair(b"NNNNN", b"NNNNN")).unwrap_err();
+        assert!(
+            expand_primer_pair(&pair(b"NNNN", b"NN")).unwrap().len() == 4096
+                && expand_primer_pair(&pair(b"NNNNN", b"NN")).is_err()
+                && too_many.starts_with("p1: 1024 left x 1024 right primers"),
+            "{} failed: {}",
+            function_name!(),
+            too_many
+        );
+    }
+}
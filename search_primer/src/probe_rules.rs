@@ -0,0 +1,325 @@
+// TaqManプローブの設計ルール。
+// search_probe・search_primer_and_probeの両方から、M窓やプローブ候補の評価に使う。
+
+use std::fmt;
+
+/// SantaLucia (1998) unified nearest-neighbor parameters, ΔH (kcal/mol) and ΔS (cal/K/mol).
+/// Keyed by the 5'->3' dinucleotide of the top strand; the other ten are reverse complements.
+fn nearest_neighbor(pair: [u8; 2]) -> Option<(f64, f64)> {
+    match &pair {
+        b"AA" | b"TT" => Some((-7.9, -22.2)),
+        b"AT" => Some((-7.2, -20.4)),
+        b"TA" => Some((-7.2, -21.3)),
+        b"CA" | b"TG" => Some((-8.5, -22.7)),
+        b"GT" | b"AC" => Some((-8.4, -22.4)),
+        b"CT" | b"AG" => Some((-7.8, -21.0)),
+        b"GA" | b"TC" => Some((-8.2, -22.2)),
+        b"CG" => Some((-10.6, -27.2)),
+        b"GC" => Some((-9.8, -24.4)),
+        b"GG" | b"CC" => Some((-8.0, -19.9)),
+        _ => None,
+    }
+}
+
+fn terminal_initiation(base: u8) -> (f64, f64) {
+    match base {
+        b'G' | b'C' => (0.1, -2.8),
+        _ => (2.3, 4.1),
+    }
+}
+
+/// Melting temperature (°C) by the nearest-neighbor model with a monovalent salt
+/// correction. Pairs containing anything other than ACGT are ignored.
+pub fn melting_temperature(sequence: &[u8], na_mm: f64, oligo_nm: f64) -> f64 {
+    let sequence: Vec<u8> = sequence.to_ascii_uppercase();
+    if sequence.len() < 2 {
+        return f64::NAN;
+    }
+    let (mut dh, mut ds) = (0.0, 0.0);
+    for end in [sequence[0], sequence[sequence.len() - 1]] {
+        let (h, s) = terminal_initiation(end);
+        dh += h;
+        ds += s;
+    }
+    for pair in sequence.windows(2) {
+        if let Some((h, s)) = nearest_neighbor([pair[0], pair[1]]) {
+            dh += h;
+            ds += s;
+        }
+    }
+    ds += 0.368 * (sequence.len() - 1) as f64 * (na_mm / 1000.0).ln();
+    1000.0 * dh / (ds + 1.987 * (oligo_nm * 1e-9 / 4.0).ln()) - 273.15
+}
+
+/// Fraction of G and C among all bases.
+pub fn gc_content(sequence: &[u8]) -> f64 {
+    if sequence.is_empty() {
+        return 0.0;
+    }
+    let gc = sequence
+        .iter()
+        .filter(|b| matches!(b.to_ascii_uppercase(), b'G' | b'C'))
+        .count();
+    gc as f64 / sequence.len() as f64
+}
+
+fn longest_run(sequence: &[u8], base: u8) -> usize {
+    sequence
+        .split(|b| b.to_ascii_uppercase() != base)
+        .map(|run| run.len())
+        .max()
+        .unwrap_or(0)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ProbeRule {
+    /// No G at the 5' end (it quenches the reporter dye).
+    NoFivePrimeG,
+    /// More C than G.
+    MoreCThanG,
+    /// Tm between `min_tm_delta` and `max_tm_delta` above the primers.
+    TmAbovePrimers,
+    /// No run of `max_g_run + 1` or more G.
+    NoGRun,
+    /// GC content between `min_gc` and `max_gc`.
+    GcContent,
+}
+
+pub const ALL_PROBE_RULES: [ProbeRule; 5] = [
+    ProbeRule::NoFivePrimeG,
+    ProbeRule::MoreCThanG,
+    ProbeRule::TmAbovePrimers,
+    ProbeRule::NoGRun,
+    ProbeRule::GcContent,
+];
+
+impl ProbeRule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProbeRule::NoFivePrimeG => "no_5p_g",
+            ProbeRule::MoreCThanG => "c_gt_g",
+            ProbeRule::TmAbovePrimers => "tm_delta",
+            ProbeRule::NoGRun => "no_g_run",
+            ProbeRule::GcContent => "gc",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<ProbeRule, String> {
+        ALL_PROBE_RULES
+            .iter()
+            .find(|rule| rule.name() == name.trim())
+            .copied()
+            .ok_or_else(|| {
+                let names: Vec<&str> = ALL_PROBE_RULES.iter().map(|r| r.name()).collect();
+                format!("unknown probe rule: {} ({})", name, names.join(", "))
+            })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProbeRules {
+    /// Rules to apply; the others are not evaluated.
+    pub rules: Vec<ProbeRule>,
+    pub min_gc: f64,
+    pub max_gc: f64,
+    pub max_g_run: usize,
+    pub min_tm_delta: f64,
+    pub max_tm_delta: f64,
+    /// Monovalent cation concentration for Tm (mM).
+    pub na_mm: f64,
+    /// Oligo concentration for Tm (nM).
+    pub oligo_nm: f64,
+}
+
+impl Default for ProbeRules {
+    fn default() -> Self {
+        ProbeRules {
+            rules: ALL_PROBE_RULES.to_vec(),
+            min_gc: 0.3,
+            max_gc: 0.8,
+            max_g_run: 3,
+            min_tm_delta: 8.0,
+            max_tm_delta: 10.0,
+            na_mm: 50.0,
+            oligo_nm: 50.0,
+        }
+    }
+}
+
+/// Outcome of one rule. `passed` is `None` when the rule could not be evaluated,
+/// e.g. the Tm rule without a primer Tm.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProbeRuleResult {
+    pub rule: ProbeRule,
+    pub passed: Option<bool>,
+    pub detail: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProbeEvaluation {
+    pub tm: f64,
+    pub gc: f64,
+    pub results: Vec<ProbeRuleResult>,
+}
+
+impl ProbeEvaluation {
+    /// True unless some evaluated rule failed.
+    pub fn passes(&self) -> bool {
+        self.results.iter().all(|r| r.passed != Some(false))
+    }
+
+    pub fn failed_rules(&self) -> Vec<ProbeRule> {
+        self.results
+            .iter()
+            .filter(|r| r.passed == Some(false))
+            .map(|r| r.rule)
+            .collect()
+    }
+}
+
+impl fmt::Display for ProbeEvaluation {
+    /// `rule:pass|fail|na(detail);...`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let results: Vec<String> = self
+            .results
+            .iter()
+            .map(|r| {
+                let state = match r.passed {
+                    Some(true) => "pass",
+                    Some(false) => "fail",
+                    None => "na",
+                };
+                format!("{}:{}({})", r.rule.name(), state, r.detail)
+            })
+            .collect();
+        write!(f, "{}", results.join(";"))
+    }
+}
+
+impl ProbeRules {
+    /// Rules given as a comma separated list of names, other settings default.
+    pub fn from_names(names: &str) -> Result<ProbeRules, String> {
+        let rules = names
+            .split(',')
+            .filter(|n| !n.trim().is_empty())
+            .map(ProbeRule::from_name)
+            .collect::<Result<Vec<ProbeRule>, String>>()?;
+        Ok(ProbeRules {
+            rules,
+            ..ProbeRules::default()
+        })
+    }
+
+    pub fn tm(&self, sequence: &[u8]) -> f64 {
+        melting_temperature(sequence, self.na_mm, self.oligo_nm)
+    }
+
+    /// Evaluates `probe` (5'->3') against the rules. `primer_tm` is the Tm the
+    /// probe is compared with, usually the higher of the two primers.
+    pub fn evaluate(&self, probe: &[u8], primer_tm: Option<f64>) -> ProbeEvaluation {
+        let probe: Vec<u8> = probe.to_ascii_uppercase();
+        let tm: f64 = self.tm(&probe);
+        let gc: f64 = gc_content(&probe);
+        let results = self
+            .rules
+            .iter()
+            .map(|&rule| {
+                let (passed, detail) = match rule {
+                    ProbeRule::NoFivePrimeG => {
+                        let first = probe.first().copied().unwrap_or(b'N');
+                        (Some(first != b'G'), (first as char).to_string())
+                    }
+                    ProbeRule::MoreCThanG => {
+                        let c = probe.iter().filter(|&&b| b == b'C').count();
+                        let g = probe.iter().filter(|&&b| b == b'G').count();
+                        (Some(c > g), format!("C={},G={}", c, g))
+                    }
+                    ProbeRule::TmAbovePrimers => match primer_tm {
+                        Some(primer_tm) => {
+                            let delta = tm - primer_tm;
+                            (
+                                Some(self.min_tm_delta <= delta && delta <= self.max_tm_delta),
+                                format!("{:+.1}", delta),
+                            )
+                        }
+                        None => (None, "no primer Tm".to_string()),
+                    },
+                    ProbeRule::NoGRun => {
+                        let run = longest_run(&probe, b'G');
+                        (Some(run <= self.max_g_run), run.to_string())
+                    }
+                    ProbeRule::GcContent => (
+                        Some(self.min_gc <= gc && gc <= self.max_gc),
+                        format!("{:.2}", gc),
+                    ),
+                };
+                ProbeRuleResult {
+                    rule,
+                    passed,
+                    detail,
+                }
+            })
+            .collect();
+        ProbeEvaluation { tm, gc, results }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::probe_rules::{melting_temperature, ProbeRule, ProbeRules};
+    use ::function_name::named;
+
+    #[test]
+    #[named]
+    fn melting_temperature_test() {
+        // typical 24-mer primer, 42% GC
+        let tm = melting_temperature(b"AGCGGATAACAATTTCACACAGGA", 50.0, 50.0);
+        assert!(
+            (50.0..60.0).contains(&tm),
+            "{} failed: {}",
+            function_name!(),
+            tm
+        );
+        let short = melting_temperature(b"ACGTACGTAC", 50.0, 50.0);
+        assert!(short < tm, "{} failed: {}", function_name!(), short);
+    }
+
+    #[test]
+    #[named]
+    fn evaluate_test() {
+        let rules = ProbeRules::default();
+        let good = rules.evaluate(b"CCTCACCTTCCACATCCACTCCAACTACCA", None);
+        assert!(
+            good.passes() && good.to_string().contains("tm_delta:na"),
+            "{} failed: {}",
+            function_name!(),
+            good
+        );
+        let bad = rules.evaluate(b"GGGGAGTGAGGTGAAGGATGCTAGTAGCAG", Some(good.tm));
+        assert!(
+            bad.failed_rules()
+                == vec![
+                    ProbeRule::NoFivePrimeG,
+                    ProbeRule::MoreCThanG,
+                    ProbeRule::TmAbovePrimers,
+                    ProbeRule::NoGRun
+                ],
+            "{} failed: {}",
+            function_name!(),
+            bad
+        );
+        let only_gc = ProbeRules::from_names("gc").unwrap();
+        assert!(
+            only_gc
+                .evaluate(b"GGGGAGTGAGGTGAAGGATGCTAGTAGCAG", None)
+                .passes(),
+            "{} failed",
+            function_name!()
+        );
+        assert!(
+            ProbeRules::from_names("gc,foo").is_err(),
+            "{} failed",
+            function_name!()
+        );
+    }
+}
@@ -0,0 +1,285 @@
+// アセンブリ(コンティグ)上のLR-tupleを、両鎖について位置付きで列挙する。
+// リードと違い被覆度がないので、コピー数は異なる座位の数として数える。
+// 座位は順鎖の座標(0-origin, 半開区間)で表し、逆鎖で見つかったものも順鎖に写す。
+
+use crate::counting_bloomfilter_util::{
+    count_high_occurence_lr_tuple, locate_lr_tuple, L_LEN, R_LEN,
+};
+use crate::sequence_encoder_util::{acgt_ranges, decode_u128_2_dna_seq, DnaSequence};
+use bio::io::fasta::FastaRead;
+use bio::io::fasta::Reader as faReader;
+use bio::io::fasta::Record as faRecord;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Write};
+use std::thread;
+
+/// A stretch of a contig without ambiguous bases, on one strand.
+pub struct ReferenceSegment {
+    pub contig: usize,
+    /// Start of the segment on the contig.
+    pub offset: usize,
+    /// '+' for the contig as given, '-' for its reverse complement.
+    pub strand: char,
+    pub sequence: DnaSequence,
+}
+
+impl ReferenceSegment {
+    /// Splits contig `contig` at every base other than A, C, G and T, and gives
+    /// each stretch on both strands.
+    pub fn from_contig(contig: usize, sequence: &[u8]) -> Vec<ReferenceSegment> {
+        acgt_ranges(sequence)
+            .into_iter()
+            .flat_map(|range| {
+                let forward = DnaSequence::new(&sequence[range.clone()].to_vec());
+                let reverse = forward.reverse_complement();
+                [
+                    ReferenceSegment {
+                        contig,
+                        offset: range.start,
+                        strand: '+',
+                        sequence: forward,
+                    },
+                    ReferenceSegment {
+                        contig,
+                        offset: range.start,
+                        strand: '-',
+                        sequence: reverse,
+                    },
+                ]
+            })
+            .collect()
+    }
+}
+
+impl AsRef<DnaSequence> for ReferenceSegment {
+    fn as_ref(&self) -> &DnaSequence {
+        &self.sequence
+    }
+}
+
+/// Contig names and segments of an assembly in FASTA. A segment's `contig` is
+/// the index of its name.
+pub fn load_reference(path: &str) -> io::Result<(Vec<String>, Vec<ReferenceSegment>)> {
+    let with_path = |e: io::Error| io::Error::new(e.kind(), format!("{}: {}", path, e));
+    let mut reader = faReader::new(File::open(path).map_err(with_path)?);
+    let mut record: faRecord = faRecord::new();
+    let mut contig_names: Vec<String> = Vec::new();
+    let mut segments: Vec<ReferenceSegment> = Vec::new();
+    loop {
+        reader.read(&mut record).map_err(with_path)?;
+        if record.is_empty() {
+            break;
+        }
+        segments.extend(ReferenceSegment::from_contig(
+            contig_names.len(),
+            record.seq(),
+        ));
+        contig_names.push(record.id().to_string());
+    }
+    eprintln!(
+        "loading {:?} done: {} contigs, {} segments",
+        path,
+        contig_names.len(),
+        segments.len()
+    );
+    Ok((contig_names, segments))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Locus {
+    pub contig: usize,
+    /// Forward-strand start of the locus: the start of the L window on '+'
+    /// and of the R window on '-'.
+    pub start: usize,
+    /// Forward-strand end of the locus, exclusive: the end of the R window on
+    /// '+' and of the L window on '-'.
+    pub end: usize,
+    pub strand: char,
+}
+
+/// Loci of every candidate in `segments`, sorted and with one locus per
+/// contig interval (a tuple reading the same on both strands is one copy).
+pub fn locate_candidates(
+    segments: &[ReferenceSegment],
+    candidates: &HashSet<u128>,
+    threads: usize,
+    margin: usize,
+) -> HashMap<u128, Vec<Locus>> {
+    let mut loci: HashMap<u128, Vec<Locus>> = HashMap::new();
+    if segments.is_empty() || candidates.is_empty() {
+        return loci;
+    }
+    let chunk_size: usize = segments.len().div_ceil(threads.max(1));
+    thread::scope(|scope| {
+        let mut children = Vec::new();
+        for chunk in segments.chunks(chunk_size) {
+            children.push(scope.spawn(move || {
+                let mut local: Vec<(u128, Locus)> = Vec::new();
+                for segment in chunk {
+                    let length = segment.sequence.len();
+                    for (tuple, start, end) in
+                        locate_lr_tuple(&segment.sequence, candidates, margin)
+                    {
+                        let (start, end) = match segment.strand {
+                            '+' => (start, end),
+                            _ => (length - end, length - start),
+                        };
+                        local.push((
+                            tuple,
+                            Locus {
+                                contig: segment.contig,
+                                start: segment.offset + start,
+                                end: segment.offset + end,
+                                strand: segment.strand,
+                            },
+                        ));
+                    }
+                }
+                local
+            }));
+        }
+        for child in children {
+            for (tuple, locus) in child.join().unwrap() {
+                loci.entry(tuple).or_default().push(locus);
+            }
+        }
+    });
+    for each_loci in loci.values_mut() {
+        each_loci.sort();
+        each_loci.dedup_by_key(|l| (l.contig, l.start, l.end));
+    }
+    loci
+}
+
+/// Tuples found at `threshold` or more distinct loci, sorted by tuple.
+/// Occurence over both strands narrows the candidates before they are located.
+pub fn reference_lr_tuple_loci(
+    segments: &[ReferenceSegment],
+    threshold: u16,
+    threads: usize,
+    margin: usize,
+    cbf_size: usize,
+) -> Vec<(u128, Vec<Locus>)> {
+    let candidates: HashSet<u128> =
+        count_high_occurence_lr_tuple(segments, threshold, threads, margin, cbf_size)
+            .into_keys()
+            .collect();
+    let mut loci: Vec<(u128, Vec<Locus>)> =
+        locate_candidates(segments, &candidates, threads, margin)
+            .into_iter()
+            .filter(|(_, each_loci)| each_loci.len() >= threshold as usize)
+            .collect();
+    loci.sort();
+    eprintln!(
+        "{} candidates by occurence, {} lr-tuples at {} or more loci",
+        candidates.len(),
+        loci.len(),
+        threshold
+    );
+    loci
+}
+
+pub fn locus_tsv_header() -> String {
+    "lr_tuple\tcopies\tloci\n".to_string()
+}
+
+/// `loci` are written as `contig:start-end(strand)` with 1-origin inclusive coordinates.
+pub fn locus_tsv_line(tuple: &[u8], loci: &[Locus], contig_names: &[String]) -> String {
+    let loci_str: Vec<String> = loci
+        .iter()
+        .map(|l| {
+            format!(
+                "{}:{}-{}({})",
+                contig_names[l.contig],
+                l.start + 1,
+                l.end,
+                l.strand
+            )
+        })
+        .collect();
+    format!(
+        "{}\t{}\t{}\n",
+        String::from_utf8_lossy(tuple),
+        loci.len(),
+        loci_str.join(",")
+    )
+}
+
+/// Writes `loci` as a TSV with a header line.
+pub fn write_locus_tsv<W: Write>(
+    w: &mut W,
+    loci: &[(u128, Vec<Locus>)],
+    contig_names: &[String],
+) -> io::Result<()> {
+    w.write_all(locus_tsv_header().as_bytes())?;
+    for (tuple, each_loci) in loci {
+        let bases: Vec<u8> = decode_u128_2_dna_seq(tuple, L_LEN + R_LEN);
+        w.write_all(locus_tsv_line(&bases, each_loci, contig_names).as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::in_silico_pcr::reverse_complement;
+    use crate::reference_loci::{locate_candidates, locus_tsv_line, Locus, ReferenceSegment};
+    use ::function_name::named;
+    use std::collections::HashSet;
+
+    #[test]
+    #[named]
+    fn locate_candidates_test() {
+        let unit: Vec<u8> =
+            b"ACGTTGCAAGGCTTACCGTAGGATCCTTAGCAGTCAAGTCTTGACCATGCGATACGGTCAGTACCTGAAGCTTCGAT"
+                .to_vec();
+        // 1コピー目は順鎖、2コピー目はNを挟んだ逆鎖
+        let contig: Vec<u8> = [
+            &b"TTGA"[..],
+            &unit,
+            b"NNNNN",
+            &reverse_complement(&unit),
+            b"CA",
+        ]
+        .concat();
+        let segments = ReferenceSegment::from_contig(0, &contig);
+        let head: u128 = segments[0]
+            .sequence
+            .subsequence_as_u128(vec![[4, 36], [36, 68]]);
+        let loci = locate_candidates(&segments, &HashSet::from([head]), 2, 0);
+        let found = loci.get(&head).cloned().unwrap_or_default();
+        let second_end = 4 + unit.len() + 5 + unit.len();
+        assert!(
+            segments.len() == 4
+                && found
+                    == vec![
+                        Locus {
+                            contig: 0,
+                            start: 4,
+                            end: 68,
+                            strand: '+'
+                        },
+                        Locus {
+                            contig: 0,
+                            start: second_end - 64,
+                            end: second_end,
+                            strand: '-'
+                        }
+                    ],
+            "{} failed: {:?}",
+            function_name!(),
+            found
+        );
+        let line = locus_tsv_line(b"ACGT", &found, &["chr1".to_string()]);
+        assert!(
+            line == format!(
+                "ACGT\t2\tchr1:5-68(+),chr1:{}-{}(-)\n",
+                second_end - 63,
+                second_end
+            ),
+            "{} failed: {}",
+            function_name!(),
+            line
+        );
+    }
+}
@@ -82,6 +82,37 @@ pub fn decode_u128_r(source: &u128) -> Vec<u8> {
     return result;
 }
 
+/// Splits `source` at every base other than A, C, G and T, so that genomes with
+/// N runs or IUPAC codes can be encoded as `DnaSequence`s. Empty pieces are dropped.
+pub fn acgt_segments(source: &[u8]) -> Vec<&[u8]> {
+    acgt_ranges(source)
+        .into_iter()
+        .map(|range| &source[range])
+        .collect()
+}
+
+/// Positions of the pieces `acgt_segments` returns, for callers that report
+/// coordinates on the original sequence.
+pub fn acgt_ranges(source: &[u8]) -> Vec<std::ops::Range<usize>> {
+    let mut ranges: Vec<std::ops::Range<usize>> = Vec::new();
+    let mut start: Option<usize> = None;
+    for (i, base) in source.iter().enumerate() {
+        let is_acgt = matches!(base, b'A' | b'C' | b'G' | b'T' | b'a' | b'c' | b'g' | b't');
+        match (is_acgt, start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                ranges.push(s..i);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        ranges.push(s..source.len());
+    }
+    ranges
+}
+
 pub struct DnaSequence {
     length: usize,
     sequence: Vec<u64>,
@@ -94,6 +125,11 @@ impl Clone for DnaSequence {
         }
     }
 }
+impl AsRef<DnaSequence> for DnaSequence {
+    fn as_ref(&self) -> &DnaSequence {
+        self
+    }
+}
 
 impl DnaSequence {
     pub fn new(source: &Vec<u8>) -> DnaSequence {
@@ -677,6 +713,7 @@ impl DnaSequence {
 mod tests {
     use crate::sequence_encoder_util::decode_u128_2_dna_seq;
     use crate::sequence_encoder_util::DnaSequence;
+    use crate::sequence_encoder_util::{acgt_ranges, acgt_segments};
     use ::function_name::named;
     /*
      *
@@ -2058,4 +2095,18 @@ mod tests {
         let answer: String = "AAAAAAAAAAAAGGGGGGGGGGGGGGGGGGGG".to_string();
         assert!(v2 == answer, "{} failed", function_name!());
     }
+
+    #[test]
+    #[named]
+    fn acgt_segments_test() {
+        let segments = acgt_segments(b"NNACGTNNNacgRTTN");
+        assert!(
+            segments == vec![&b"ACGT"[..], b"acg", b"TT"]
+                && acgt_ranges(b"NNACGTNNNacgRTTN") == vec![2..6, 9..12, 13..15]
+                && acgt_ranges(b"ACGTNA") == vec![0..4, 5..6],
+            "{} failed: {:?}",
+            function_name!(),
+            segments
+        );
+    }
 }
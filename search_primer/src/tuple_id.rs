@@ -0,0 +1,251 @@
+// tupleの識別子。primer3_coreのSEQUENCE_ID、BLASTの問い合わせ名、FASTAの見出しに共通して使い、元のtupleに戻せる。
+//   lr_{L_LEN}.{R_LEN}_{u128を32桁}
+//   probe_{塩基数}_{u128を32桁}
+//   lmr_{l_len}.{m_len}.{r_len}_{m_offset}.{r_offset}_{l, m, rのu128を32桁ずつ}
+// hexは小文字で桁数固定。'-'を使わないので、"-L"や"_l"のような接尾辞をつけても先頭から読み出せる。
+
+use crate::counting_bloomfilter_util::{L_LEN, R_LEN};
+use crate::sequence_encoder_util::decode_u128_2_dna_seq;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TupleId {
+    /// L and R of `L_LEN` and `R_LEN` bases packed into one u128.
+    Lr(u128),
+    /// A probe of `len` bases, right-aligned in the u128.
+    Probe { value: u128, len: u8 },
+    /// Windows of up to 64 bases right-aligned in each u128; offsets are from the start of L.
+    Lmr {
+        l: u128,
+        m: u128,
+        r: u128,
+        lens: [u8; 3],
+        m_offset: u16,
+        r_offset: u16,
+    },
+}
+
+impl fmt::Display for TupleId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TupleId::Lr(v) => write!(f, "lr_{}.{}_{:032x}", L_LEN, R_LEN, v),
+            TupleId::Probe { value, len } => write!(f, "probe_{}_{:032x}", len, value),
+            TupleId::Lmr {
+                l,
+                m,
+                r,
+                lens,
+                m_offset,
+                r_offset,
+            } => write!(
+                f,
+                "lmr_{}.{}.{}_{}.{}_{:032x}{:032x}{:032x}",
+                lens[0], lens[1], lens[2], m_offset, r_offset, l, m, r
+            ),
+        }
+    }
+}
+
+impl FromStr for TupleId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TupleId, String> {
+        match TupleId::parse_prefix(s) {
+            Some((id, used)) if used == s.len() => Ok(id),
+            _ => Err(format!("invalid tuple id {:?}", s)),
+        }
+    }
+}
+
+/// Reads `digits` hex digits at the start of `s`.
+fn hex_at(s: &str, digits: usize) -> Option<u128> {
+    let field = s.get(..digits)?;
+    if !field.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u128::from_str_radix(field, 16).ok()
+}
+
+/// Reads a decimal number at the start of `s` and returns it with the digits used.
+fn decimal_at<T: FromStr>(s: &str) -> Option<(T, usize)> {
+    let digits = s.bytes().take_while(|b| b.is_ascii_digit()).count();
+    Some((s[..digits].parse().ok()?, digits))
+}
+
+/// Reads decimal numbers separated by `sep`, followed by `_`, and returns them with the bytes used.
+fn geometry_at<T: FromStr + Copy>(s: &str, count: usize, sep: char) -> Option<(Vec<T>, usize)> {
+    let mut values: Vec<T> = Vec::with_capacity(count);
+    let mut used: usize = 0;
+    for i in 0..count {
+        let (value, digits) = decimal_at::<T>(&s[used..])?;
+        values.push(value);
+        used += digits;
+        let expected = if i + 1 == count { '_' } else { sep };
+        if !s[used..].starts_with(expected) {
+            return None;
+        }
+        used += 1;
+    }
+    Some((values, used))
+}
+
+impl TupleId {
+    /// Reads an id at the start of `s`, returning it with the number of bytes it takes,
+    /// so that suffixes such as `-L` can follow.
+    pub fn parse_prefix(s: &str) -> Option<(TupleId, usize)> {
+        if let Some(rest) = s.strip_prefix("lr_") {
+            let (lens, used) = geometry_at::<usize>(rest, 2, '.')?;
+            if lens != [L_LEN, R_LEN] {
+                return None;
+            }
+            let value = hex_at(&rest[used..], 32)?;
+            return Some((TupleId::Lr(value), 3 + used + 32));
+        }
+        if let Some(rest) = s.strip_prefix("probe_") {
+            let (len, used) = geometry_at::<u8>(rest, 1, '.')?;
+            if len[0] as usize > 64 {
+                return None;
+            }
+            let value = hex_at(&rest[used..], 32)?;
+            return Some((TupleId::Probe { value, len: len[0] }, 6 + used + 32));
+        }
+        if let Some(rest) = s.strip_prefix("lmr_") {
+            let (lens, lens_used) = geometry_at::<u8>(rest, 3, '.')?;
+            let (offsets, offsets_used) = geometry_at::<u16>(&rest[lens_used..], 2, '.')?;
+            let used = lens_used + offsets_used;
+            let words: Vec<u128> = (0..3)
+                .map(|i| hex_at(rest.get(used + 32 * i..)?, 32))
+                .collect::<Option<Vec<u128>>>()?;
+            if lens.iter().any(|&len| len > 64) {
+                return None;
+            }
+            return Some((
+                TupleId::Lmr {
+                    l: words[0],
+                    m: words[1],
+                    r: words[2],
+                    lens: [lens[0], lens[1], lens[2]],
+                    m_offset: offsets[0],
+                    r_offset: offsets[1],
+                },
+                4 + used + 96,
+            ));
+        }
+        None
+    }
+
+    /// Every id in `text` with its byte range. An id must not follow a letter, digit or `_`.
+    pub fn find_all(text: &str) -> Vec<(Range<usize>, TupleId)> {
+        let bytes = text.as_bytes();
+        let mut found: Vec<(Range<usize>, TupleId)> = Vec::new();
+        let mut i: usize = 0;
+        while i < bytes.len() {
+            let at_boundary =
+                i == 0 || !(bytes[i - 1].is_ascii_alphanumeric() || bytes[i - 1] == b'_');
+            if at_boundary && text.is_char_boundary(i) {
+                if let Some((id, used)) = TupleId::parse_prefix(&text[i..]) {
+                    found.push((i..i + used, id));
+                    i += used;
+                    continue;
+                }
+            }
+            i += 1;
+        }
+        found
+    }
+
+    /// Windows of the tuple named `L`, `M`, `R` or `P`, with their bases.
+    pub fn windows(&self) -> Vec<(char, Vec<u8>)> {
+        match self {
+            TupleId::Lr(v) => {
+                let bases = decode_u128_2_dna_seq(v, L_LEN + R_LEN);
+                vec![
+                    ('L', bases[..L_LEN].to_vec()),
+                    ('R', bases[L_LEN..].to_vec()),
+                ]
+            }
+            TupleId::Probe { value, len } => {
+                vec![('P', decode_u128_2_dna_seq(value, *len as usize))]
+            }
+            TupleId::Lmr { l, m, r, lens, .. } => vec![
+                ('L', decode_u128_2_dna_seq(l, lens[0] as usize)),
+                ('M', decode_u128_2_dna_seq(m, lens[1] as usize)),
+                ('R', decode_u128_2_dna_seq(r, lens[2] as usize)),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tuple_id::TupleId;
+    use ::function_name::named;
+
+    #[test]
+    #[named]
+    fn tuple_id_round_trip_test() {
+        let lmr = TupleId::Lmr {
+            l: 0x1b,
+            m: 2,
+            r: 3,
+            lens: [4, 2, 3],
+            m_offset: 10,
+            r_offset: 20,
+        };
+        let ids = [TupleId::Lr(0x1b), TupleId::Probe { value: 4, len: 30 }, lmr];
+        let names: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+        assert!(
+            names[0] == format!("lr_32.32_{:032x}", 0x1b)
+                && names[2] == format!("lmr_4.2.3_10.20_{:032x}{:032x}{:032x}", 0x1b, 2, 3)
+                && ids
+                    .iter()
+                    .zip(&names)
+                    .all(|(id, name)| name.parse::<TupleId>() == Ok(*id)),
+            "{} failed: {:?}",
+            function_name!(),
+            names
+        );
+        assert!(
+            "lr_16.48_0000000000000000000000000000001b"
+                .parse::<TupleId>()
+                .is_err()
+                && "lr_32.32_1b".parse::<TupleId>().is_err()
+                && format!("lmr_65.2.3_10.80_{}", "0".repeat(96))
+                    .parse::<TupleId>()
+                    .is_err()
+                && format!("{}x", names[0]).parse::<TupleId>().is_err(),
+            "{} failed",
+            function_name!()
+        );
+        let windows = lmr.windows();
+        assert!(
+            windows
+                == vec![
+                    ('L', b"ACGT".to_vec()),
+                    ('M', b"AG".to_vec()),
+                    ('R', b"AAT".to_vec())
+                ],
+            "{} failed: {:?}",
+            function_name!(),
+            windows
+        );
+    }
+
+    #[test]
+    #[named]
+    fn tuple_id_find_all_test() {
+        let lr = TupleId::Lr(5);
+        let text = format!(">{}-L\t{}_r\tx{}\n", lr, lr, lr);
+        let found = TupleId::find_all(&text);
+        assert!(
+            found.len() == 2
+                && found[0].0 == (1..1 + lr.to_string().len())
+                && found.iter().all(|(_, id)| *id == lr),
+            "{} failed: {:?}",
+            function_name!(),
+            found
+        );
+    }
+}
@@ -0,0 +1,181 @@
+// 各パイプラインが書き出す固定長のtuple(lr-tuple, プローブのk-mer, lmr-tuple)に共通の操作。
+// バイト列への変換、窓の位置と長さ、塩基配列、逆相補鎖をまとめ、読み書きやマージを一度だけ書けばよいようにする。
+// どの型もバイト列の辞書順と値の順序が一致する。
+// lr-tupleはL(上位64bit)とR(下位64bit)を詰めたu128で、16 byte big endianで書く。
+
+use crate::counting_bloomfilter_util::{L_LEN, R_LEN};
+use crate::tuple_id::TupleId;
+use std::io::{self, ErrorKind, Read, Write};
+
+/// A tuple written as a fixed number of bytes whose order is the order of the tuples.
+pub trait TupleRecord: Copy + Ord {
+    /// Bytes of one record in a binary file.
+    const RECORD_SIZE: usize;
+
+    fn to_bytes(&self) -> Vec<u8>;
+
+    /// Fails unless `bytes` is `RECORD_SIZE` long and holds a valid tuple.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, String>;
+
+    /// Id naming the kind, the geometry and the value of the tuple.
+    fn tuple_id(&self) -> TupleId;
+
+    /// Windows named `L`, `M`, `R` or `P` with their offset from the start of the tuple and their length.
+    fn geometry(&self) -> Vec<(char, usize, usize)>;
+
+    /// The same tuple read on the other strand.
+    fn reverse_complement(&self) -> Self;
+
+    /// Bases of each window, in the order of `geometry`.
+    fn windows(&self) -> Vec<(char, Vec<u8>)> {
+        self.tuple_id().windows()
+    }
+
+    /// Bases of all windows joined, without the gaps between them.
+    fn sequence(&self) -> Vec<u8> {
+        self.windows()
+            .into_iter()
+            .flat_map(|(_, bases)| bases)
+            .collect()
+    }
+}
+
+/// Reverse complement of `bases` bases right-aligned in `value` (A=0, C=1, G=2, T=3).
+pub fn reverse_complement_bits(value: u128, bases: usize) -> u128 {
+    let mut rest = value;
+    let mut result: u128 = 0;
+    for _ in 0..bases {
+        result = (result << 2) | (3 - (rest & 3));
+        rest >>= 2;
+    }
+    result
+}
+
+/// Every record of a binary file; a file that ends inside a record is an error.
+pub fn read_tuple_records<T: TupleRecord, R: Read>(r: &mut R) -> io::Result<Vec<T>> {
+    let mut bytes: Vec<u8> = Vec::new();
+    r.read_to_end(&mut bytes)?;
+    if !bytes.len().is_multiple_of(T::RECORD_SIZE) {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "{} bytes is not a whole number of {} byte records",
+                bytes.len(),
+                T::RECORD_SIZE
+            ),
+        ));
+    }
+    bytes
+        .chunks_exact(T::RECORD_SIZE)
+        .enumerate()
+        .map(|(i, chunk)| {
+            T::from_bytes(chunk)
+                .map_err(|e| io::Error::new(ErrorKind::InvalidData, format!("record {}: {}", i, e)))
+        })
+        .collect()
+}
+
+/// `bytes` as an array of `N` bytes, for `from_bytes` of fixed size records.
+pub fn record_bytes<const N: usize>(bytes: &[u8]) -> Result<[u8; N], String> {
+    bytes
+        .try_into()
+        .map_err(|_| format!("a record is {} bytes, not {}", N, bytes.len()))
+}
+
+pub fn write_tuple_records<T: TupleRecord, W: Write>(
+    w: &mut W,
+    records: impl IntoIterator<Item = T>,
+) -> io::Result<()> {
+    for record in records {
+        w.write_all(&record.to_bytes())?;
+    }
+    Ok(())
+}
+
+/// An lr-tuple: L of `L_LEN` bases followed by R of `R_LEN` bases.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LrTuple(pub u128);
+
+impl TupleRecord for LrTuple {
+    const RECORD_SIZE: usize = 16;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_be_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<LrTuple, String> {
+        Ok(LrTuple(u128::from_be_bytes(record_bytes(bytes)?)))
+    }
+
+    fn tuple_id(&self) -> TupleId {
+        TupleId::Lr(self.0)
+    }
+
+    fn geometry(&self) -> Vec<(char, usize, usize)> {
+        vec![('L', 0, L_LEN), ('R', L_LEN, R_LEN)]
+    }
+
+    // 反対の鎖ではRの逆相補鎖がL、Lの逆相補鎖がRになるので、L+R全体の逆相補鎖と同じ
+    fn reverse_complement(&self) -> LrTuple {
+        LrTuple(reverse_complement_bits(self.0, L_LEN + R_LEN))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sequence_encoder_util::DnaSequence;
+    use crate::tuple_record::{
+        read_tuple_records, reverse_complement_bits, write_tuple_records, LrTuple, TupleRecord,
+    };
+    use ::function_name::named;
+
+    #[test]
+    #[named]
+    fn lr_tuple_record_test() {
+        let bases = b"ACGTTTGCAAGGCCTTACGATCGATCGGGAAACCCTTTGGGAAATTTCCCGGGATATATCGCGC".to_vec();
+        let tuple = LrTuple(DnaSequence::new(&bases).subsequence_as_u128(vec![[0, 64]]));
+        let rc = tuple.reverse_complement();
+        let expected: Vec<u8> = bases
+            .iter()
+            .rev()
+            .map(|b| match b {
+                b'A' => b'T',
+                b'C' => b'G',
+                b'G' => b'C',
+                _ => b'A',
+            })
+            .collect();
+        assert!(
+            tuple.sequence() == bases
+                && rc.sequence() == expected
+                && rc.reverse_complement() == tuple
+                && tuple.windows()[1] == ('R', bases[32..].to_vec())
+                && tuple.geometry() == vec![('L', 0, 32), ('R', 32, 32)]
+                && reverse_complement_bits(0b0001, 2) == 0b1011,
+            "{} failed: {:?}",
+            function_name!(),
+            String::from_utf8(rc.sequence())
+        );
+
+        let tuples = vec![LrTuple(1 << 100), LrTuple(3), LrTuple(1 << 64)];
+        let mut bytes: Vec<u8> = Vec::new();
+        write_tuple_records(&mut bytes, tuples.iter().copied()).unwrap();
+        let read: Vec<LrTuple> = read_tuple_records(&mut bytes.as_slice()).unwrap();
+        let mut chunks: Vec<&[u8]> = bytes.chunks(LrTuple::RECORD_SIZE).collect();
+        let mut sorted = tuples.clone();
+        chunks.sort();
+        sorted.sort();
+        assert!(
+            read == tuples
+                && chunks
+                    .iter()
+                    .map(|chunk| LrTuple::from_bytes(chunk).unwrap())
+                    .eq(sorted.iter().copied())
+                && LrTuple::from_bytes(&bytes[..15]).is_err()
+                && read_tuple_records::<LrTuple, _>(&mut &bytes[1..]).is_err(),
+            "{} failed: {:?}",
+            function_name!(),
+            read
+        );
+    }
+}
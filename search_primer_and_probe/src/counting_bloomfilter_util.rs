@@ -5,100 +5,149 @@ pub const HASHSET_SIZE: usize = (u32::MAX >> 4) as usize;
 pub const BLOOMFILTER_TABLE_SIZE: usize = (u32::MAX >> 1) as usize;
 //const length: usize = 141;
 const DUPPLICATION: u32 = 1;
-use crate::sequence_encoder_util::{DnaSequence, LmrTuple};
+use crate::sequence_encoder_util::{DnaSequence, LmrTuple, MAX_WINDOW_LEN};
+use search_primer::probe_rules::ProbeRules;
 
 use std::time::{Instant};
+use std::time::Duration;
 use std::collections::HashSet;
+use std::cmp;
 
 
-//全てのL, M, Rと、hash値を出力する
-//部分配列のdecoderを書き、テストする
-pub fn build_counting_bloom_filter(sequences: &Vec<DnaSequence>, start_idx: usize, end_idx: usize, length: usize, thread_id: usize) -> Vec<u32>{
-    let mut l_window_start: usize;
-    let mut l_window_end:   usize;
-    let mut m_window_start: usize;
-    let mut m_window_end:   usize;
-    let mut r_window_start: usize;
-    let mut r_window_end:   usize;
+//L, M, Rの長さと、L→M, M→Rの間隔(塩基数)の範囲。max_lengthはLの先頭からRの末尾までの最大長。
+//probe_rulesがあれば、ルールを満たさないM窓は数えない(Tmの比較にはprimer_tmを使う)。
+//table_sizeはcounting bloom filterのカウンタの数。
+#[derive(Clone, Debug, PartialEq)]
+pub struct LmrParams{
+    pub l_len: usize,
+    pub m_len: usize,
+    pub r_len: usize,
+    pub min_lm_gap: usize,
+    pub max_lm_gap: usize,
+    pub min_mr_gap: usize,
+    pub max_mr_gap: usize,
+    pub max_length: usize,
+    pub probe_rules: Option<ProbeRules>,
+    pub primer_tm: Option<f64>,
+    pub table_size: usize,
+}
+
+impl Default for LmrParams{
+    fn default() -> Self{
+        LmrParams{l_len: L_LEN, m_len: M_LEN, r_len: R_LEN, min_lm_gap: 0, max_lm_gap: usize::MAX, min_mr_gap: 0, max_mr_gap: usize::MAX, max_length: 200, probe_rules: None, primer_tm: None, table_size: BLOOMFILTER_TABLE_SIZE}
+    }
+}
+
+impl LmrParams{
+    pub fn validate(&self) -> Result<(), String>{
+        //has_repeatは4塩基以上の区間しか扱えない
+        for (name, len) in [("L", self.l_len), ("M", self.m_len), ("R", self.r_len)]{
+            if !(4..=MAX_WINDOW_LEN).contains(&len){
+                return Err(format!("{} length must be between 4 and {}: {}", name, MAX_WINDOW_LEN, len));
+            }
+        }
+        if self.min_lm_gap > self.max_lm_gap{
+            return Err(format!("minimum L-M gap {} is larger than maximum {}", self.min_lm_gap, self.max_lm_gap));
+        }
+        if self.min_mr_gap > self.max_mr_gap{
+            return Err(format!("minimum M-R gap {} is larger than maximum {}", self.min_mr_gap, self.max_mr_gap));
+        }
+        let shortest: usize = self.l_len + self.min_lm_gap + self.m_len + self.min_mr_gap + self.r_len;
+        if shortest > self.max_length{
+            return Err(format!("shortest L-M-R ({} bases) is longer than the maximum length {}", shortest, self.max_length));
+        }
+        if self.max_length > u16::MAX as usize{
+            return Err(format!("maximum length must be {} or less: {}", u16::MAX, self.max_length));
+        }
+        //hashの添字はu32
+        if self.table_size == 0 || self.table_size > u32::MAX as usize{
+            return Err(format!("table size must be between 1 and {}: {}", u32::MAX, self.table_size));
+        }
+        Ok(())
+    }
+}
+
+//各位置から始まるlen塩基の窓がrepeatを含むかどうか
+fn repeat_mask(sequence: &DnaSequence, len: usize) -> Vec<bool>{
+    if sequence.len() < len{
+        return Vec::new();
+    }
+    (0..=sequence.len() - len).map(|start| sequence.has_repeat(start, start + len).0).collect()
+}
 
+//repeatを含まない全てのL, M, Rの窓の組み合わせについて、[[L], [M], [R]]の座標でfを呼ぶ
+pub fn for_each_lmr_window<F: FnMut([[usize; 2]; 3])>(sequence: &DnaSequence, params: &LmrParams, mut f: F){
+    let seq_len: usize = sequence.len();
+    if seq_len < params.l_len + params.m_len + params.r_len{
+        return;
+    }
+    let l_repeat: Vec<bool> = repeat_mask(sequence, params.l_len);
+    let mut m_repeat: Vec<bool> = repeat_mask(sequence, params.m_len);
+    if let Some(rules) = &params.probe_rules{
+        for (m_start, rejected) in m_repeat.iter_mut().enumerate(){
+            if !*rejected{
+                *rejected = !rules.evaluate(&sequence.decode(m_start, m_start + params.m_len), params.primer_tm).passes();
+            }
+        }
+    }
+    let r_repeat: Vec<bool> = repeat_mask(sequence, params.r_len);
+    for (l_start, _) in l_repeat.iter().enumerate().filter(|(_, has_repeat)| !**has_repeat){
+        let l_end: usize = l_start + params.l_len;
+        let limit: usize = cmp::min(seq_len, l_start.saturating_add(params.max_length));
+        let m_first: usize = l_end + params.min_lm_gap;
+        let m_last: usize = l_end.saturating_add(params.max_lm_gap);
+        let mut m_start: usize = m_first;
+        while m_start <= m_last && m_start + params.m_len + params.min_mr_gap + params.r_len <= limit{
+            if m_repeat[m_start]{
+                m_start += 1;
+                continue;
+            }
+            let m_end: usize = m_start + params.m_len;
+            let r_last: usize = m_end.saturating_add(params.max_mr_gap);
+            let mut r_start: usize = m_end + params.min_mr_gap;
+            while r_start <= r_last && r_start + params.r_len <= limit{
+                if !r_repeat[r_start]{
+                    f([[l_start, l_end], [m_start, m_end], [r_start, r_start + params.r_len]]);
+                }
+                r_start += 1;
+            }
+            m_start += 1;
+        }
+    }
+}
+
+fn log_progress(phase: &str, thread_id: usize, range: [usize; 2], loop_cnt: usize, seq_len: usize, elapsed: Duration, extra: String){
+    eprintln!("{}[{:02?}]({}-{}, length is {}): {:09?}\tlength: {}\tsec: {}.{:03}\t subject to add bloom filter: {}", phase, thread_id, range[0], range[1], range[1] - range[0], loop_cnt, seq_len, elapsed.as_secs(), elapsed.subsec_millis(), extra);
+}
+
+//全てのL, M, Rと、hash値を出力する
+pub fn build_counting_bloom_filter(sequences: &Vec<DnaSequence>, start_idx: usize, end_idx: usize, params: &LmrParams, thread_id: usize) -> Vec<u32>{
     let mut loop_cnt:usize = 0;
-    eprintln!("Allocating Vec<u32> where BLOOMFILTER_TABLE_SIZE = {}", BLOOMFILTER_TABLE_SIZE);
-    let mut ret_array: Vec<u32> = vec![0;BLOOMFILTER_TABLE_SIZE];
-    eprintln!("Filling Vec<u32; {}> with 0", BLOOMFILTER_TABLE_SIZE);
+    eprintln!("Allocating Vec<u32> where table size = {}", params.table_size);
+    let mut ret_array: Vec<u32> = vec![0;params.table_size];
     eprintln!("finish allocating");
 
     let start_time = Instant::now();
     let mut previous_time = start_time.elapsed();
 
-    'each_read: for current_sequence in sequences[start_idx..end_idx].iter() {
+    for current_sequence in sequences[start_idx..end_idx].iter() {
         let mut add_bloom_filter_cnt: usize = 0;
-        let mut l_window_cnt: usize         = 0;
         loop_cnt += 1;
-        l_window_start = 0;
-        if current_sequence.len() < L_LEN ||current_sequence.len() < M_LEN ||current_sequence.len() < R_LEN{
-            continue 'each_read;
-        }
-        'each_l_window: loop{
-            l_window_end = l_window_start + L_LEN;
-            if l_window_end >= current_sequence.len() + 1{
-                break 'each_l_window;
-            }
-            l_window_cnt += 1;
-            let (l_has_repeat_bool, l_has_repeat_offset) = current_sequence.has_repeat(l_window_start, l_window_end);
-            if l_has_repeat_bool {
-                l_window_start += l_has_repeat_offset + 1;
-                continue 'each_l_window;
-            }
-            m_window_start = l_window_end;
-            'each_m_window: loop{
-                m_window_end = m_window_start + M_LEN;
-                if m_window_end >= current_sequence.len() + 1{
-                    break 'each_m_window;
-                }
-                if m_window_end - l_window_start >= length - R_LEN{
-                    break 'each_m_window;
+        for_each_lmr_window(current_sequence, params, |ranges| {
+            add_bloom_filter_cnt += 1;
+            let lmr_string: LmrTuple = current_sequence.subsequence_as_lmrtuple(ranges);
+            let table_indice:[u32;8] = lmr_string.hash(params.table_size);
+            for idx in table_indice{
+                let idx: usize = idx as usize;
+                if ret_array[idx] == u32::MAX{
+                    eprintln!("index {} reaches u32::MAX", idx);
+                }else{
+                    ret_array[idx] += 1;
                 }
-                let (m_has_repeat_bool, m_has_repeat_offset) = current_sequence.has_repeat(m_window_start, m_window_end);
-                if m_has_repeat_bool {
-                    m_window_start += m_has_repeat_offset + 1;
-                    continue 'each_m_window;
-                }
-                r_window_start = l_window_end;
-                'each_r_window: loop{
-                    r_window_end = r_window_start + R_LEN;
-                    if r_window_end >= current_sequence.len() + 1{
-                        let end = start_time.elapsed();
-                        eprintln!("1st loop[{:02?}]({}-{}, length is {}): {:09?}\tlength: {}\tsec: {}.{:03}\t subject to add bloom filter: {}\tl_window_cnt: {}",thread_id, start_idx, end_idx, end_idx - start_idx, loop_cnt, current_sequence.len(), end.as_secs() - previous_time.as_secs(),end.subsec_nanos() - previous_time.subsec_nanos(),  add_bloom_filter_cnt, l_window_cnt);
-                        previous_time = end;
-                        continue 'each_read;
-                    }
-                    if r_window_end - l_window_start >= length{
-                        break 'each_r_window;
-                    }
-                    let (r_has_repeat_bool, r_has_repeat_offset) = current_sequence.has_repeat(r_window_start, r_window_end);
-                    if r_has_repeat_bool {
-                        r_window_start += r_has_repeat_offset + 1;
-                        continue 'each_r_window;
-                    }
-                    add_bloom_filter_cnt += 1;
-                    let lmr_string: LmrTuple = current_sequence.subsequence_as_lmrtuple([[l_window_start, l_window_end], [r_window_start, r_window_end], [r_window_start, r_window_end]]);
-                    let table_indice:[u32;8] = lmr_string.hash();
-                    for i in 0..8{
-                        let idx: usize = table_indice[i] as usize;
-                        if ret_array[idx] == u32::MAX{
-                            eprintln!("index {} reaches u32::MAX", idx);
-                        }else{
-                            ret_array[idx] += 1;
-                        }
-                    }
-                    r_window_start += 1;
-                }
-                m_window_start += 1;
             }
-            l_window_start += 1;
-        }
+        });
         let end = start_time.elapsed();
-        eprintln!("1st loop[{:02?}]({}-{}, length is {}): {:09?}\tlength: {}\tsec: {}.{:03}\t subject to add bloom filter: {}\tl_window_cnt: {}",thread_id, start_idx, end_idx, end_idx - start_idx, loop_cnt, current_sequence.len(), end.as_secs() - previous_time.as_secs(),end.subsec_nanos() - previous_time.subsec_nanos(),  add_bloom_filter_cnt, l_window_cnt);
+        log_progress("1st loop", thread_id, [start_idx, end_idx], loop_cnt, current_sequence.len(), end - previous_time, add_bloom_filter_cnt.to_string());
         previous_time = end;
     }
     return ret_array;
@@ -115,93 +164,157 @@ fn count_occurence_from_counting_bloomfilter_table(counting_bloomfilter_table: &
     return retval;
 }
 
-pub fn number_of_high_occurence_lmr_tuple(source_table: &Vec<u32>, sequences: &Vec<DnaSequence>, start_idx: usize, end_idx: usize, threshold: u32, length: usize, thread_id: usize) -> HashSet<LmrTuple>{
+pub fn number_of_high_occurence_lmr_tuple(source_table: &Vec<u32>, sequences: &Vec<DnaSequence>, start_idx: usize, end_idx: usize, threshold: u32, params: &LmrParams, thread_id: usize) -> HashSet<LmrTuple>{
     let mut ret_table: HashSet<LmrTuple> = HashSet::with_capacity(HASHSET_SIZE);
-    let mut l_window_start: usize;
-    let mut l_window_end:   usize;
-    let mut m_window_start: usize;
-    let mut m_window_end:   usize;
-    let mut r_window_start: usize;
-    let mut r_window_end:   usize;
     let mut ho_lmr: usize = 0;
 
     let start = Instant::now();
     let mut previous_time = start.elapsed();
     let mut loop_cnt:usize = 0;
-    'each_read: for current_sequence in sequences[start_idx..end_idx].iter() {
+    for current_sequence in sequences[start_idx..end_idx].iter() {
         let mut add_bloom_filter_cnt: usize = 0;
-        let mut l_window_cnt: usize         = 0;
         loop_cnt += 1;
-        l_window_start = 0;
-        if current_sequence.len() < L_LEN ||current_sequence.len() < M_LEN ||current_sequence.len() < R_LEN{
-            continue 'each_read;
-        }
-        'each_l_window: loop{
-            l_window_end = l_window_start + L_LEN;
-            if l_window_end >= current_sequence.len() + 1{
-                break 'each_l_window;
-            }
-            l_window_cnt += 1;
-            let (l_has_repeat_bool, l_has_repeat_offset) = current_sequence.has_repeat(l_window_start, l_window_end);
-            if l_has_repeat_bool {
-                l_window_start += l_has_repeat_offset + 1;
-                continue 'each_l_window;
-            }
-            m_window_start = l_window_end;
-            'each_m_window: loop{
-                m_window_end = m_window_start + M_LEN;
-                if m_window_end >= current_sequence.len() + 1{
-                    let end = start.elapsed();
-                    eprintln!("2nd loop[{:02?}]({}-{}, length is {}): {:09?}\tlength: {}\tsec: {}.{:03}\t subject to add bloom filter: {}\tl_window_cnt: {}\tho_lmr: {}", thread_id, start_idx, end_idx, end_idx - start_idx, loop_cnt, current_sequence.len(), end.as_secs() - previous_time.as_secs(),end.subsec_nanos() - previous_time.subsec_nanos(),  add_bloom_filter_cnt, l_window_cnt, ho_lmr);
-                    previous_time = end;
-                    continue 'each_read;
-                }
-                if m_window_end - l_window_start >= length - R_LEN{
-                    break 'each_m_window;
-                }
-                let (m_has_repeat_bool, m_has_repeat_offset) = current_sequence.has_repeat(m_window_start, m_window_end);
-                if m_has_repeat_bool {
-                    m_window_start += m_has_repeat_offset + 1;
-                    continue 'each_m_window;
-                }
-                r_window_start = m_window_end;
-                'each_r_window: loop{
-                    r_window_end = r_window_start + R_LEN;
-                    if r_window_end >= current_sequence.len() + 1{
-                        let end = start.elapsed();
-                        eprintln!("2nd loop[{:02?}]({}-{}, length is {}): {:09?}\tlength: {}\tsec: {}.{:03}\t subject to add bloom filter: {}\tl_window_cnt: {}\tho_lmr: {}", thread_id, start_idx, end_idx, end_idx - start_idx, loop_cnt, current_sequence.len(), end.as_secs() - previous_time.as_secs(),end.subsec_nanos() - previous_time.subsec_nanos(),  add_bloom_filter_cnt, l_window_cnt, ho_lmr);
-                        previous_time = end;
-                        continue 'each_read;
-                    }
-                    if r_window_end - l_window_start > length{
-                        break 'each_r_window;
-                    }
-                    let (r_has_repeat_bool, r_has_repeat_offset) = current_sequence.has_repeat(r_window_start, r_window_end);
-                    if r_has_repeat_bool {
-                        r_window_start += r_has_repeat_offset + 1;
-                        continue 'each_r_window;
-                    }
-                    add_bloom_filter_cnt += 1;
-                    let lmr_string: LmrTuple = current_sequence.subsequence_as_lmrtuple([[l_window_start, l_window_end], [r_window_start, r_window_end], [r_window_start, r_window_end]]);
-                    let table_indice:[u32;8] = lmr_string.hash();
-                    let occurence: u32 = count_occurence_from_counting_bloomfilter_table(source_table, table_indice);
-                    if occurence >= threshold * DUPPLICATION{
-                        if ret_table.len() < HASHSET_SIZE{
-                            ret_table.insert(lmr_string);
-                            ho_lmr += 1;
-                        }else{
-                            panic!("reached to the maximum size of has table");
-                        }
-                    }
-                    r_window_start += 1;
+        for_each_lmr_window(current_sequence, params, |ranges| {
+            add_bloom_filter_cnt += 1;
+            let lmr_string: LmrTuple = current_sequence.subsequence_as_lmrtuple(ranges);
+            let table_indice:[u32;8] = lmr_string.hash(params.table_size);
+            let occurence: u32 = count_occurence_from_counting_bloomfilter_table(source_table, table_indice);
+            if occurence >= threshold * DUPPLICATION{
+                if ret_table.len() < HASHSET_SIZE{
+                    ret_table.insert(lmr_string);
+                    ho_lmr += 1;
+                }else{
+                    panic!("reached to the maximum size of has table");
                 }
-                m_window_start += 1;
             }
-            l_window_start += 1;
-        }
+        });
         let end = start.elapsed();
-        eprintln!("2nd loop[{:02?}]({}-{}, length is {}): {:09?}\tlength: {}\tsec: {}.{:03}\t subject to add bloom filter: {}\tl_window_cnt: {}\tho_lmr: {}", thread_id, start_idx, end_idx, end_idx - start_idx, loop_cnt, current_sequence.len(), end.as_secs() - previous_time.as_secs(),end.subsec_nanos() - previous_time.subsec_nanos(),  add_bloom_filter_cnt, l_window_cnt, ho_lmr);
+        log_progress("2nd loop", thread_id, [start_idx, end_idx], loop_cnt, current_sequence.len(), end - previous_time, format!("{}\tho_lmr: {}", add_bloom_filter_cnt, ho_lmr));
         previous_time = end;
     }
     return ret_table;
 }
+
+
+#[cfg(test)]
+mod tests{
+    use crate::counting_bloomfilter_util::{build_counting_bloom_filter, count_occurence_from_counting_bloomfilter_table, for_each_lmr_window, LmrParams, BLOOMFILTER_TABLE_SIZE};
+    use crate::sequence_encoder_util::{DnaSequence, LmrTuple, LMR_RECORD_SIZE};
+    use search_primer::probe_rules::ProbeRules;
+    use ::function_name::named;
+
+    const SOURCE: &str = "GAACGACTGTTTTTACTATAAATCCTTCCTTCCTAGCCTATCATTTCTGGAGTCCTTGGTGAACTGTAGGAAGCTCTGAACACACACGTTCCCTTGGATTCGTACCTATGAATACTCCGT";
+
+    #[test]
+    #[named]
+    fn for_each_lmr_window_gap_test(){
+        let obj = DnaSequence::new(&SOURCE.as_bytes().to_vec());
+        let params = LmrParams{l_len: 20, m_len: 24, r_len: 20, min_lm_gap: 5, max_lm_gap: 10, min_mr_gap: 3, max_mr_gap: 8, max_length: 100, ..LmrParams::default()};
+        assert!(params.validate().is_ok(), "{} failed", function_name!());
+        let mut windows: Vec<[[usize; 2]; 3]> = Vec::new();
+        for_each_lmr_window(&obj, &params, |ranges| windows.push(ranges));
+        assert!(!windows.is_empty(), "{} failed", function_name!());
+        for [l, m, r] in &windows{
+            let lm_gap = m[0] - l[1];
+            let mr_gap = r[0] - m[1];
+            assert!(l[1] - l[0] == 20 && m[1] - m[0] == 24 && r[1] - r[0] == 20, "{} failed: {:?}", function_name!(), (l, m, r));
+            assert!((5..=10).contains(&lm_gap) && (3..=8).contains(&mr_gap), "{} failed: {:?}", function_name!(), (l, m, r));
+            assert!(r[1] - l[0] <= 100 && r[1] <= SOURCE.len(), "{} failed: {:?}", function_name!(), (l, m, r));
+        }
+
+        //プローブのルールを満たすM窓だけが残る
+        let rules = ProbeRules::from_names("no_5p_g,c_gt_g").unwrap();
+        let filtered = LmrParams{probe_rules: Some(rules.clone()), ..params};
+        let mut filtered_windows: Vec<[[usize; 2]; 3]> = Vec::new();
+        for_each_lmr_window(&obj, &filtered, |ranges| filtered_windows.push(ranges));
+        assert!(filtered_windows.len() < windows.len(), "{} failed", function_name!());
+        for [_, m, _] in &filtered_windows{
+            assert!(rules.evaluate(&SOURCE.as_bytes()[m[0]..m[1]], None).passes(), "{} failed: {:?}", function_name!(), m);
+        }
+    }
+
+    #[test]
+    #[named]
+    fn lmrtuple_layout_roundtrip_test(){
+        let obj = DnaSequence::new(&SOURCE.as_bytes().to_vec());
+        let lmr_tuple: LmrTuple = obj.subsequence_as_lmrtuple([[3, 23], [30, 54], [60, 80]]);
+        assert!(lmr_tuple.m_offset == 27 && lmr_tuple.r_offset == 57 && lmr_tuple.product_len() == 77, "{} failed", function_name!());
+        let (l, m, r) = lmr_tuple.decode_as_triple_vec();
+        assert!(l == SOURCE.as_bytes()[3..23] && m == SOURCE.as_bytes()[30..54] && r == SOURCE.as_bytes()[60..80], "{} failed", function_name!());
+        let record: [u8; LMR_RECORD_SIZE] = lmr_tuple.lmr();
+        assert!(LmrTuple::from_lmr(&record) == Ok(lmr_tuple), "{} failed", function_name!());
+        //間隔が違えば別のtuple
+        let shifted: LmrTuple = LmrTuple{r_offset: 58, ..lmr_tuple};
+        assert!(shifted.hash(BLOOMFILTER_TABLE_SIZE) != lmr_tuple.hash(BLOOMFILTER_TABLE_SIZE), "{} failed", function_name!());
+    }
+
+    #[test]
+    #[named]
+    fn lmrtuple_long_window_test(){
+        //32塩基を超える窓もu128に入る
+        let obj = DnaSequence::new(&SOURCE.as_bytes().to_vec());
+        let lmr_tuple: LmrTuple = obj.subsequence_as_lmrtuple([[0, 40], [45, 109], [110, 120]]);
+        let (l, m, r) = lmr_tuple.decode_as_triple_vec();
+        assert!(l == SOURCE.as_bytes()[0..40] && m == SOURCE.as_bytes()[45..109] && r == SOURCE.as_bytes()[110..120], "{} failed", function_name!());
+        assert!(LmrTuple::from_lmr(&lmr_tuple.lmr()) == Ok(lmr_tuple) && lmr_tuple.product_len() == 120, "{} failed", function_name!());
+        //長い窓のrepeatは、中に含まれるどれかの32塩基の窓にrepeatがあるときだけ
+        for len in 33..=64{
+            for start in 0..=SOURCE.len() - len{
+                let expected: bool = (start..=start + len - 32).any(|s| obj.has_repeat(s, s + 32).0);
+                assert!(obj.has_repeat(start, start + len).0 == expected, "{} failed: {}..{}", function_name!(), start, start + len);
+            }
+        }
+    }
+
+    #[test]
+    #[named]
+    fn lmr_params_validate_test(){
+        assert!(LmrParams::default().validate().is_ok(), "{} failed", function_name!());
+        assert!(LmrParams{m_len: 64, max_length: 300, ..LmrParams::default()}.validate().is_ok(), "{} failed", function_name!());
+        assert!(LmrParams{m_len: 65, max_length: 300, ..LmrParams::default()}.validate().is_err(), "{} failed", function_name!());
+        assert!(LmrParams{min_lm_gap: 10, max_lm_gap: 5, ..LmrParams::default()}.validate().is_err(), "{} failed", function_name!());
+        assert!(LmrParams{min_lm_gap: 100, min_mr_gap: 100, ..LmrParams::default()}.validate().is_err(), "{} failed", function_name!());
+        assert!(LmrParams{table_size: 0, ..LmrParams::default()}.validate().is_err(), "{} failed", function_name!());
+    }
+
+    #[test]
+    #[named]
+    fn lmrtuple_hash_distribution_test(){
+        //eval_cbfと同じく、違うtupleを1回ずつ表に入れて、出現数が1からずれる割合を見る
+        let limit: u128 = 10_000;
+        let table_size: usize = 1 << 20;
+        let tuples: Vec<LmrTuple> = (0..limit).map(|i| LmrTuple::new(i, i * 7 + 1, i * 13 + 5)).collect();
+        let mut table: Vec<u32> = vec![0; table_size];
+        for tuple in &tuples{
+            for index in tuple.hash(table_size){
+                table[index as usize] += 1;
+            }
+        }
+        let not_one: usize = tuples.iter().filter(|tuple| count_occurence_from_counting_bloomfilter_table(&table, tuple.hash(table_size)) != 1).count();
+        assert!(not_one * 1000 < tuples.len(), "{} failed: {} of {}", function_name!(), not_one, tuples.len());
+
+        //添字は表の中に一様に散らばる(自由度255のカイ二乗分布の99.9%点はおよそ330)
+        let buckets: usize = 256;
+        let mut counts: Vec<usize> = vec![0; buckets];
+        for tuple in &tuples{
+            for index in tuple.hash(buckets){
+                counts[index as usize] += 1;
+            }
+        }
+        let expected: f64 = (tuples.len() * 8) as f64 / buckets as f64;
+        let chi_square: f64 = counts.iter().map(|&c| (c as f64 - expected).powi(2) / expected).sum();
+        assert!(chi_square < 330.0, "{} failed: {} {:?}", function_name!(), chi_square, counts);
+
+        //表の大きさを変えて数えても、各tupleの出現数は同じ
+        let obj = DnaSequence::new(&SOURCE.as_bytes().to_vec());
+        let params = LmrParams{l_len: 20, m_len: 24, r_len: 20, min_lm_gap: 5, max_lm_gap: 10, min_mr_gap: 3, max_mr_gap: 8, max_length: 100, table_size: 1 << 16, ..LmrParams::default()};
+        let cbf: Vec<u32> = build_counting_bloom_filter(&vec![DnaSequence::new(&SOURCE.as_bytes().to_vec()), DnaSequence::new(&SOURCE.as_bytes().to_vec())], 0, 2, &params, 0);
+        let mut windows: Vec<[[usize; 2]; 3]> = Vec::new();
+        for_each_lmr_window(&obj, &params, |ranges| windows.push(ranges));
+        assert!(cbf.len() == 1 << 16 && !windows.is_empty(), "{} failed", function_name!());
+        for ranges in &windows{
+            let tuple = obj.subsequence_as_lmrtuple(*ranges);
+            assert!(count_occurence_from_counting_bloomfilter_table(&cbf, tuple.hash(params.table_size)) >= 2, "{} failed: {:?}", function_name!(), ranges);
+        }
+    }
+}
@@ -9,9 +9,11 @@ use std::collections::HashSet;
 use std::thread;
 use std::sync::{Mutex, Arc};
 use std::iter::zip;
-use search_primer_and_probe::counting_bloomfilter_util::{BLOOMFILTER_TABLE_SIZE, L_LEN, M_LEN, R_LEN, HASHSET_SIZE};
+use search_primer_and_probe::counting_bloomfilter_util::{BLOOMFILTER_TABLE_SIZE, L_LEN, M_LEN, R_LEN, HASHSET_SIZE, LmrParams};
 use search_primer_and_probe::counting_bloomfilter_util::{build_counting_bloom_filter, number_of_high_occurence_lmr_tuple};
 use search_primer_and_probe::sequence_encoder_util::{DnaSequence, LmrTuple};
+use search_primer::tuple_record::TupleRecord;
+use search_primer::probe_rules::ProbeRules;
 use bio::io::fasta::Reader as faReader;
 use bio::io::fasta::Record as faRecord;
 use std::fs::File;
@@ -33,6 +35,16 @@ fn main() {
     opts.optopt("t", "thread", "number of threads to use for radix sort. default value is 8.", "THREAD");
     opts.optopt("a", "threshold", "threshold of the occurence of each lmr tuple. default value is 1000.", "THRESHOLD");
     opts.optopt("l", "length", "length of product of PCR. default value is 200.", "LENGTH");
+    opts.optopt("L", "l_len", "length of L window, up to 64. default value is 32.", "LEN");
+    opts.optopt("M", "m_len", "length of M window, up to 64. default value is 32.", "LEN");
+    opts.optopt("R", "r_len", "length of R window, up to 64. default value is 32.", "LEN");
+    opts.optopt("", "min_lm_gap", "minimum number of bases between L and M. default value is 0.", "GAP");
+    opts.optopt("", "max_lm_gap", "maximum number of bases between L and M. default value is unlimited.", "GAP");
+    opts.optopt("", "min_mr_gap", "minimum number of bases between M and R. default value is 0.", "GAP");
+    opts.optopt("", "max_mr_gap", "maximum number of bases between M and R. default value is unlimited.", "GAP");
+    opts.optopt("", "probe_rules", "count only M windows passing these TaqMan probe rules (comma separated: no_5p_g,c_gt_g,tm_delta,no_g_run,gc, or all).", "RULES");
+    opts.optopt("", "primer_tm", "primer Tm the probe Tm is compared with for the tm_delta rule.", "TM");
+    opts.optopt("", "table_size", &format!("number of counters of the counting bloom filter. default value is {}.", BLOOMFILTER_TABLE_SIZE), "SIZE");
     opts.optflag("b", "binary", "outputs binary file");
     opts.optflag("r", "only-num", "outputs only total number of k-mer");
     opts.optflag("h", "help", "print this help menu");
@@ -65,6 +77,35 @@ fn main() {
         200
     };
 
+    let opt_usize = |name: &str, default: usize| -> usize {
+        matches.opt_str(name).map(|v| v.parse::<usize>().unwrap()).unwrap_or(default)
+    };
+    let params = LmrParams{
+        l_len: opt_usize("L", L_LEN),
+        m_len: opt_usize("M", M_LEN),
+        r_len: opt_usize("R", R_LEN),
+        min_lm_gap: opt_usize("min_lm_gap", 0),
+        max_lm_gap: opt_usize("max_lm_gap", usize::MAX),
+        min_mr_gap: opt_usize("min_mr_gap", 0),
+        max_mr_gap: opt_usize("max_mr_gap", usize::MAX),
+        max_length: length,
+        probe_rules: match matches.opt_str("probe_rules").as_deref() {
+            None => None,
+            Some("all") => Some(ProbeRules::default()),
+            Some(names) => Some(ProbeRules::from_names(names).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                process::exit(1);
+            })),
+        },
+        primer_tm: matches.opt_str("primer_tm").map(|v| v.parse::<f64>().unwrap()),
+        table_size: opt_usize("table_size", BLOOMFILTER_TABLE_SIZE),
+    };
+    if let Err(e) = params.validate() {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+    let params_ref = &params;
+
 
     let threshold:u32 = if matches.opt_present("a") {
         matches.opt_str("a").unwrap().parse::<u32>().unwrap()
@@ -98,7 +139,7 @@ fn main() {
 
     let chunk_size: usize = sequences.len() / (threads - 1);
     let sequences_ref = &sequences;
-    let mut cbf_oyadama: Vec<u32> = vec![0;BLOOMFILTER_TABLE_SIZE];
+    let mut cbf_oyadama: Vec<u32> = vec![0;params.table_size];
 
     thread::scope(|scope|{
         let mut children_1 = Vec::new();
@@ -114,7 +155,7 @@ fn main() {
                             end_idx = sequences_ref.len() - 1;
                         }
                         eprintln!("start calling build_counting_bloom_filter[{}]", i);
-                        let cbf: Vec<u32> = build_counting_bloom_filter(sequences_ref, start_idx, end_idx, length, i);
+                        let cbf: Vec<u32> = build_counting_bloom_filter(sequences_ref, start_idx, end_idx, params_ref, i);
                         eprintln!("finish calling build_counting_bloom_filter[{}]", i);
                         cbf
                     }
@@ -144,7 +185,7 @@ fn main() {
                             end_idx = sequences_ref.len() - 1;
                         }
                         eprintln!("thread [{}]: start calling number_of_high_occurence_lmr_tuple", i);
-                        let h_cbf_h: HashSet<LmrTuple> = number_of_high_occurence_lmr_tuple(cbf_oyadama_ref, sequences_ref, start_idx, end_idx, threshold, length, i);
+                        let h_cbf_h: HashSet<LmrTuple> = number_of_high_occurence_lmr_tuple(cbf_oyadama_ref, sequences_ref, start_idx, end_idx, threshold, params_ref, i);
                         h_cbf_h_oyadama_ref.lock().unwrap().extend(h_cbf_h);
                         eprintln!("thread [{}]: finish calling number_of_high_occurence_lmr_tuple", i);
                     }
@@ -168,18 +209,19 @@ fn main() {
     if !matches.opt_present("r") && matches.opt_present("b"){
         eprintln!("matches.opt_present('r'): {}\tmatches.opt_present('b'): {}", matches.opt_present("r"), matches.opt_present("b"));
         for each_tuple in &high_occurence_lmr_tuple{
-            w.write(&each_tuple.lmr()).unwrap();
+            w.write_all(&each_tuple.to_bytes()).unwrap();
         }
     }
     if !matches.opt_present("r") && !matches.opt_present("b"){
         eprintln!("matches.opt_present('r'): {}\tmatches.opt_present('b'): {}", matches.opt_present("r"), matches.opt_present("b"));
+        //L, M, Rを連結した配列と、M, Rの開始位置(Lの先頭から)
         for each_tuple in &high_occurence_lmr_tuple{
             w.write(&each_tuple.decode_as_single_vec()).unwrap();
-            w.write(b"\n").unwrap();
+            writeln!(&mut w, "\t{}\t{}", each_tuple.m_offset, each_tuple.r_offset).unwrap();
         }
     }
     eprintln!("finish writing to output file: {:?}", &output_file);
-    eprint!("L:{}\tM:{}\tR:{}\tthreshold:{}\tcardinarity: {}\t", L_LEN, M_LEN, R_LEN, threshold, high_occurence_lmr_tuple.len());
+    eprint!("L:{}\tM:{}\tR:{}\tL-M gap:{}-{}\tM-R gap:{}-{}\tthreshold:{}\tcardinarity: {}\t", params.l_len, params.m_len, params.r_len, params.min_lm_gap, params.max_lm_gap, params.min_mr_gap, params.max_mr_gap, threshold, high_occurence_lmr_tuple.len());
     eprintln!("threads: {}\tinput file {:?}", threads, &input_file);
 
 }
\ No newline at end of file
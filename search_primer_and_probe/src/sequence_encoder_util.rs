@@ -1,39 +1,98 @@
 use crate::counting_bloomfilter_util::L_LEN;
 use crate::counting_bloomfilter_util::M_LEN;
 use crate::counting_bloomfilter_util::R_LEN;
-use crate::counting_bloomfilter_util::BLOOMFILTER_TABLE_SIZE;
 use std::cmp;
 use std::hash::Hash;
-use sha256::digest;
+use search_primer::counting_bloomfilter_util::hash_from_bytes;
+use search_primer::tuple_id::TupleId;
+use search_primer::tuple_record::{record_bytes, reverse_complement_bits, TupleRecord};
 
+pub const LMR_RECORD_SIZE: usize = 56;
+//lmrレコードの形式の版。24byte(1), 32byte(2)の頃の形式のファイルを56byteずつ読み違えないよう、各レコードの51byte目に書いて読むときに確かめる
+pub const LMR_FORMAT_VERSION: u8 = 3;
+//L, M, Rの窓の最大塩基数。各窓をu128に右詰めで持つ
+pub const MAX_WINDOW_LEN: usize = 64;
+
+//m_offset, r_offsetはLの先頭からの距離。同じL, M, Rでも間隔が違えば別のtupleとして扱う。
 #[derive(Eq, Hash, PartialEq, Clone, Copy, Ord, PartialOrd)]
 pub struct LmrTuple{
-    pub l: u64,
-    pub m: u64,
-    pub r: u64,
+    pub l: u128,
+    pub m: u128,
+    pub r: u128,
+    pub l_len: u8,
+    pub m_len: u8,
+    pub r_len: u8,
+    pub m_offset: u16,
+    pub r_offset: u16,
 }
 impl LmrTuple{
-    pub fn new(l: u64, m: u64, r: u64) -> Self{
-        {
-            LmrTuple {l, m, r}
-        }
+    //L, M, Rが32塩基ずつ隙間なく並んでいるtuple
+    pub fn new(l: u128, m: u128, r: u128) -> Self{
+        LmrTuple::with_layout(l, m, r, [L_LEN as u8, M_LEN as u8, R_LEN as u8], L_LEN as u16, (L_LEN + M_LEN) as u16)
     }
+    pub fn with_layout(l: u128, m: u128, r: u128, lens: [u8; 3], m_offset: u16, r_offset: u16) -> Self{
+        LmrTuple {l, m, r, l_len: lens[0], m_len: lens[1], r_len: lens[2], m_offset, r_offset}
+    }
+    //primer3_coreのSEQUENCE_IDやBLASTの問い合わせ名に使う。from_tuple_idで元に戻せる
     pub fn id(&self) -> Vec<u8>{
-        return format!("{:X}{:X}{:X}", self.l, self.m, self.r).into_bytes();
-    }
-    pub fn lmr(&self) -> [u8; 24]{
-        let mut retval: [u8;24] = [0; 24];
-        let l_u8s: [u8;8] = [(self.l >> 56) as u8, (self.l >> 48) as u8, (self.l >> 40) as u8, (self.l >> 32) as u8, (self.l >> 24) as u8, (self.l >> 16) as u8, (self.l >> 8) as u8, self.l as u8];
-        let m_u8s: [u8;8] = [(self.m >> 56) as u8, (self.m >> 48) as u8, (self.m >> 40) as u8, (self.m >> 32) as u8, (self.m >> 24) as u8, (self.m >> 16) as u8, (self.m >> 8) as u8, self.m as u8];
-        let r_u8s: [u8;8] = [(self.r >> 56) as u8, (self.r >> 48) as u8, (self.r >> 40) as u8, (self.r >> 32) as u8, (self.r >> 24) as u8, (self.r >> 16) as u8, (self.r >> 8) as u8, self.r as u8];
-        retval[0..8].copy_from_slice(&l_u8s);
-        retval[8..16].copy_from_slice(&m_u8s);
-        retval[16..24].copy_from_slice(&r_u8s);
-        return retval;
+        return self.tuple_id().to_string().into_bytes();
+    }
+    pub fn tuple_id(&self) -> TupleId{
+        TupleId::Lmr{l: self.l, m: self.m, r: self.r, lens: [self.l_len, self.m_len, self.r_len], m_offset: self.m_offset, r_offset: self.r_offset}
+    }
+    pub fn from_tuple_id(id: &TupleId) -> Option<LmrTuple>{
+        match id{
+            TupleId::Lmr{l, m, r, lens, m_offset, r_offset} => LmrTuple::checked(LmrTuple::with_layout(*l, *m, *r, *lens, *m_offset, *r_offset)).ok(),
+            _ => None,
+        }
+    }
+    //ファイルやidから読んだtupleの検査。窓は1..=MAX_WINDOW_LEN塩基で長さに収まる値を持ち、L, M, Rの順に重ならず並び、産物がu16に収まること
+    pub fn checked(tuple: LmrTuple) -> Result<LmrTuple, String>{
+        for (name, window, len) in [('L', tuple.l, tuple.l_len), ('M', tuple.m, tuple.m_len), ('R', tuple.r, tuple.r_len)]{
+            if len == 0 || len as usize > MAX_WINDOW_LEN{
+                return Err(format!("{} is {} bases, not 1..={}", name, len, MAX_WINDOW_LEN));
+            }
+            if (len as usize) < MAX_WINDOW_LEN && window >> (2 * len as u32) != 0{
+                return Err(format!("{} has bits beyond its {} bases", name, len));
+            }
+        }
+        if (tuple.m_offset as usize) < tuple.l_len as usize || (tuple.r_offset as usize) < tuple.m_offset as usize + tuple.m_len as usize{
+            return Err(format!("windows overlap: L 0+{}, M {}+{}, R {}+{}", tuple.l_len, tuple.m_offset, tuple.m_len, tuple.r_offset, tuple.r_len));
+        }
+        if tuple.product_len() > u16::MAX as usize{
+            return Err(format!("product of {} bases is too long", tuple.product_len()));
+        }
+        Ok(tuple)
+    }
+    //L, M, R(各16byte, big endian), l_len, m_len, r_len, LMR_FORMAT_VERSION, m_offset, r_offset(各2byte, big endian)の56byte
+    pub fn lmr(&self) -> [u8; LMR_RECORD_SIZE]{
+        let mut retval: [u8; LMR_RECORD_SIZE] = [0; LMR_RECORD_SIZE];
+        retval[0..16].copy_from_slice(&self.l.to_be_bytes());
+        retval[16..32].copy_from_slice(&self.m.to_be_bytes());
+        retval[32..48].copy_from_slice(&self.r.to_be_bytes());
+        retval[48] = self.l_len;
+        retval[49] = self.m_len;
+        retval[50] = self.r_len;
+        retval[51] = LMR_FORMAT_VERSION;
+        retval[52..54].copy_from_slice(&self.m_offset.to_be_bytes());
+        retval[54..56].copy_from_slice(&self.r_offset.to_be_bytes());
+        retval
+    }
+    pub fn from_lmr(buffer: &[u8; LMR_RECORD_SIZE]) -> Result<LmrTuple, String>{
+        let u128_at = |i: usize| u128::from_be_bytes(buffer[i..i + 16].try_into().unwrap());
+        let u16_at = |i: usize| u16::from_be_bytes(buffer[i..i + 2].try_into().unwrap());
+        if buffer[51] != LMR_FORMAT_VERSION{
+            return Err(format!("lmr record of format version {}, expected {}; the file was written by another version or is not an lmr file", buffer[51], LMR_FORMAT_VERSION));
+        }
+        LmrTuple::checked(LmrTuple::with_layout(u128_at(0), u128_at(16), u128_at(32), [buffer[48], buffer[49], buffer[50]], u16_at(52), u16_at(54)))
+    }
+    //Lの先頭からRの末尾までの長さ
+    pub fn product_len(&self) -> usize{
+        self.r_offset as usize + self.r_len as usize
     }
 
 
-    pub fn decode_single_window(source: &u64, len: usize) -> Vec<u8>{
+    pub fn decode_single_window(source: &u128, len: usize) -> Vec<u8>{
         let mut result: Vec<u8> = Vec::new();
         let mut base;
         for i in 0..len{
@@ -50,44 +109,46 @@ impl LmrTuple{
     }
 
     pub fn decode_as_single_vec(&self) -> Vec<u8>{
-        let mut l_vec = LmrTuple::decode_single_window(&self.l, L_LEN);
-        let mut m_vec = LmrTuple::decode_single_window(&self.m, M_LEN);
-        let mut r_vec = LmrTuple::decode_single_window(&self.r, R_LEN);
+        let mut l_vec = LmrTuple::decode_single_window(&self.l, self.l_len as usize);
+        let mut m_vec = LmrTuple::decode_single_window(&self.m, self.m_len as usize);
+        let mut r_vec = LmrTuple::decode_single_window(&self.r, self.r_len as usize);
         l_vec.append(&mut m_vec);
         l_vec.append(&mut r_vec);
         return l_vec
     }
 
     pub fn decode_as_triple_vec(&self) -> (Vec<u8>, Vec<u8>, Vec<u8>){
-        let l_vec = LmrTuple::decode_single_window(&self.l, L_LEN);
-        let m_vec = LmrTuple::decode_single_window(&self.m, M_LEN);
-        let r_vec = LmrTuple::decode_single_window(&self.r, R_LEN);
+        let l_vec = LmrTuple::decode_single_window(&self.l, self.l_len as usize);
+        let m_vec = LmrTuple::decode_single_window(&self.m, self.m_len as usize);
+        let r_vec = LmrTuple::decode_single_window(&self.r, self.r_len as usize);
         return (l_vec, m_vec, r_vec)
     }
 
-    pub fn hash(&self) -> [u32; 8]{
-        let l_base = &self.l.to_be_bytes();
-        let m_base = &self.m.to_be_bytes();
-        let r_base = &self.r.to_be_bytes();
-        let mut v: Vec<u8> = Vec::new();
-        for i in 0..8{
-            v.push(l_base[i]);
-            v.push(m_base[i]);
-            v.push(r_base[i]);
-        }
-        let mut a = [0; 24];
-        a.copy_from_slice(&v);
-        let binding = digest(&a);
-        let val = binding.as_bytes();
-        let mut ret_val: [u32; 8] = [0; 8];
-        for i in 0..8{
-            for j in 0..4{
-                ret_val[i] += val[i * 4 + j] as u32;
-                ret_val[i] <<= 8;
-            }
-            ret_val[i] %= BLOOMFILTER_TABLE_SIZE as u32;
-        }
-        return ret_val;
+    //counting bloom filterの添字(0..table_size)。長さと間隔も含めたレコードの56byteをhashする
+    pub fn hash(&self, table_size: usize) -> [u32; 8]{
+        hash_from_bytes(&self.lmr(), table_size)
+    }
+}
+
+impl TupleRecord for LmrTuple{
+    const RECORD_SIZE: usize = LMR_RECORD_SIZE;
+    fn to_bytes(&self) -> Vec<u8>{
+        self.lmr().to_vec()
+    }
+    fn from_bytes(bytes: &[u8]) -> Result<LmrTuple, String>{
+        LmrTuple::from_lmr(&record_bytes(bytes)?)
+    }
+    fn tuple_id(&self) -> TupleId{
+        LmrTuple::tuple_id(self)
+    }
+    fn geometry(&self) -> Vec<(char, usize, usize)>{
+        vec![('L', 0, self.l_len as usize), ('M', self.m_offset as usize, self.m_len as usize), ('R', self.r_offset as usize, self.r_len as usize)]
+    }
+    //反対の鎖ではRの逆相補鎖がL、Lの逆相補鎖がRになる。位置は産物の末尾から測り直す
+    fn reverse_complement(&self) -> LmrTuple{
+        let rc = |window: u128, len: u8| reverse_complement_bits(window, len as usize);
+        let product_len = self.product_len() as u16;
+        LmrTuple::with_layout(rc(self.r, self.r_len), rc(self.m, self.m_len), rc(self.l, self.l_len), [self.r_len, self.m_len, self.l_len], product_len - self.m_offset - self.m_len as u16, product_len - self.l_len as u16)
     }
 }
 
@@ -202,9 +263,9 @@ impl DnaSequence{
         }
         return buf
     }
-    //subsequence_as_LmrTupleは、各u64に右詰め
+    //subsequence_as_LmrTupleは、各u128に右詰め
     pub fn subsequence_as_lmrtuple(&self, ranges: [[usize; 2]; 3]) -> LmrTuple{
-        let mut buf  : u64   = 0;
+        let mut buf  : u128  = 0;
         let     l_w_s: usize = ranges[0][0];
         let     l_w_e: usize = ranges[0][1];
         let     m_w_s: usize = ranges[1][0];
@@ -214,34 +275,65 @@ impl DnaSequence{
         assert!(l_w_s < l_w_e, "DnaSequence::subsequence_as_lmrtuple assertion failed: {} !< {}", l_w_s, l_w_e);
         assert!(m_w_s < m_w_e, "DnaSequence::subsequence_as_lmrtuple assertion failed: {} !< {}", m_w_s, m_w_e);
         assert!(r_w_s < r_w_e, "DnaSequence::subsequence_as_lmrtuple assertion failed: {} !< {}", r_w_s, r_w_e);
-        assert!(l_w_e - l_w_s < 33, "DnaSequence::subsequence_as_lmrtuple assertion failed: {} - {} > 32", l_w_e, l_w_s);
-        assert!(m_w_e - m_w_s < 33, "DnaSequence::subsequence_as_lmrtuple assertion failed: {} - {} > 32", m_w_e, m_w_s);
-        assert!(r_w_e - r_w_s < 33, "DnaSequence::subsequence_as_lmrtuple assertion failed: {} - {} > 32", r_w_e, r_w_s);
+        assert!(l_w_e - l_w_s <= MAX_WINDOW_LEN, "DnaSequence::subsequence_as_lmrtuple assertion failed: {} - {} > {}", l_w_e, l_w_s, MAX_WINDOW_LEN);
+        assert!(m_w_e - m_w_s <= MAX_WINDOW_LEN, "DnaSequence::subsequence_as_lmrtuple assertion failed: {} - {} > {}", m_w_e, m_w_s, MAX_WINDOW_LEN);
+        assert!(r_w_e - r_w_s <= MAX_WINDOW_LEN, "DnaSequence::subsequence_as_lmrtuple assertion failed: {} - {} > {}", r_w_e, r_w_s, MAX_WINDOW_LEN);
 
         for i in l_w_s..l_w_e{
             buf <<= 2;
-            buf += ((self.sequence[i / 32] >> (62 - 2 * (i % 32))) & 3) as u64;
+            buf += ((self.sequence[i / 32] >> (62 - 2 * (i % 32))) & 3) as u128;
         }
-        let l :u64= buf;
+        let l :u128= buf;
         buf = 0;
         for i in m_w_s..m_w_e{
             buf <<= 2;
-            buf += ((self.sequence[i / 32] >> (62 - 2 * (i % 32))) & 3) as u64;
+            buf += ((self.sequence[i / 32] >> (62 - 2 * (i % 32))) & 3) as u128;
         }
-        let m :u64= buf;
+        let m :u128= buf;
         buf = 0;
         for i in r_w_s..r_w_e{
             buf <<= 2;
-            buf += ((self.sequence[i / 32] >> (62 - 2 * (i % 32))) & 3) as u64;
+            buf += ((self.sequence[i / 32] >> (62 - 2 * (i % 32))) & 3) as u128;
         }
-        let r:u64 = buf;
-        return LmrTuple{l, m, r};
+        let r:u128 = buf;
+        assert!(l_w_s <= m_w_s && m_w_s <= r_w_s, "DnaSequence::subsequence_as_lmrtuple assertion failed: L, M, R must be in this order");
+        assert!(r_w_s - l_w_s <= u16::MAX as usize, "DnaSequence::subsequence_as_lmrtuple assertion failed: R starts too far from L");
+        return LmrTuple::with_layout(l, m, r, [(l_w_e - l_w_s) as u8, (m_w_e - m_w_s) as u8, (r_w_e - r_w_s) as u8], (m_w_s - l_w_s) as u16, (r_w_s - l_w_s) as u16);
         
     }
 
 
 
+    //start..endを右詰めでu64に入れる(32塩基まで)。32塩基の境界を跨いでもよい。
+    pub fn window_as_u64(&self, start: usize, end: usize) -> u64{
+        assert!(start < end && end - start <= 32, "DnaSequence::window_as_u64 assertion failed: {}..{}", start, end);
+        let mut buf: u128 = (self.sequence[start / 32] as u128) << 64;
+        if start / 32 + 1 < self.sequence.len(){
+            buf |= self.sequence[start / 32 + 1] as u128;
+        }
+        buf <<= 2 * (start % 32);
+        (buf >> (128 - 2 * (end - start))) as u64
+    }
+
     pub fn has_repeat(&self, start: usize, end: usize) -> (bool, usize) {
+        //32塩基より長い区間は、16塩基ずつ重ねた32塩基の区間に分けて調べる。
+        //繰り返しの判定は10塩基あれば足りるので、重なりがあれば区間の境目を跨ぐ繰り返しも見落とさない
+        //各区間の位置は区間の先頭からなので、startからの位置に直してから最大値をとる
+        if end - start > 32{
+            let mut retval: (bool, usize) = (false, 0);
+            let mut chunk_start: usize = start;
+            loop{
+                let chunk_end: usize = cmp::min(chunk_start + 32, end);
+                let chunk: (bool, usize) = self.has_repeat(chunk_end - 32, chunk_end);
+                if chunk.0{
+                    retval = (true, cmp::max(retval.1, chunk_end - 32 - start + chunk.1));
+                }
+                if chunk_end == end{
+                    return retval
+                }
+                chunk_start += 16;
+            }
+        }
         let has_one_base_repeat: (bool, usize)   = self.has_one_base_repeat(start, end);
         let has_two_base_repeat: (bool, usize)   = self.has_two_base_repeat(start, end);
         let has_three_base_repeat: (bool, usize) = self.has_three_base_repeat(start, end);
@@ -257,14 +349,7 @@ impl DnaSequence{
         assert!(end <= self.length, "DnaSequence::has_one_base_repeat assertion failed: end coordinate must be smaller than length of the sequence. start: {}, end: {}, self.lngth: {}", start, end, self.length);
         let zero_ichi: u64  = 0x5555555555555554;
 
-        let upper_mask:u128 = 0x0000000000000000FFFFFFFFFFFFFFFF;
-        let mut left_bits: u128 = self.sequence[start/32] as u128;
-        left_bits <<= 64 - 2 * (start % 32);
-        left_bits &= upper_mask;
-        left_bits >>= 64 - 2 * (start % 32);
-        let mut original = u64::try_from(left_bits).unwrap();
-        original <<= 2 * (end % 32);
-        original += self.sequence[end / 32] >> (64 - 2 * (end % 32));
+        let original: u64 = self.window_as_u64(start, end);
         let val1  = original;
         let val2  = original << 2;
         let val3  = val1 ^ val2;
@@ -310,14 +395,7 @@ impl DnaSequence{
         assert!(end - start <= 32, "DnaSequence::has_two_base_repeat assertion failed: length of the evaluation subject must be shorter than 32");
         assert!(end <= self.length, "DnaSequence::has_two_base_repeat assertion failed: end coordinate must be smaller than length of the sequence. end: {}, self.lngth: {}", end, self.length);
         let zero_ichi: u64 = 0x5555_5555_5555_5555 & !63;
-        let upper_mask:u128 = 0x0000000000000000FFFFFFFFFFFFFFFF;
-        let mut left_bits: u128 = self.sequence[start/32] as u128;
-        left_bits <<= 64 - 2 * (start % 32);
-        left_bits &= upper_mask;
-        left_bits >>= 64 - 2 * (start % 32);
-        let mut original = u64::try_from(left_bits).unwrap();
-        original <<= 2 * (end % 32);
-        original += self.sequence[end / 32] >> (64 - 2 * (end % 32));
+        let original: u64 = self.window_as_u64(start, end);
         //ここまでで、originalに右詰で対象の領域がコピーされる。
         let val1 = original;
         let val2 = original << 4;
@@ -364,14 +442,7 @@ impl DnaSequence{
         assert!(end - start <= 32, "DnaSequence::has_three_base_repeat assertion failed: length of the evaluation subject must be shorter than 32");
         assert!(end <= self.length, "DnaSequence::has_three_base_repeat assertion failed: end coordinate must be smaller than length of the sequence. end: {}, self.lngth: {}", end, self.length);
         let zero_ichi: u64  = 0x5555555555555555 & !63;
-        let upper_mask:u128 = 0x0000000000000000FFFFFFFFFFFFFFFF;
-        let mut left_bits: u128 = self.sequence[start/32] as u128;
-        left_bits <<= 64 - 2 * (start % 32);
-        left_bits &= upper_mask;
-        left_bits >>= 64 - 2 * (start % 32);
-        let mut original = u64::try_from(left_bits).unwrap();
-        original <<= 2 * (end % 32);
-        original += self.sequence[end / 32] >> (64 - 2 * (end % 32));
+        let original: u64 = self.window_as_u64(start, end);
         
         //ここまでで、originalに右詰で対象の領域がコピーされる。
         let val1 = original;
@@ -422,8 +493,48 @@ impl DnaSequence{
 
 #[cfg(test)]
 mod tests{
-    use crate::sequence_encoder_util::DnaSequence;
+    use crate::sequence_encoder_util::{DnaSequence, LmrTuple, LMR_FORMAT_VERSION};
+    use search_primer::tuple_record::TupleRecord;
     use ::function_name::named;
+
+    #[test]
+    #[named]
+    fn lmr_tuple_record_test(){
+        //L: 0..4, M: 6..8, R: 11..14
+        let source: Vec<u8> = b"ACGTTTGCAAGGCCT".to_vec();
+        let seq = DnaSequence::new(&source);
+        let tuple = seq.subsequence_as_lmrtuple([[0, 4], [6, 8], [11, 14]]);
+        let rc_source: Vec<u8> = source[..14].iter().rev().map(|b| match b {b'A' => b'T', b'C' => b'G', b'G' => b'C', _ => b'A'}).collect();
+        let rc_expected = DnaSequence::new(&rc_source).subsequence_as_lmrtuple([[0, 3], [6, 8], [10, 14]]);
+        let rc = tuple.reverse_complement();
+        assert!(tuple.geometry() == vec![('L', 0, 4), ('M', 6, 2), ('R', 11, 3)], "{} failed", function_name!());
+        assert!(rc == rc_expected, "{} failed: {:?}", function_name!(), rc.geometry());
+        assert!(rc.reverse_complement() == tuple, "{} failed", function_name!());
+        assert!(LmrTuple::from_bytes(&tuple.to_bytes()) == Ok(tuple) && tuple.sequence() == b"ACGTGCGCC".to_vec(), "{} failed", function_name!());
+    }
+
+    #[test]
+    #[named]
+    fn lmr_tuple_from_bytes_rejects_invalid_records(){
+        let tuple = LmrTuple::with_layout(0x1b, 2, 3, [4, 2, 3], 10, 20);
+        let with = |i: usize, value: u8| {let mut bytes = tuple.to_bytes(); bytes[i] = value; bytes};
+        let invalid: Vec<Vec<u8>> = vec![
+            vec![0xff; 56],
+            with(48, 65),
+            with(49, 0),
+            with(14, 1),
+            with(51, 0),
+            with(53, 3),
+            with(55, 11),
+            tuple.to_bytes()[..32].to_vec(),
+        ];
+        for bytes in &invalid{
+            assert!(LmrTuple::from_bytes(bytes).is_err(), "{} failed: {:?}", function_name!(), bytes);
+        }
+        assert!(LmrTuple::from_bytes(&tuple.to_bytes()) == Ok(tuple) && tuple.to_bytes()[51] == LMR_FORMAT_VERSION, "{} failed", function_name!());
+        let far = LmrTuple::with_layout(1, 2, 3, [64, 64, 64], u16::MAX - 200, u16::MAX - 60);
+        assert!(LmrTuple::from_bytes(&far.to_bytes()).is_err(), "{} failed", function_name!());
+    }
 /*
 *
 *Encode Test
@@ -997,6 +1108,17 @@ has_repeat test
             assert!(obj.has_repeat(0, 25) == (true, 6), "{} failed", function_name!());
         }
     }
+    #[test]
+    #[named]
+    fn has_repeat_over_32(){
+        //繰り返しは最後の区間(9..41)にだけあり、位置はstartから数える
+        let source: String = "CATCACCAATTATTGGTCCTAATGTAGCTGCAAAAAAAGCT".to_string();
+        let v: Vec<u8> = source.into_bytes();
+        let obj = DnaSequence::new(&v);
+        let local: (bool, usize) = obj.has_repeat(9, 41);
+        assert!(local.0 && obj.has_repeat(0, 41) == (true, 9 + local.1), "{} failed: {:?} {:?}", function_name!(), local, obj.has_repeat(0, 41));
+        assert!(obj.has_repeat(0, 26) == (false, 0), "{} failed", function_name!());
+    }
 
 /*
 *
@@ -1106,6 +1228,18 @@ has_repeat test
 
 
 
+    #[test]
+    #[named]
+    fn window_as_u64_test(){
+        let source: String = "GAACGACTGTTTTTACTATAAATCCTTCCTTCCTAGCCTATCATTTCTGGAGTCCTTGGTGAACTGTAGGAAGCTCTGAACACACACGTTCCCTTGGATTCGTACCTATGAATACTCCGT".to_string();
+        let v: Vec<u8> = source.into_bytes();
+        let obj = DnaSequence::new(&v);
+        for (start, end) in [(0, 32), (32, 64), (3, 23), (20, 52), (90, 120)]{
+            let expected: u64 = obj.subsequence_as_u128(vec![[start, end]]) as u64;
+            assert!(obj.window_as_u64(start, end) == expected, "{} failed: {}..{}", function_name!(), start, end);
+        }
+    }
+
     /*
     *
     *LmrTuple Test
@@ -1120,11 +1254,21 @@ has_repeat test
         let lmr_tuple: crate::sequence_encoder_util::LmrTuple = obj.subsequence_as_lmrtuple([[0, 32], [32, 64], [64, 96]]);
         let decode_as_single_vec_result = lmr_tuple.decode_as_single_vec();
         assert!(decode_as_single_vec_result == v[0..96], "{} failed\n{:?}\n{:?}", function_name!(), decode_as_single_vec_result, v);
-        let lmr:[u8;24] = lmr_tuple.lmr();
+        let lmr:[u8;56] = lmr_tuple.lmr();
         //assert!(false, "{} failed\n{:?}\n{:?}", function_name!(), String::from_utf8(decode_as_single_vec_result).unwrap(), lmr.as_ref().iter().map(|x| format!("{:08b}", x)).collect::<Vec<_>>());
 
     }
 
+    #[test]
+    #[named]
+    fn lmrtuple_id_test(){
+        let source: Vec<u8> = b"GAACGACTGTTTTTACTATAAATCCTTCCTTCCTAGCCTATCATTTCTGGAGTCC".to_vec();
+        let lmr_tuple = DnaSequence::new(&source).subsequence_as_lmrtuple([[0, 20], [25, 40], [45, 55]]);
+        let id: String = String::from_utf8(lmr_tuple.id()).unwrap();
+        let parsed = id.parse::<search_primer::tuple_id::TupleId>().ok().and_then(|t| crate::sequence_encoder_util::LmrTuple::from_tuple_id(&t));
+        assert!(id.starts_with("lmr_20.15.10_25.45_") && parsed == Some(lmr_tuple), "{} failed: {}", function_name!(), id);
+    }
+
 
 
 }
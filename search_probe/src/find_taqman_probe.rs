@@ -7,6 +7,10 @@ use sha2::Digest;
 
 use std::time::{Instant};
 use std::collections::HashSet;
+use std::collections::HashMap;
+use search_primer::in_silico_pcr::PrimerPair;
+use search_primer::primer_set::TrimRule;
+use search_primer::probe_rules::ProbeEvaluation;
 pub const BLOOMFILTER_TABLE_SIZE: usize = (u32::MAX >> 1) as usize;
 
 pub fn build_counting_bloom_filter(sequences: &Vec<DnaSequence>, start_idx: usize, end_idx: usize, thread_id: usize, primer: &Vec<(Vec<u8>, DnaSequence, DnaSequence)>) -> Vec<u32>{
@@ -140,7 +144,7 @@ fn hash_from_u128(source: u128) -> [u32; 8]{
     return ret_val;
 }
 
-fn count_occurence_from_counting_bloomfilter_table(counting_bloomfilter_table: &Vec<u32>, indice: [u32; 8]) -> u32{
+fn count_occurence_from_counting_bloomfilter_table(counting_bloomfilter_table: &[u32], indice: [u32; 8]) -> u32{
     let mut retval: u32 = u32::MAX;
     for index in indice{
         if counting_bloomfilter_table[index as usize] < retval{
@@ -253,6 +257,188 @@ pub fn number_of_high_occurence_kmer(source_table: &Vec<u32>, sequences: &Vec<Dn
 }
 
 
+//プライマーのタプルの添字, プローブ, トリミング前の5'側のプライマーの5'末端からのオフセット
+pub type ProbeCandidateKey = (usize, u128, usize);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProbeCandidate{
+    pub primer_idx: usize,
+    pub probe:      u128,
+    pub offset:     usize,
+    pub occurence:  u32,
+    pub amplicons:  usize,
+}
+
+/*
+number_of_high_occurence_kmerと同じ探索をするが、プローブごとに
+どのプライマーのタプルの、5'側のプライマーの5'末端から何塩基目で見つかったかを残す。
+オフセットはトリミング前のプライマーで測るので、primer_pair_infoのトリミングした塩基数を足す。
+値は(counting bloom filter上の出現数, そのプローブを含むアンプリコンの数)
+ */
+pub fn collect_probe_candidates(source_table: &[u32], sequences: &[DnaSequence], threshold: u32, thread_id: usize, primer: &[(Vec<u8>, DnaSequence, DnaSequence)], primer_pair_info: &[(String, char, usize)], product_size_max: usize) -> HashMap<ProbeCandidateKey, (u32, usize)>{
+    let mut ret_table: HashMap<ProbeCandidateKey, (u32, usize)> = HashMap::new();
+    let mut l_window_start: usize;
+    let mut l_window_end:   usize;
+    let mut m_window_start: usize;
+    let mut m_window_end:   usize;
+    let mut r_window_start: usize;
+    let mut r_window_end:   usize;
+    let mut primer_l_seq:   u128;
+    let mut primer_l_size:  usize;
+    let mut primer_r_seq:   u128;
+    let mut primer_r_size:  usize;
+    let mut mask_l:         u128;
+    let mut mask_r:         u128;
+
+    let start_time = Instant::now();
+    for (primer_idx, current_primer) in primer.iter().enumerate() {
+        primer_l_size = current_primer.1.len();
+        primer_l_seq  = current_primer.1.subsequence_as_u128(vec!([0, primer_l_size]));
+        primer_r_size = current_primer.2.len();
+        primer_r_seq  = current_primer.2.subsequence_as_u128(vec!([0, primer_r_size]));
+        mask_l        = u128::MAX >> ((64 - primer_l_size) * 2);
+        mask_r        = u128::MAX >> ((64 - primer_r_size) * 2);
+
+        'each_read: for current_sequence in sequences.iter() {
+            l_window_start = 0;
+            if current_sequence.len() < primer_l_size || current_sequence.len() <  primer_r_size ||current_sequence.len() < PROBE_LEN{
+                continue 'each_read;
+            }
+            'each_l_window: loop{
+                l_window_end = l_window_start + primer_l_size;
+                if l_window_end > current_sequence.len(){
+                    break 'each_l_window;
+                }
+                let l_window_as_u128: u128 = current_sequence.subsequence_as_u128(vec![[l_window_start, l_window_end]]);
+                if l_window_as_u128 & mask_l != primer_l_seq{
+                    l_window_start += 1;
+                    continue 'each_l_window;
+                }
+                r_window_start = l_window_end + PROBE_LEN;
+                'each_r_window: loop{
+                    r_window_end = r_window_start + primer_r_size;
+                    if r_window_end > current_sequence.len() || r_window_end - l_window_start > product_size_max{
+                        break 'each_r_window;
+                    }
+                    let r_window_as_u128: u128 = current_sequence.subsequence_as_u128(vec![[r_window_start, r_window_end]]);
+                    if r_window_as_u128 & mask_r != primer_r_seq {
+                        r_window_start += 1;
+                        continue 'each_r_window;
+                    }
+                    //ここまでで、LとRが一致してる。このアンプリコン内のプローブ候補を数える。
+                    m_window_start = l_window_end;
+                    'each_m_window: loop{
+                        m_window_end = m_window_start + PROBE_LEN;
+                        if m_window_end >= r_window_start{
+                            break 'each_m_window;
+                        }
+                        let (m_has_repeat_bool, m_has_repeat_offset) = current_sequence.has_repeat(m_window_start, m_window_end);
+                        if m_has_repeat_bool {
+                            m_window_start += m_has_repeat_offset + 1;
+                            continue 'each_m_window;
+                        }
+                        let probe_candidate: u128    = current_sequence.subsequence_as_u128(vec![[m_window_start, m_window_end]]);
+                        let table_indice:     [u32;8] = hash_from_u128(probe_candidate);
+                        let occurence:        u32     = count_occurence_from_counting_bloomfilter_table(source_table, table_indice);
+                        if occurence >= threshold * DUPPLICATION{
+                            let offset: usize = primer_pair_info[primer_idx].2 + m_window_start - l_window_start;
+                            let entry = ret_table.entry((primer_idx, probe_candidate, offset)).or_insert((occurence, 0));
+                            entry.1 += 1;
+                        }
+                        m_window_start += 1;
+                    }
+                    r_window_start += 1;
+                }
+                l_window_start += 1;
+            }
+        }
+        let end = start_time.elapsed();
+        eprintln!("collect_probe_candidates[{:02?}]: primer {}/{}\tcandidates: {}\tsec: {}.{:03}", thread_id, primer_idx + 1, primer.len(), ret_table.len(), end.as_secs(), end.subsec_millis());
+    }
+    ret_table
+}
+
+//スレッドごとのcollect_probe_candidatesの結果をまとめる。
+pub fn merge_probe_candidates(dest: &mut HashMap<ProbeCandidateKey, (u32, usize)>, source: HashMap<ProbeCandidateKey, (u32, usize)>){
+    for (key, (occurence, amplicons)) in source{
+        let entry = dest.entry(key).or_insert((occurence, 0));
+        entry.1 += amplicons;
+    }
+}
+
+/*
+含まれるアンプリコン数の多い順、次に出現数の多い順に並べる。
+同順位はプライマーのタプル、オフセット、配列の順にして出力を決定的にする。
+ */
+pub fn rank_probe_candidates(candidates: &HashMap<ProbeCandidateKey, (u32, usize)>) -> Vec<ProbeCandidate>{
+    let mut ret_vec: Vec<ProbeCandidate> = candidates.iter().map(|(&(primer_idx, probe, offset), &(occurence, amplicons))| ProbeCandidate{primer_idx, probe, offset, occurence, amplicons}).collect();
+    ret_vec.sort_by(|a, b| b.amplicons.cmp(&a.amplicons)
+        .then(b.occurence.cmp(&a.occurence))
+        .then(a.primer_idx.cmp(&b.primer_idx))
+        .then(a.offset.cmp(&b.offset))
+        .then(a.probe.cmp(&b.probe)));
+    ret_vec
+}
+
+
+//rank_probe_candidatesの順位を、元のプライマーペアごとに上位top件まで分ける。ペアはid順。
+pub fn rank_probe_candidates_by_pair(candidates: &HashMap<ProbeCandidateKey, (u32, usize)>, primer_pair_info: &[(String, char, usize)], top: usize) -> Vec<(String, Vec<ProbeCandidate>)>{
+    let mut ranked_by_pair: HashMap<&String, Vec<ProbeCandidate>> = HashMap::new();
+    for candidate in rank_probe_candidates(candidates){
+        let ranked = ranked_by_pair.entry(&primer_pair_info[candidate.primer_idx].0).or_default();
+        if ranked.len() < top{
+            ranked.push(candidate);
+        }
+    }
+    let mut ret_vec: Vec<(String, Vec<ProbeCandidate>)> = ranked_by_pair.into_iter().map(|(pair_id, ranked)| (pair_id.clone(), ranked)).collect();
+    ret_vec.sort_by(|a, b| a.0.cmp(&b.0));
+    ret_vec
+}
+
+pub fn probe_candidate_tsv_header() -> String{
+    "primer_id\trank\tprobe\tstrand\toffset\toccurrence\tamplicons\ttm\tgc\trules\tevaluation\n".to_string()
+}
+
+//rankは1から。strandはprimer_pair_infoの向き
+pub fn probe_candidate_tsv_line(pair_id: &str, rank: usize, candidate: &ProbeCandidate, strand: char, evaluation: &ProbeEvaluation) -> String{
+    let probe_seq: Vec<u8> = decode_u128_2_dna_seq(&candidate.probe, PROBE_LEN);
+    format!("{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.1}\t{:.2}\t{}\t{}\n", pair_id, rank, String::from_utf8(probe_seq).unwrap(), strand, candidate.offset, candidate.occurence, candidate.amplicons, evaluation.tm, evaluation.gc, if evaluation.passes() {"pass"} else {"fail"}, evaluation)
+}
+
+//タプルごとの(元のプライマーペアのid, 向き, 5'側のプライマーのトリミングで除いた塩基数)
+pub type PrimerPairInfo = Vec<(String, char, usize)>;
+//(id, 5'側のプライマー, 3'側のプライマー)
+pub type PrimerTuple = (Vec<u8>, DnaSequence, DnaSequence);
+
+/*
+展開済みのプライマーペアから、探索に使うプライマーのタプルを作る。
+PCR産物は、+鎖ではL primerの後ろにR primerのrevcomp、-鎖ではR primerの後ろにL primerのrevcompとして読まれるので、この2つの向きだけを探す。
+5'側のプライマーは添字が若い方が5'なので、Keep3Primeで前半をトリミングする。3'側のrevcompはトリミングしない。
+triming_sizeより短いプライマーはそのまま使う。
+2つ目の戻り値はタプルと同じ並びで、元のプライマーペアのidと、+鎖の産物(+)か-鎖の産物(-)か、5'側のプライマーから除いた塩基数
+triming_sizeが0のときはプライマーが空になるのでエラー
+ */
+pub fn primer_tuples(expanded_pairs: &[PrimerPair], triming_size: usize) -> Result<(Vec<PrimerTuple>, PrimerPairInfo), String>{
+    let triming_rule = TrimRule::Keep3Prime(triming_size);
+    let mut primer: Vec<PrimerTuple> = Vec::new();
+    let mut primer_pair_info: PrimerPairInfo = Vec::new();
+    for pair in expanded_pairs.iter() {
+        let primer_id            = Vec::from(pair.id.as_bytes());
+        let left_trimmed         = triming_rule.apply(&pair.left).map_err(|e| format!("{}: {}", pair.id, e))?;
+        let right_trimmed        = triming_rule.apply(&pair.right).map_err(|e| format!("{}: {}", pair.id, e))?;
+        let left_primer_revcomp  = DnaSequence::new(&pair.left).reverse_complement();
+        let right_primer_revcomp = DnaSequence::new(&pair.right).reverse_complement();
+        let id_2: Vec<u8> = [&primer_id, &b"LeftPrimerForward_RightPrimerRevcomp"[..]].concat();
+        let id_3: Vec<u8> = [&primer_id, &b"RightPrimerForward_LeftPrimerRevcomp"[..]].concat();
+
+        primer.push((id_2, DnaSequence::new(&left_trimmed), right_primer_revcomp));
+        primer.push((id_3, DnaSequence::new(&right_trimmed), left_primer_revcomp));
+        primer_pair_info.push((pair.id.clone(), '+', pair.left.len() - left_trimmed.len()));
+        primer_pair_info.push((pair.id.clone(), '-', pair.right.len() - right_trimmed.len()));
+    }
+    Ok((primer, primer_pair_info))
+}
+
 pub fn aggregate_length_between_primer(sequences: &Vec<DnaSequence>, thread_id: usize, primer: &Vec<(Vec<u8>, DnaSequence, DnaSequence)>, product_size_max: usize) -> Vec<u8>{
     let mut l_window_start: usize;
     let mut l_window_end:   usize;
@@ -352,3 +538,47 @@ pub fn aggregate_length_between_primer(sequences: &Vec<DnaSequence>, thread_id:
     }
     return ret_array;
 }
+
+
+#[cfg(test)]
+mod tests{
+    use crate::find_taqman_probe::{merge_probe_candidates, primer_tuples, rank_probe_candidates, rank_probe_candidates_by_pair, ProbeCandidateKey};
+    use search_primer::in_silico_pcr::PrimerPair;
+    use std::collections::HashMap;
+    use ::function_name::named;
+
+    #[test]
+    #[named]
+    fn rank_probe_candidates_test(){
+        let mut candidates: HashMap<ProbeCandidateKey, (u32, usize)> = HashMap::new();
+        candidates.insert((0, 1, 30), (10, 2));
+        candidates.insert((0, 2, 31), (50, 1));
+        candidates.insert((1, 3, 40), (20, 2));
+        let mut other: HashMap<ProbeCandidateKey, (u32, usize)> = HashMap::new();
+        other.insert((0, 2, 31), (50, 4));
+        merge_probe_candidates(&mut candidates, other);
+        let ranked: Vec<(u128, usize)> = rank_probe_candidates(&candidates).iter().map(|c| (c.probe, c.amplicons)).collect();
+        assert!(ranked == vec![(2, 5), (3, 2), (1, 2)], "{} failed: {:?}", function_name!(), ranked);
+    }
+
+    #[test]
+    #[named]
+    fn primer_tuples_test(){
+        let pairs = vec![PrimerPair{id: "p1".to_string(), left: b"AAAACCCCGGGGTTTTAC".to_vec(), right: b"GGTTCCAAGGTTCCAA".to_vec()}];
+        let (primer, primer_pair_info) = primer_tuples(&pairs, 15).unwrap();
+        assert!(primer_tuples(&pairs, 0).is_err(), "{} failed", function_name!());
+        //+鎖はLの3'側15塩基とRのrevcomp、-鎖はRの3'側15塩基とLのrevcomp。revcompはトリミングしない
+        let decode = |seq: &crate::sequence_encoder_util::DnaSequence| seq.decode(0, seq.len());
+        assert!(primer.len() == 2 && primer_pair_info == vec![("p1".to_string(), '+', 3), ("p1".to_string(), '-', 1)], "{} failed: {:?}", function_name!(), primer_pair_info);
+        assert!(primer[0].0 == b"p1LeftPrimerForward_RightPrimerRevcomp".to_vec() && decode(&primer[0].1) == b"ACCCCGGGGTTTTAC".to_vec() && decode(&primer[0].2) == b"TTGGAACCTTGGAACC".to_vec(), "{} failed", function_name!());
+        assert!(primer[1].0 == b"p1RightPrimerForward_LeftPrimerRevcomp".to_vec() && decode(&primer[1].1) == b"GTTCCAAGGTTCCAA".to_vec() && decode(&primer[1].2) == b"GTAAAACCCCGGGGTTTT".to_vec(), "{} failed", function_name!());
+
+        let mut candidates: HashMap<ProbeCandidateKey, (u32, usize)> = HashMap::new();
+        candidates.insert((0, 1, 30), (10, 2));
+        candidates.insert((1, 2, 31), (50, 1));
+        candidates.insert((1, 3, 40), (20, 3));
+        let by_pair = rank_probe_candidates_by_pair(&candidates, &primer_pair_info, 2);
+        let probes: Vec<u128> = by_pair[0].1.iter().map(|c| c.probe).collect();
+        assert!(by_pair.len() == 1 && probes == vec![3, 1], "{} failed: {:?}", function_name!(), probes);
+    }
+}
@@ -14,13 +14,17 @@ use std::sync::{Mutex, Arc};
 use search_probe::find_taqman_probe::BLOOMFILTER_TABLE_SIZE;
 use search_probe::find_taqman_probe::{PROBE_LEN, HASHSET_SIZE};
 use search_probe::find_taqman_probe::{build_counting_bloom_filter, number_of_high_occurence_kmer, aggregate_length_between_primer};
+use search_probe::find_taqman_probe::{collect_probe_candidates, merge_probe_candidates, primer_tuples, rank_probe_candidates_by_pair, ProbeCandidate, ProbeCandidateKey};
+use search_probe::find_taqman_probe::{probe_candidate_tsv_header, probe_candidate_tsv_line};
 use search_probe::sequence_encoder_util::{decode_u128_2_dna_seq};
-use search_probe::sequence_encoder_util::DnaSequence;
+use search_probe::sequence_encoder_util::{DnaSequence, ProbeKmer};
+use search_primer::tuple_record::TupleRecord;
+use search_primer::probe_rules::ProbeRules;
+use search_primer::in_silico_pcr::PrimerPair;
+use search_primer::primer_set::{expand_primer_pair, load_primer_set, PrimerSetOptions};
 use bio::io::fasta::Reader as faReader;
 use bio::io::fasta::Record as faRecord;
 use crate::bio::io::fasta::FastaRead;
-use std::io::BufReader;
-use std::io::BufRead;
 
 fn print_usage(program: &str, opts: &Options) {
     let brief = format!("Usage: {} FILE [options]", program);
@@ -38,9 +42,14 @@ fn main() {
     opts.optopt("a", "threshold", "threshold for hyper log counter. default value is 8.", "THRESHOLD");
     opts.optopt("s", "triming_size", "each primer will be trimmed to this size. 3' side will be remain.", "TRIMSIZE");
     opts.optopt("l", "max_product_size", "max product size of PCR", "PRODUCT SIZE");
-    opts.optopt("p", "primer", "input primers (TSV file).", "TSV FILE");
+    opts.optopt("p", "primer", "input primer pairs (TSV, CSV or FASTA). columns are found by name, e.g. primer id, left primer, right primer.", "TSV FILE");
     opts.optflag("e", "extract", "extract genomic region where primer is located");
     opts.optflag("b", "binary", "outputs binary file");
+    opts.optflag("r", "only-num", "outputs only total number of k-mer");
+    opts.optflag("c", "candidates", "outputs ranked probe candidates per primer pair with their offset from the left primer, strand, occurrence and number of amplicons");
+    opts.optopt("", "top", "number of probe candidates reported per primer pair with -c. default value is all.", "N");
+    opts.optopt("", "probe_rules", "TaqMan probe rules each reported probe is annotated with (comma separated: no_5p_g,c_gt_g,tm_delta,no_g_run,gc). default value is all.", "RULES");
+    opts.optopt("", "primer_tm", "primer Tm used by the tm_delta rule. default value is the highest Tm of the input primers.", "TM");
     opts.optflag("h", "help", "print this help menu");
 
     let matches = match opts.parse(&args[1..]) {
@@ -119,44 +128,42 @@ fn main() {
     gc016 check_crossing_reaction 23/04/07 23:33:13$ 
     */
 
-    let primer_file = File::open(&primer_filename).expect("Error during opening the file");
-    //let primer_reader = BufReader::new(primer_file);
-
-    // 各行を読み取り、タブで分割する
-    let mut primer: Vec<(Vec<u8>, DnaSequence, DnaSequence)> = Vec::new();
-
-    for result in BufReader::new(File::open(&primer_filename).unwrap()).lines() {
-        let line = result.unwrap();
-        if line.starts_with('p') {continue}
-        let fields: Vec<&str> = line.split('\t').collect();
-        // 1, 2, 3カラムのデータをu8型に変換してベクターに格納する
-        //pub fn subsequence(&self, ranges: Vec<[usize; 2]>) -> DnaSequence{
-        /*
-        L primerは、添字が若い方が5'
-        つまり前半数塩基をトリミングする
-        R primerも、添字が若い方が5'
-        つまり前半数塩基をトリミングする
-        */
-        let primer_id            = Vec::from(fields[0].as_bytes());
-        let left_primer_seq      = &fields[1][fields[1].len() - triming_size..]; // 後ろから15文字を取得
-        let right_primer_seq     = &fields[2][fields[2].len() - triming_size..]; // 後ろから15文字を取得
-        let left_primer          = DnaSequence::new(&left_primer_seq.into());
-        let right_primer         = DnaSequence::new(&right_primer_seq.into());
-
-        //let left_primer          = DnaSequence::new(&Vec::from(fields[1].as_bytes()));
-        //let right_primer         = DnaSequence::new(&Vec::from(fields[2].as_bytes()));
-        let left_primer_revcomp  = DnaSequence::new(&Vec::from(fields[1].as_bytes())).reverse_complement();
-        let right_primer_revcomp = DnaSequence::new(&Vec::from(fields[2].as_bytes())).reverse_complement();
-        let id_1: Vec<u8> = [&primer_id, &b"LeftPrimerForward_LeftPrimerRevcomp"[..]].concat();
-        let id_2: Vec<u8> = [&primer_id, &b"LeftPrimerForward_RightPrimerRevcomp"[..]].concat();
-        let id_4: Vec<u8> = [&primer_id, &b"RightPrimerRevcomp_RightPrimerForward"[..]].concat();
-
-        primer.push((id_1, left_primer.clone(), left_primer_revcomp.clone()));
-        primer.push((id_2, left_primer.clone(), right_primer_revcomp.clone()));
-        //primer.push((id_3, right_primer.clone(), left_primer_revcomp.clone()));
-        primer.push((id_4, right_primer_revcomp.clone(), right_primer.clone()));
+    let probe_rules: ProbeRules = match matches.opt_str("probe_rules") {
+        Some(names) => ProbeRules::from_names(&names).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        }),
+        None => ProbeRules::default(),
+    };
+    let mut max_primer_tm: f64 = f64::NAN;
+
+    //TSV/CSV/FASTAを読み、列名から各列を探す。縮重塩基は展開する。
+    let primer_pairs = load_primer_set(&primer_filename, &PrimerSetOptions::default()).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let mut expanded_pairs: Vec<PrimerPair> = Vec::new();
+    for pair in primer_pairs.iter() {
+        let expanded = expand_primer_pair(pair).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+        for pair in expanded {
+            max_primer_tm = max_primer_tm.max(probe_rules.tm(&pair.left)).max(probe_rules.tm(&pair.right));
+            expanded_pairs.push(pair);
+        }
     }
+    let (primer, primer_pair_info) = primer_tuples(&expanded_pairs, triming_size).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
     eprintln!("Number of primers: {:?}", &primer.len());
+    let primer_tm: Option<f64> = match matches.opt_str("primer_tm") {
+        Some(tm) => Some(tm.parse::<f64>().unwrap()),
+        None if max_primer_tm.is_nan() => None,
+        None => Some(max_primer_tm),
+    };
+    eprintln!("Primer Tm for probe rules: {:?}", primer_tm);
 
     let ngsread_file = File::open(&ngsread_input_file).expect("Error during opening the file");
     let mut reader = faReader::new(ngsread_file);
@@ -252,6 +259,52 @@ fn main() {
                 zip(cbf_oyadama.iter_mut(), cbf).for_each(|(x, y)| *x = x.checked_add(y).unwrap_or(u32::MAX));
             }
         });
+        if matches.opt_present("c") {
+            let top: usize = match matches.opt_str("top") {
+                Some(n) => n.parse::<usize>().unwrap(),
+                None => usize::MAX,
+            };
+            let cbf_oyadama_ref = &cbf_oyadama;
+            let primer_pair_info_ref = &primer_pair_info;
+            let mut candidates: HashMap<ProbeCandidateKey, (u32, usize)> = HashMap::new();
+            thread::scope(|scope|{
+                let mut children_2 = Vec::new();
+                for i in 1..threads {
+                    let sequences_ref = Arc::clone(&sequences_ref);
+                    let primer_ref    = Arc::clone(&primer_ref);
+                    children_2.push(
+                        scope.spawn(move ||
+                            {
+                                let start_idx: usize = (i - 1) * chunk_size;
+                                let end_idx: usize = if i != threads - 1 { i * chunk_size } else { sequences_ref.len() };
+                                eprintln!("thread [{}]: start calling collect_probe_candidates", i);
+                                let candidates: HashMap<ProbeCandidateKey, (u32, usize)> = collect_probe_candidates(cbf_oyadama_ref, &sequences_ref[start_idx..end_idx], threshold, i, &primer_ref, primer_pair_info_ref, max_product_size);
+                                eprintln!("thread [{}]: finish calling collect_probe_candidates", i);
+                                candidates
+                            }
+                        )
+                    )
+                }
+                for child in children_2{
+                    merge_probe_candidates(&mut candidates, child.join().unwrap());
+                }
+            });
+
+            //プライマーペアごとに、アンプリコン数・出現数の順位をつけて出力する
+            let ranked_by_pair: Vec<(String, Vec<ProbeCandidate>)> = rank_probe_candidates_by_pair(&candidates, &primer_pair_info, top);
+            let mut w = BufWriter::new(fs::File::create(&output_file).unwrap());
+            w.write_all(probe_candidate_tsv_header().as_bytes()).unwrap();
+            for (pair_id, ranked) in &ranked_by_pair{
+                for (rank, candidate) in ranked.iter().enumerate(){
+                    let evaluation = probe_rules.evaluate(&decode_u128_2_dna_seq(&candidate.probe, PROBE_LEN), primer_tm);
+                    w.write_all(probe_candidate_tsv_line(pair_id, rank + 1, candidate, primer_pair_info[candidate.primer_idx].1, &evaluation).as_bytes()).unwrap();
+                }
+            }
+            w.flush().unwrap();
+            eprintln!("finish writing to output file: {:?}", &output_file);
+            eprintln!("primer pairs with probe candidates: {}\tcandidates: {}", ranked_by_pair.len(), candidates.len());
+            return;
+        }
         let h_cbf_h_oyadama: Arc<Mutex<HashSet<u128>>> = Arc::new(Mutex::new(HashSet::with_capacity(HASHSET_SIZE)));
         let cbf_oyadama_ref = &cbf_oyadama;
         let h_cbf_h_oyadama_ref = &h_cbf_h_oyadama;
@@ -292,8 +345,6 @@ fn main() {
 
         let mut previous_kmer: u128 = 0;
         let mut cnt = 0;
-        let mut buf_array: [u8; 16] = [0; 16];
-        let mut buf_num: u128;
 
         if matches.opt_present("r") {
             eprintln!("matches.opt_present('r'): {}\tmatches.opt_present('b'): {}", matches.opt_present("r"), matches.opt_present("b"));
@@ -310,22 +361,20 @@ fn main() {
             for each_kmer in &high_occurence_kmer{
                 if previous_kmer != *each_kmer{
                     cnt += 1;
-                    buf_num = *each_kmer;
-                    for i in 0..16{
-                        buf_array[15 - i] = u8::try_from(buf_num & 0xFF).unwrap();
-                        buf_num >>= 8;
-                    }
-                    w.write(&buf_array).unwrap();
+                    w.write_all(&ProbeKmer(*each_kmer).to_bytes()).unwrap();
                 }
                 previous_kmer = *each_kmer;
             }
         }
         if !matches.opt_present("r") && !matches.opt_present("b"){
             eprintln!("matches.opt_present('r'): {}\tmatches.opt_present('b'): {}", matches.opt_present("r"), matches.opt_present("b"));
+            //プローブ配列, Tm, GC, 全ルールを満たすか, ルールごとの結果
             for each_kmer in &high_occurence_kmer{
                 if previous_kmer != *each_kmer{
                     cnt += 1;
-                    writeln!(&mut w, "{:?}", String::from_utf8(decode_u128_2_dna_seq(&each_kmer, PROBE_LEN)).unwrap()).unwrap();
+                    let probe_seq: Vec<u8> = decode_u128_2_dna_seq(&each_kmer, PROBE_LEN);
+                    let evaluation = probe_rules.evaluate(&probe_seq, primer_tm);
+                    writeln!(&mut w, "{:?}\t{:.1}\t{:.2}\t{}\t{}", String::from_utf8(probe_seq).unwrap(), evaluation.tm, evaluation.gc, if evaluation.passes() {"pass"} else {"fail"}, evaluation).unwrap();
                 }
                 previous_kmer = *each_kmer;
             }
@@ -1,5 +1,7 @@
 use crate::find_taqman_probe::PROBE_LEN;
 use std::cmp;
+use search_primer::tuple_id::TupleId;
+use search_primer::tuple_record::{record_bytes, reverse_complement_bits, TupleRecord};
 
 
 pub fn decode_u128_2_dna_seq(source:&u128, char_size: usize) -> Vec<u8>{
@@ -36,6 +38,30 @@ pub fn decode_u128_probe(source: &u128) -> [u8; PROBE_LEN]{
 }
 
 
+//プローブのk-mer。PROBE_LEN塩基をu128の下位bitに寄せて詰め、16byte big endianで書く
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProbeKmer(pub u128);
+
+impl TupleRecord for ProbeKmer{
+    const RECORD_SIZE: usize = 16;
+    fn to_bytes(&self) -> Vec<u8>{
+        self.0.to_be_bytes().to_vec()
+    }
+    fn from_bytes(bytes: &[u8]) -> Result<ProbeKmer, String>{
+        Ok(ProbeKmer(u128::from_be_bytes(record_bytes(bytes)?)))
+    }
+    fn tuple_id(&self) -> TupleId{
+        TupleId::Probe{value: self.0, len: PROBE_LEN as u8}
+    }
+    fn geometry(&self) -> Vec<(char, usize, usize)>{
+        vec![('P', 0, PROBE_LEN)]
+    }
+    fn reverse_complement(&self) -> ProbeKmer{
+        ProbeKmer(reverse_complement_bits(self.0, PROBE_LEN))
+    }
+}
+
+
 
 
 pub struct DnaSequence{
@@ -386,8 +412,21 @@ impl DnaSequence{//DNA sequenceは上位bitに寄せてる
 
 #[cfg(test)]
 mod tests{
-    use crate::sequence_encoder_util::DnaSequence;
+    use crate::sequence_encoder_util::{DnaSequence, ProbeKmer};
+    use search_primer::tuple_record::TupleRecord;
     use ::function_name::named;
+
+    #[test]
+    #[named]
+    fn probe_kmer_record_test(){
+        let source: Vec<u8> = b"ACGTTTGCAAGGCCTTACGATCGATCGGGA".to_vec();
+        let kmer = ProbeKmer(DnaSequence::new(&source).subsequence_as_u128(vec![[0, source.len()]]));
+        let rc = kmer.reverse_complement();
+        assert!(kmer.sequence() == source, "{} failed", function_name!());
+        assert!(rc.sequence() == DnaSequence::new(&source).reverse_complement().decode(0, source.len()), "{} failed", function_name!());
+        assert!(rc.reverse_complement() == kmer, "{} failed", function_name!());
+        assert!(ProbeKmer::from_bytes(&kmer.to_bytes()) == Ok(kmer), "{} failed", function_name!());
+    }
 /*
 *
 *Encode Test
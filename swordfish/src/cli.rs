@@ -0,0 +1,114 @@
+// サブコマンドに共通のオプション処理とエラー。
+// 使い方の誤り(未知のオプション、必須オプションの欠落、不正な値)は終了コード2、
+// 実行中の失敗(ファイルが開けない、primer3_coreが失敗したなど)は終了コード1で終わる。
+
+use bio::io::fasta::FastaRead;
+use bio::io::fasta::Reader as faReader;
+use bio::io::fasta::Record as faRecord;
+use getopts::Matches;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq)]
+pub enum CliError {
+    Usage(String),
+    Failure(String),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage(_) => 2,
+            CliError::Failure(_) => 1,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Usage(message) | CliError::Failure(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(e: std::io::Error) -> CliError {
+        CliError::Failure(e.to_string())
+    }
+}
+
+/// Library functions report failures as `String`.
+impl From<String> for CliError {
+    fn from(message: String) -> CliError {
+        CliError::Failure(message)
+    }
+}
+
+/// Value of `--name`, or `default` when it is not given.
+pub fn parse_opt<T: FromStr>(matches: &Matches, name: &str, default: T) -> Result<T, CliError> {
+    match matches.opt_str(name) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| CliError::Usage(format!("invalid value for --{}: {:?}", name, value))),
+        None => Ok(default),
+    }
+}
+
+pub fn required(matches: &Matches, name: &str) -> Result<String, CliError> {
+    matches
+        .opt_str(name)
+        .ok_or_else(|| CliError::Usage(format!("--{} is required", name)))
+}
+
+/// Every value of a repeatable option; at least one must be given.
+pub fn required_multi(matches: &Matches, name: &str) -> Result<Vec<String>, CliError> {
+    let values = matches.opt_strs(name);
+    if values.is_empty() {
+        return Err(CliError::Usage(format!("--{} is required", name)));
+    }
+    Ok(values)
+}
+
+/// Number of threads from `--thread`, which must be at least 1.
+pub fn threads(matches: &Matches, default: usize) -> Result<usize, CliError> {
+    let threads: usize = parse_opt(matches, "thread", default)?;
+    if threads == 0 {
+        return Err(CliError::Usage("--thread must be at least 1".to_string()));
+    }
+    Ok(threads)
+}
+
+pub fn open(path: &str) -> Result<File, CliError> {
+    File::open(path).map_err(|e| CliError::Failure(format!("{}: {}", path, e)))
+}
+
+pub fn create(path: &str) -> Result<File, CliError> {
+    File::create(path).map_err(|e| CliError::Failure(format!("{}: {}", path, e)))
+}
+
+/// Calls `f` with the id and sequence of every record of a FASTA file.
+pub fn for_each_fasta_record<F: FnMut(&str, &[u8])>(path: &str, mut f: F) -> Result<(), CliError> {
+    let mut reader = faReader::new(open(path)?);
+    let mut record = faRecord::new();
+    loop {
+        reader
+            .read(&mut record)
+            .map_err(|e| CliError::Failure(format!("{}: {}", path, e)))?;
+        if record.is_empty() {
+            return Ok(());
+        }
+        f(record.id(), record.seq());
+    }
+}
+
+/// Calls `f` with every line of a text file.
+pub fn for_each_line<F: FnMut(&str)>(path: &str, mut f: F) -> Result<(), CliError> {
+    let reader = BufReader::new(open(path)?);
+    for line in reader.lines() {
+        f(&line.map_err(|e| CliError::Failure(format!("{}: {}", path, e)))?);
+    }
+    Ok(())
+}
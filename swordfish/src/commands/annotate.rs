@@ -0,0 +1,237 @@
+// テキスト(TSV, FASTA, BLASTの結果など)に含まれるtupleの識別子を塩基配列に戻して書き足す。
+// FASTAの見出し行には " L=... R=..." を、それ以外の行には "\tL=...;R=..." を、見つけた識別子ごとに行末へ足す。
+// 識別子を含まない行はそのまま書き出す。
+// --legacyでは、tupleの識別子を使う前の版が書いた名前(lr, probeの値の16進数)も読む。
+
+use crate::cli::{create, for_each_line, required, CliError};
+use crate::records::{Record, RecordKind};
+use getopts::{Matches, Options};
+use search_primer::tuple_id::TupleId;
+use std::io::{BufWriter, Write};
+
+pub fn options() -> Options {
+    let mut opts = Options::new();
+    opts.optopt("i", "input", "text file with tuple ids.", "FILE");
+    opts.optopt("o", "output", "annotated text file.", "OUTPUT");
+    opts.optflag(
+        "",
+        "header",
+        "the first line is a TSV header; a windows column is added to it.",
+    );
+    opts.optopt(
+        "",
+        "legacy",
+        "also decode names written by older versions: the hex of an lr or probe value after SEQUENCE_ID= or before a window suffix (-L, -R, -P, _l, _r, _p).",
+        "KIND",
+    );
+    opts
+}
+
+/// Ids of `kind` written as the bare hex of the value, by versions before tuple ids:
+/// a `SEQUENCE_ID=` value of primer3_core, or a whitespace separated field followed by
+/// a window suffix, such as the BLAST query `>1b-L` or the FASTA header `>1B_l`.
+pub fn find_legacy_ids(line: &str, kind: RecordKind) -> Vec<TupleId> {
+    let suffixes: &[&str] = match kind {
+        RecordKind::Lr => &["-L", "-R", "_l", "_r"],
+        RecordKind::Probe => &["-P", "_p"],
+        RecordKind::Lmr => &[],
+    };
+    let decode = |hex: &str| -> Option<TupleId> {
+        if hex.is_empty() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let v = u128::from_str_radix(hex, 16).ok()?;
+        Record::from_bytes(kind, &v.to_be_bytes())
+            .ok()
+            .map(|record| record.tuple_id())
+    };
+    if let Some(value) = line.trim().strip_prefix("SEQUENCE_ID=") {
+        return decode(value).into_iter().collect();
+    }
+    line.split_whitespace()
+        .filter_map(|field| {
+            let field = field.strip_prefix('>').unwrap_or(field);
+            suffixes
+                .iter()
+                .find_map(|suffix| field.strip_suffix(suffix))
+                .and_then(decode)
+        })
+        .collect()
+}
+
+/// `line` with the windows of every tuple id in it appended, and with `legacy`
+/// also of the ids `find_legacy_ids` finds.
+pub fn annotate_line(line: &str, legacy: Option<RecordKind>) -> String {
+    let mut ids: Vec<TupleId> = TupleId::find_all(line)
+        .into_iter()
+        .map(|(_, id)| id)
+        .collect();
+    if let Some(kind) = legacy {
+        ids.extend(find_legacy_ids(line, kind));
+    }
+    let fasta_header = line.starts_with('>');
+    let mut annotated = line.to_string();
+    for id in ids {
+        let windows: Vec<String> = id
+            .windows()
+            .into_iter()
+            .map(|(name, bases)| format!("{}={}", name, String::from_utf8(bases).unwrap()))
+            .collect();
+        if fasta_header {
+            annotated.push(' ');
+            annotated.push_str(&windows.join(" "));
+        } else {
+            annotated.push('\t');
+            annotated.push_str(&windows.join(";"));
+        }
+    }
+    annotated
+}
+
+pub fn run(matches: &Matches) -> Result<(), CliError> {
+    let input_file = required(matches, "i")?;
+    let output_file = required(matches, "o")?;
+    let header = matches.opt_present("header");
+    let legacy = match matches.opt_str("legacy") {
+        None => None,
+        Some(name) => match RecordKind::parse(&name)? {
+            RecordKind::Lmr => return Err(CliError::Usage(
+                "--legacy lmr is not supported: old lmr names joined l, m and r without separators"
+                    .to_string(),
+            )),
+            kind => Some(kind),
+        },
+    };
+
+    let mut w = BufWriter::new(create(&output_file)?);
+    let mut lines: usize = 0;
+    let mut annotated: usize = 0;
+    let mut result: Result<(), CliError> = Ok(());
+    for_each_line(&input_file, |line| {
+        if result.is_err() {
+            return;
+        }
+        let out = if header && lines == 0 {
+            format!("{}\twindows", line)
+        } else {
+            let out = annotate_line(line, legacy);
+            annotated += (out.len() != line.len()) as usize;
+            out
+        };
+        lines += 1;
+        result = writeln!(w, "{}", out).map_err(CliError::from);
+    })?;
+    result?;
+    w.flush()?;
+    eprintln!(
+        "{} of {} lines of {:?} annotated: {}",
+        annotated, lines, input_file, output_file
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::annotate::annotate_line;
+    use crate::commands::{args, dispatch};
+    use crate::records::RecordKind;
+    use ::function_name::named;
+    use search_primer::tuple_id::TupleId;
+    use std::fs;
+
+    #[test]
+    #[named]
+    fn annotate_line_test() {
+        let id = TupleId::Lmr {
+            l: 0x1b,
+            m: 2,
+            r: 3,
+            lens: [4, 2, 3],
+            m_offset: 10,
+            r_offset: 20,
+        };
+        let header = annotate_line(&format!(">{}-M", id), None);
+        let tsv = annotate_line(&format!("{}\t99.5", id), None);
+        assert!(
+            header == format!(">{}-M L=ACGT M=AG R=AAT", id)
+                && tsv == format!("{}\t99.5\tL=ACGT;M=AG;R=AAT", id)
+                && annotate_line("no ids here", None) == "no ids here",
+            "{} failed: {} {}",
+            function_name!(),
+            header,
+            tsv
+        );
+    }
+
+    #[test]
+    #[named]
+    fn annotate_legacy_line_test() {
+        // 0x1bはLが32塩基のA、Rが29塩基のAとCGT
+        let l = "A".repeat(32);
+        let r = format!("{}CGT", "A".repeat(29));
+        let lr = Some(RecordKind::Lr);
+        let primer3 = annotate_line("SEQUENCE_ID=1b", lr);
+        let blast = annotate_line("1b-R\tchr1\t100\t99.5", lr);
+        let header = annotate_line(">1B_l", lr);
+        assert!(
+            primer3 == format!("SEQUENCE_ID=1b\tL={};R={}", l, r)
+                && blast == format!("1b-R\tchr1\t100\t99.5\tL={};R={}", l, r)
+                && header == format!(">1B_l L={} R={}", l, r),
+            "{} failed: {} {} {}",
+            function_name!(),
+            primer3,
+            blast,
+            header
+        );
+        // 接尾辞のない数や、種類の違う接尾辞は識別子とみなさない
+        let untouched = ["100\t200", "1b-P\tchr1", "SEQUENCE_ID=xyz"];
+        assert!(
+            untouched
+                .iter()
+                .all(|line| annotate_line(line, lr) == *line)
+                && annotate_line("1b-R", None) == "1b-R",
+            "{} failed",
+            function_name!()
+        );
+        let probe = annotate_line(">1b-P", Some(RecordKind::Probe));
+        assert!(
+            probe == format!(">1b-P P={}CGT", "A".repeat(27)),
+            "{} failed: {}",
+            function_name!(),
+            probe
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        fs::write(path("in.txt"), "SEQUENCE_ID=1b\n").unwrap();
+        let annotate = |kind: &str, output: &str| {
+            dispatch(&args(&[
+                "annotate",
+                "-i",
+                &path("in.txt"),
+                "-o",
+                &path(output),
+                "--legacy",
+                kind,
+            ]))
+        };
+        let code = annotate("lr", "out.txt");
+        let out = fs::read_to_string(path("out.txt")).unwrap_or_default();
+        assert!(
+            code == 0 && out == format!("SEQUENCE_ID=1b\tL={};R={}\n", l, r),
+            "{} failed: {} {:?}",
+            function_name!(),
+            code,
+            out
+        );
+        let lmr = annotate("lmr", "lmr.txt");
+        let unknown = annotate("xyz", "xyz.txt");
+        assert!(
+            lmr == 2 && unknown == 2,
+            "{} failed: {} {}",
+            function_name!(),
+            lmr,
+            unknown
+        );
+    }
+}
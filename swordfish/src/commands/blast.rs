@@ -0,0 +1,95 @@
+use crate::cli::{create, required, CliError};
+use crate::records::{kind_option, read_record_file, record_kind};
+use getopts::{Matches, Options};
+use std::io::{BufWriter, Write};
+
+pub fn options() -> Options {
+    let mut opts = Options::new();
+    opts.optopt("i", "input", "binary record file.", "FILE");
+    opts.optopt("o", "output", "BLAST query file (FASTA).", "FASTA");
+    opts.optopt("n", "names", "list of record names, one per line.", "NAMES");
+    kind_option(&mut opts);
+    opts
+}
+
+pub fn run(matches: &Matches) -> Result<(), CliError> {
+    let input_file = required(matches, "i")?;
+    let query_file = required(matches, "o")?;
+    let names_file = required(matches, "n")?;
+    let kind = record_kind(matches)?;
+
+    // 入力の順を保つ
+    let records = read_record_file(&input_file, kind)?;
+    let mut queries = BufWriter::new(create(&query_file)?);
+    let mut names = BufWriter::new(create(&names_file)?);
+    for record in &records {
+        writeln!(queries, "{}", record.blast_query())?;
+        writeln!(names, "{}", record.name())?;
+    }
+    queries.flush()?;
+    names.flush()?;
+    eprintln!(
+        "{} records written to {:?} and {:?}",
+        records.len(),
+        query_file,
+        names_file
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{args, dispatch};
+    use crate::records::Record;
+    use ::function_name::named;
+    use std::fs;
+
+    #[test]
+    #[named]
+    fn blast_query_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        let records: Vec<Record> = vec![Record::Lr(0x1b), Record::Lr(1 << 64)];
+        fs::write(
+            path("lr.bin"),
+            records
+                .iter()
+                .flat_map(|r| r.to_bytes())
+                .collect::<Vec<u8>>(),
+        )
+        .unwrap();
+        let code = dispatch(&args(&[
+            "blast",
+            "-i",
+            &path("lr.bin"),
+            "-o",
+            &path("query.fa"),
+            "-n",
+            &path("names.txt"),
+        ]));
+        let names = fs::read_to_string(path("names.txt")).unwrap_or_default();
+        let queries = fs::read_to_string(path("query.fa")).unwrap_or_default();
+        let first = "lr_32.32_0000000000000000000000000000001b";
+        let second = "lr_32.32_00000000000000010000000000000000";
+        // レコードごとにL, Rの順で、空行で区切る
+        let expected_queries = format!(
+            ">{}-L\n{}\n>{}-R\n{}CGT\n\n>{}-L\n{}C\n>{}-R\n{}\n\n",
+            first,
+            "A".repeat(32),
+            first,
+            "A".repeat(29),
+            second,
+            "A".repeat(31),
+            second,
+            "A".repeat(32)
+        );
+        assert!(
+            code == 0 && names == format!("{}\n{}\n", first, second) && queries == expected_queries,
+            "{} failed: {} {:?} {:?}",
+            function_name!(),
+            code,
+            names,
+            queries
+        );
+    }
+}
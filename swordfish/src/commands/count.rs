@@ -0,0 +1,345 @@
+use crate::cli::{create, for_each_fasta_record, parse_opt, required, threads, CliError};
+use getopts::{Matches, Options};
+use search_primer::background::remove_background;
+use search_primer::counting_bloomfilter_util::{
+    count_high_occurence_lr_tuple, BLOOMFILTER_TABLE_SIZE, L_LEN, R_LEN,
+};
+use search_primer::reference_loci::{load_reference, reference_lr_tuple_loci, write_locus_tsv};
+use search_primer::sequence_encoder_util::{decode_u128_2_dna_seq, DnaSequence};
+use search_primer::tuple_record::{write_tuple_records, LrTuple};
+use std::io::{BufWriter, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CountFormat {
+    Bin,
+    Text,
+    Count,
+}
+
+pub fn options() -> Options {
+    let mut opts = Options::new();
+    opts.optopt(
+        "i",
+        "input",
+        "reads, or an assembly with -g (FASTA).",
+        "READS",
+    );
+    opts.optopt("o", "output", "output file.", "OUTPUT");
+    opts.optopt(
+        "t",
+        "thread",
+        "number of threads to use. default value is 8.",
+        "THREAD",
+    );
+    opts.optopt(
+        "a",
+        "threshold",
+        "threshold of occurence (of distinct loci with -g). default value is 1000.",
+        "THRESHOLD",
+    );
+    opts.optopt(
+        "m",
+        "margin_size",
+        "margin between l and r segments. default value is 0.",
+        "MARGIN_SIZE",
+    );
+    opts.optmulti(
+        "B",
+        "background",
+        "background (non-target) genomes or reads in FASTA. can be given more than once.",
+        "FILE",
+    );
+    opts.optopt(
+        "x",
+        "max_background",
+        "lr-tuples occurring more often than this in the background sets are removed. default value is 0.",
+        "COUNT",
+    );
+    opts.optflag(
+        "g",
+        "reference",
+        "READS is an assembly: lr-tuples are enumerated on both strands of each contig and counted by distinct loci. bin also writes OUTPUT.loci.tsv, text writes the loci.",
+    );
+    opts.optopt(
+        "f",
+        "format",
+        "bin (16 bytes per lr-tuple, default), text (one lr-tuple per line) or count (number of lr-tuples).",
+        "FORMAT",
+    );
+    opts.optopt(
+        "",
+        "cbf_size",
+        "number of counters in the counting bloom filter (2 bytes each). default value is 2^30.",
+        "SIZE",
+    );
+    opts
+}
+
+pub fn run(matches: &Matches) -> Result<(), CliError> {
+    let input_file = required(matches, "i")?;
+    let output_file = required(matches, "o")?;
+    let threads = threads(matches, 8)?;
+    let threshold: u16 = parse_opt(matches, "a", 1000)?;
+    let margin: usize = parse_opt(matches, "m", 0)?;
+    let max_background: u16 = parse_opt(matches, "x", 0)?;
+    let background_files: Vec<String> = matches.opt_strs("B");
+    let cbf_size: usize = parse_opt(matches, "cbf_size", BLOOMFILTER_TABLE_SIZE)?;
+    if cbf_size == 0 {
+        return Err(CliError::Usage("--cbf_size must be at least 1".to_string()));
+    }
+    let format = match matches.opt_str("f").as_deref() {
+        None | Some("bin") => CountFormat::Bin,
+        Some("text") => CountFormat::Text,
+        Some("count") => CountFormat::Count,
+        Some(other) => {
+            return Err(CliError::Usage(format!(
+                "unknown format {:?} (bin, text or count)",
+                other
+            )))
+        }
+    };
+
+    let mut w = BufWriter::new(create(&output_file)?);
+    if matches.opt_present("g") {
+        let (contig_names, segments) = load_reference(&input_file)?;
+        let mut loci = reference_lr_tuple_loci(&segments, threshold, threads, margin, cbf_size);
+        drop(segments);
+        if !background_files.is_empty() {
+            remove_background(
+                &mut loci,
+                |l| l.0,
+                &background_files,
+                max_background,
+                threads,
+                margin,
+            )?;
+        }
+        match format {
+            CountFormat::Count => writeln!(
+                w,
+                "lr_tuple count: {}\tthreshold: {}\tinput file {:?}\treference: true",
+                loci.len(),
+                threshold,
+                input_file
+            )?,
+            CountFormat::Text => write_locus_tsv(&mut w, &loci, &contig_names)?,
+            CountFormat::Bin => {
+                write_tuple_records(&mut w, loci.iter().map(|(tuple, _)| LrTuple(*tuple)))?;
+                let loci_file = format!("{}.loci.tsv", output_file);
+                let mut loci_writer = BufWriter::new(create(&loci_file)?);
+                write_locus_tsv(&mut loci_writer, &loci, &contig_names)?;
+                loci_writer.flush()?;
+            }
+        }
+        w.flush()?;
+        eprintln!(
+            "{} lr-tuples at {} or more loci: {:?}",
+            loci.len(),
+            threshold,
+            output_file
+        );
+        return Ok(());
+    }
+
+    let mut sequences: Vec<DnaSequence> = Vec::new();
+    for_each_fasta_record(&input_file, |_, seq| {
+        sequences.push(DnaSequence::new(&seq.to_vec()))
+    })?;
+    eprintln!("loading {:?} done: {} reads", input_file, sequences.len());
+    let mut tuples: Vec<u128> =
+        count_high_occurence_lr_tuple(&sequences, threshold, threads, margin, cbf_size)
+            .into_keys()
+            .collect();
+    drop(sequences);
+    tuples.sort_unstable();
+    if !background_files.is_empty() {
+        remove_background(
+            &mut tuples,
+            |t| *t,
+            &background_files,
+            max_background,
+            threads,
+            margin,
+        )?;
+    }
+    match format {
+        CountFormat::Count => writeln!(
+            w,
+            "lr_tuple count: {}\tthreshold: {}\tinput file {:?}",
+            tuples.len(),
+            threshold,
+            input_file
+        )?,
+        CountFormat::Text => {
+            for tuple in &tuples {
+                writeln!(
+                    w,
+                    "{}",
+                    String::from_utf8(decode_u128_2_dna_seq(tuple, L_LEN + R_LEN)).unwrap()
+                )?;
+            }
+        }
+        CountFormat::Bin => {
+            write_tuple_records(&mut w, tuples.iter().map(|tuple| LrTuple(*tuple)))?;
+        }
+    }
+    w.flush()?;
+    eprintln!(
+        "{} lr-tuples occurring {} or more times: {:?}",
+        tuples.len(),
+        threshold,
+        output_file
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{args, dispatch};
+    use ::function_name::named;
+    use search_primer::in_silico_pcr::reverse_complement;
+    use std::fs;
+
+    const TARGET: &[u8] =
+        b"ACGTTGCAAGGCTTACCGTAGGATCCTTAGCAGTCAAGTCTTGACCATGCGATACGGTCAGTACCTGAAGCTTCGAT";
+
+    #[test]
+    #[named]
+    fn count_reads_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        let head = String::from_utf8(TARGET[..64].to_vec()).unwrap();
+        // 先頭64塩基のtupleだけが4回、ほかは1回
+        fs::write(
+            path("reads.fa"),
+            format!(
+                ">r1\n{}\n>r2\n{}\n>r3\n{}\n>r4\n{}\n",
+                head,
+                head,
+                head,
+                String::from_utf8_lossy(TARGET)
+            ),
+        )
+        .unwrap();
+        fs::write(path("background.fa"), format!(">bg\n{}\n", head)).unwrap();
+        let count = |extra: &[&str], output: &str| -> (i32, String) {
+            let mut words = vec![
+                "count",
+                "-i",
+                &path("reads.fa"),
+                "-o",
+                output,
+                "-a",
+                "3",
+                "-t",
+                "2",
+                "--cbf_size",
+                "65536",
+            ]
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<String>>();
+            words.extend(extra.iter().map(|s| s.to_string()));
+            let code = dispatch(&words);
+            (code, fs::read_to_string(output).unwrap_or_default())
+        };
+
+        let (code, text) = count(&["-f", "text"], &path("text.txt"));
+        assert!(
+            code == 0 && text == format!("{}\n", head),
+            "{} failed: {} {:?}",
+            function_name!(),
+            code,
+            text
+        );
+        let (code, _) = count(&[], &path("out.bin"));
+        let bin_len = fs::metadata(path("out.bin")).map(|m| m.len()).unwrap_or(0);
+        assert!(
+            code == 0 && bin_len == 16,
+            "{} failed: {} {}",
+            function_name!(),
+            code,
+            bin_len
+        );
+        // 背景に1回あるので、-x 0では除かれ、-x 1では残る
+        let background = path("background.fa");
+        let (code, removed) = count(&["-f", "count", "-B", &background], &path("removed.txt"));
+        let (_, kept) = count(
+            &["-f", "count", "-B", &background, "-x", "1"],
+            &path("kept.txt"),
+        );
+        assert!(
+            code == 0
+                && removed.starts_with("lr_tuple count: 0\t")
+                && kept.starts_with("lr_tuple count: 1\t"),
+            "{} failed: {:?} {:?}",
+            function_name!(),
+            removed,
+            kept
+        );
+        let zero = dispatch(&args(&[
+            "count",
+            "-i",
+            &path("reads.fa"),
+            "-o",
+            &path("zero.bin"),
+            "--cbf_size",
+            "0",
+        ]));
+        assert!(zero == 2, "{} failed: {}", function_name!(), zero);
+    }
+
+    #[test]
+    #[named]
+    fn count_reference_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        // 1コピー目は順鎖、2コピー目はNを挟んだ逆鎖
+        let contig: Vec<u8> = [
+            &b"TTGA"[..],
+            TARGET,
+            b"NNNNN",
+            &reverse_complement(TARGET),
+            b"CA",
+        ]
+        .concat();
+        fs::write(
+            path("assembly.fa"),
+            format!(">chr1\n{}\n", String::from_utf8_lossy(&contig)),
+        )
+        .unwrap();
+        let code = dispatch(&args(&[
+            "count",
+            "-g",
+            "-i",
+            &path("assembly.fa"),
+            "-o",
+            &path("out.bin"),
+            "-a",
+            "2",
+            "-t",
+            "2",
+            "--cbf_size",
+            "65536",
+        ]));
+        let loci = fs::read_to_string(path("out.bin.loci.tsv")).unwrap_or_default();
+        let second_end = 4 + TARGET.len() + 5 + TARGET.len();
+        let head_line = format!(
+            "{}\t2\tchr1:5-68(+),chr1:{}-{}(-)",
+            String::from_utf8_lossy(&TARGET[..64]),
+            second_end - 63,
+            second_end
+        );
+        let bin_len = fs::metadata(path("out.bin")).map(|m| m.len()).unwrap_or(0);
+        assert!(
+            code == 0
+                && loci.starts_with("lr_tuple\tcopies\tloci\n")
+                && loci.lines().any(|l| l == head_line)
+                && bin_len as usize == 16 * (loci.lines().count() - 1),
+            "{} failed: {} {}",
+            function_name!(),
+            code,
+            loci
+        );
+    }
+}
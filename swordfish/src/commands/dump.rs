@@ -0,0 +1,498 @@
+// バイナリのレコードを1件ずつ読み、テキストに戻す。入力は指定された順にそのまま読み、重複も除かない。
+// 出力は text(配列のみ), tsv, fasta(1件を1エントリ), fasta_windows(L, M, Rなどを別エントリ), jsonl。
+// カウント・GC・IUPACのパターンで絞り込んだ後、N件ごと、または無作為にk件を取り出せる。
+// to-fastaは -f fasta を指定したdumpとして残している。
+
+use crate::cli::{create, parse_opt, required, required_multi, CliError};
+use crate::commands::merge::counted_sources;
+use crate::records::{kind_option, record_kind, Record, RecordKind};
+use getopts::{Matches, Options};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use search_primer::primer_set::iupac_bases;
+use search_primer::probe_rules::{gc_content, ProbeRules};
+use std::io::{BufWriter, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DumpFormat {
+    Text,
+    Tsv,
+    Fasta,
+    FastaWindows,
+    Jsonl,
+}
+
+impl DumpFormat {
+    pub fn parse(name: &str) -> Result<DumpFormat, CliError> {
+        match name {
+            "text" => Ok(DumpFormat::Text),
+            "tsv" => Ok(DumpFormat::Tsv),
+            "fasta" => Ok(DumpFormat::Fasta),
+            "fasta_windows" => Ok(DumpFormat::FastaWindows),
+            "jsonl" => Ok(DumpFormat::Jsonl),
+            _ => Err(CliError::Usage(format!(
+                "unknown format {:?} (text, tsv, fasta, fasta_windows or jsonl)",
+                name
+            ))),
+        }
+    }
+}
+
+/// Conditions a record must meet to be written. GC is over all bases of the record.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DumpFilter {
+    pub min_count: u32,
+    pub max_count: u32,
+    pub min_gc: f64,
+    pub max_gc: f64,
+    /// IUPAC pattern that must occur in one of the windows.
+    pub pattern: Option<Vec<u8>>,
+}
+
+impl Default for DumpFilter {
+    fn default() -> Self {
+        DumpFilter {
+            min_count: 0,
+            max_count: u32::MAX,
+            min_gc: 0.0,
+            max_gc: 1.0,
+            pattern: None,
+        }
+    }
+}
+
+fn matches_at(window: &[u8], pattern: &[u8]) -> bool {
+    window.windows(pattern.len()).any(|w| {
+        w.iter()
+            .zip(pattern)
+            .all(|(&base, &code)| iupac_bases(code).contains(&base))
+    })
+}
+
+impl DumpFilter {
+    pub fn keeps(&self, record: &Record, count: u32) -> bool {
+        if count < self.min_count || count > self.max_count {
+            return false;
+        }
+        let bases = record.sequence();
+        let gc = gc_content(bases.as_bytes());
+        if gc < self.min_gc || gc > self.max_gc {
+            return false;
+        }
+        match &self.pattern {
+            Some(pattern) => record
+                .tuple_id()
+                .windows()
+                .iter()
+                .any(|(_, window)| matches_at(window, pattern)),
+            None => true,
+        }
+    }
+}
+
+/// Which of the records passing the filter are written.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sampling {
+    All,
+    /// The first record and every `n`th after it.
+    Every(usize),
+    /// `k` records chosen uniformly at random, written in input order.
+    Random {
+        k: usize,
+        seed: u64,
+    },
+}
+
+/// Keeps the records chosen by a `Sampling` while they stream past.
+pub struct Sampler {
+    sampling: Sampling,
+    seen: usize,
+    rng: StdRng,
+    reservoir: Vec<(usize, Record, u32)>,
+}
+
+impl Sampler {
+    pub fn new(sampling: Sampling) -> Sampler {
+        let seed = match sampling {
+            Sampling::Random { seed, .. } => seed,
+            _ => 0,
+        };
+        Sampler {
+            sampling,
+            seen: 0,
+            rng: StdRng::seed_from_u64(seed),
+            reservoir: Vec::new(),
+        }
+    }
+
+    /// Whether to write `record` now; random sampling holds it back until `finish`.
+    pub fn offer(&mut self, record: Record, count: u32) -> bool {
+        let index = self.seen;
+        self.seen += 1;
+        match self.sampling {
+            Sampling::All => true,
+            Sampling::Every(n) => index.is_multiple_of(n),
+            Sampling::Random { k, .. } => {
+                if self.reservoir.len() < k {
+                    self.reservoir.push((index, record, count));
+                } else {
+                    let j = self.rng.gen_range(0..=index);
+                    if j < k {
+                        self.reservoir[j] = (index, record, count);
+                    }
+                }
+                false
+            }
+        }
+    }
+
+    /// Records held back by random sampling, in input order.
+    pub fn finish(mut self) -> Vec<(Record, u32)> {
+        self.reservoir.sort_by_key(|&(index, _, _)| index);
+        self.reservoir
+            .into_iter()
+            .map(|(_, record, count)| (record, count))
+            .collect()
+    }
+}
+
+fn window_names(kind: RecordKind) -> &'static [char] {
+    match kind {
+        RecordKind::Lr => &['L', 'R'],
+        RecordKind::Probe => &['P'],
+        RecordKind::Lmr => &['L', 'M', 'R'],
+    }
+}
+
+pub fn tsv_header(kind: RecordKind) -> String {
+    let names = window_names(kind);
+    let mut columns: Vec<String> = vec!["id".to_string()];
+    columns.extend(names.iter().map(|n| n.to_string()));
+    columns.push("count".to_string());
+    columns.extend(names.iter().map(|n| format!("gc_{}", n)));
+    columns.extend(names.iter().map(|n| format!("tm_{}", n)));
+    columns.join("\t")
+}
+
+/// `record` in `format`, with a trailing newline.
+pub fn format_record(
+    record: &Record,
+    count: u32,
+    format: DumpFormat,
+    rules: &ProbeRules,
+) -> String {
+    let id = record.name();
+    let windows = record.tuple_id().windows();
+    let bases = |w: &Vec<u8>| String::from_utf8_lossy(w).to_string();
+    match format {
+        DumpFormat::Text => format!("{}\n", record.sequence()),
+        DumpFormat::Fasta => format!(">{}\n{}\n", id, record.sequence()),
+        DumpFormat::FastaWindows => record.blast_query(),
+        DumpFormat::Tsv => {
+            let mut columns: Vec<String> = vec![id];
+            columns.extend(windows.iter().map(|(_, w)| bases(w)));
+            columns.push(count.to_string());
+            columns.extend(windows.iter().map(|(_, w)| format!("{:.3}", gc_content(w))));
+            columns.extend(windows.iter().map(|(_, w)| format!("{:.1}", rules.tm(w))));
+            format!("{}\n", columns.join("\t"))
+        }
+        DumpFormat::Jsonl => {
+            let object = |value: &dyn Fn(&Vec<u8>) -> String| -> String {
+                let fields: Vec<String> = windows
+                    .iter()
+                    .map(|(name, w)| format!("\"{}\":{}", name, value(w)))
+                    .collect();
+                format!("{{{}}}", fields.join(","))
+            };
+            let number = |v: f64| -> String {
+                if v.is_finite() {
+                    format!("{:.3}", v)
+                } else {
+                    "null".to_string()
+                }
+            };
+            format!(
+                "{{\"id\":\"{}\",\"windows\":{},\"count\":{},\"gc\":{},\"tm\":{}}}\n",
+                id,
+                object(&|w| format!("\"{}\"", bases(w))),
+                count,
+                object(&|w| number(gc_content(w))),
+                object(&|w| number(rules.tm(w)))
+            )
+        }
+    }
+}
+
+pub fn options() -> Options {
+    let mut opts = to_fasta_options();
+    opts.optopt(
+        "f",
+        "format",
+        "text (bases of each record, default), tsv (id, windows, count, GC and Tm of each window), fasta, fasta_windows (one entry per window, named as by blast) or jsonl.",
+        "FORMAT",
+    );
+    opts
+}
+
+/// Options of `to-fasta`: those of `dump` without `-f`.
+pub fn to_fasta_options() -> Options {
+    let mut opts = Options::new();
+    opts.optmulti(
+        "i",
+        "input",
+        "binary record file. can be given more than once; read in order.",
+        "FILE",
+    );
+    opts.optopt("o", "output", "output file.", "OUTPUT");
+    opts.optflag(
+        "c",
+        "counts",
+        "every record of the inputs is followed by a 4 byte count; otherwise each counts 1.",
+    );
+    opts.optopt("", "min_count", "minimum count.", "N");
+    opts.optopt("", "max_count", "maximum count.", "N");
+    opts.optopt(
+        "",
+        "min_gc",
+        "minimum GC fraction of the whole record.",
+        "GC",
+    );
+    opts.optopt(
+        "",
+        "max_gc",
+        "maximum GC fraction of the whole record.",
+        "GC",
+    );
+    opts.optopt(
+        "",
+        "pattern",
+        "IUPAC pattern that must occur in one of the windows.",
+        "PATTERN",
+    );
+    opts.optopt(
+        "",
+        "every",
+        "write the first record and every Nth after it.",
+        "N",
+    );
+    opts.optopt(
+        "",
+        "sample",
+        "write K records chosen at random, in input order.",
+        "K",
+    );
+    opts.optopt("", "seed", "seed for --sample. default value is 1.", "SEED");
+    kind_option(&mut opts);
+    opts
+}
+
+pub fn run(matches: &Matches) -> Result<(), CliError> {
+    let format = DumpFormat::parse(&matches.opt_str("f").unwrap_or_else(|| "text".to_string()))?;
+    dump(matches, format)
+}
+
+/// `to-fasta`: `dump -f fasta`.
+pub fn run_to_fasta(matches: &Matches) -> Result<(), CliError> {
+    dump(matches, DumpFormat::Fasta)
+}
+
+fn dump(matches: &Matches, format: DumpFormat) -> Result<(), CliError> {
+    let inputs = required_multi(matches, "i")?;
+    let output_file = required(matches, "o")?;
+    let kind = record_kind(matches)?;
+    let defaults = DumpFilter::default();
+    let filter = DumpFilter {
+        min_count: parse_opt(matches, "min_count", defaults.min_count)?,
+        max_count: parse_opt(matches, "max_count", defaults.max_count)?,
+        min_gc: parse_opt(matches, "min_gc", defaults.min_gc)?,
+        max_gc: parse_opt(matches, "max_gc", defaults.max_gc)?,
+        pattern: matches
+            .opt_str("pattern")
+            .map(|p| p.to_ascii_uppercase().into_bytes()),
+    };
+    if let Some(pattern) = &filter.pattern {
+        if pattern.is_empty() || pattern.iter().any(|&b| iupac_bases(b).is_empty()) {
+            return Err(CliError::Usage(format!(
+                "invalid IUPAC pattern {:?}",
+                String::from_utf8_lossy(pattern)
+            )));
+        }
+    }
+    let sampling = match (matches.opt_str("every"), matches.opt_str("sample")) {
+        (Some(_), Some(_)) => {
+            return Err(CliError::Usage(
+                "--every and --sample cannot be used together".to_string(),
+            ))
+        }
+        (Some(_), None) => match parse_opt(matches, "every", 1usize)? {
+            0 => return Err(CliError::Usage("--every must be at least 1".to_string())),
+            n => Sampling::Every(n),
+        },
+        (None, Some(_)) => Sampling::Random {
+            k: parse_opt(matches, "sample", 0usize)?,
+            seed: parse_opt(matches, "seed", 1u64)?,
+        },
+        (None, None) => Sampling::All,
+    };
+    let rules = ProbeRules::default();
+
+    let mut w = BufWriter::new(create(&output_file)?);
+    if format == DumpFormat::Tsv {
+        writeln!(w, "{}", tsv_header(kind))?;
+    }
+    let mut sampler = Sampler::new(sampling);
+    let (mut read, mut written): (usize, usize) = (0, 0);
+    for source in counted_sources(&inputs, kind, matches.opt_present("c"))? {
+        for next in source {
+            let (record, count) = next?;
+            read += 1;
+            if filter.keeps(&record, count) && sampler.offer(record, count) {
+                w.write_all(format_record(&record, count, format, &rules).as_bytes())?;
+                written += 1;
+            }
+        }
+    }
+    for (record, count) in sampler.finish() {
+        w.write_all(format_record(&record, count, format, &rules).as_bytes())?;
+        written += 1;
+    }
+    w.flush()?;
+    eprintln!(
+        "{} of {} records written to {:?}",
+        written, read, output_file
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::dump::{
+        format_record, tsv_header, DumpFilter, DumpFormat, Sampler, Sampling,
+    };
+    use crate::commands::{args, dispatch};
+    use crate::records::{Record, RecordKind};
+    use ::function_name::named;
+    use search_primer::probe_rules::ProbeRules;
+    use std::fs;
+
+    #[test]
+    #[named]
+    fn dump_filter_test() {
+        // Lは全部A、Rは末尾がCGT
+        let lr = Record::Lr(0x1b);
+        let filter = DumpFilter {
+            min_count: 2,
+            pattern: Some(b"ASGT".to_vec()),
+            ..DumpFilter::default()
+        };
+        assert!(
+            filter.keeps(&lr, 2)
+                && !filter.keeps(&lr, 1)
+                && !DumpFilter {
+                    pattern: Some(b"TTT".to_vec()),
+                    ..DumpFilter::default()
+                }
+                .keeps(&lr, 1)
+                && !DumpFilter {
+                    min_gc: 0.1,
+                    ..DumpFilter::default()
+                }
+                .keeps(&lr, 1),
+            "{} failed",
+            function_name!()
+        );
+
+        let rules = ProbeRules::default();
+        let tsv = format_record(&lr, 3, DumpFormat::Tsv, &rules);
+        let json = format_record(&lr, 3, DumpFormat::Jsonl, &rules);
+        let columns: Vec<&str> = tsv.trim_end().split('\t').collect();
+        assert!(
+            tsv_header(RecordKind::Lr) == "id\tL\tR\tcount\tgc_L\tgc_R\ttm_L\ttm_R"
+                && columns.len() == 8
+                && columns[0] == lr.name()
+                && columns[2] == format!("{}CGT", "A".repeat(29))
+                && columns[3] == "3"
+                && columns[4] == "0.000"
+                && json.starts_with(&format!(
+                    "{{\"id\":\"{}\",\"windows\":{{\"L\":\"{}\",\"R\":",
+                    lr.name(),
+                    "A".repeat(32)
+                ))
+                && json.contains("\"count\":3,\"gc\":{\"L\":0.000,\"R\":0.062}"),
+            "{} failed: {} {}",
+            function_name!(),
+            tsv,
+            json
+        );
+    }
+
+    #[test]
+    #[named]
+    fn sampler_test() {
+        let offered = |sampling: Sampling| -> (Vec<u128>, Vec<u128>) {
+            let mut sampler = Sampler::new(sampling);
+            let now: Vec<u128> = (0..100u128)
+                .filter(|&v| sampler.offer(Record::Lr(v), 1))
+                .collect();
+            let later: Vec<u128> = sampler
+                .finish()
+                .iter()
+                .map(|(r, _)| match r {
+                    Record::Lr(v) => *v,
+                    _ => 0,
+                })
+                .collect();
+            (now, later)
+        };
+        let (every, none) = offered(Sampling::Every(30));
+        let (empty, sampled) = offered(Sampling::Random { k: 5, seed: 7 });
+        let (_, again) = offered(Sampling::Random { k: 5, seed: 7 });
+        assert!(
+            every == vec![0, 30, 60, 90]
+                && none.is_empty()
+                && empty.is_empty()
+                && sampled.len() == 5
+                && sampled.windows(2).all(|p| p[0] < p[1])
+                && sampled == again,
+            "{} failed: {:?} {:?}",
+            function_name!(),
+            every,
+            sampled
+        );
+    }
+
+    #[test]
+    #[named]
+    fn to_fasta_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        let records = [Record::Lr(0x1b), Record::Lr(0x2c)];
+        let bytes: Vec<u8> = records.iter().flat_map(|r| r.to_bytes()).collect();
+        fs::write(path("in.bin"), bytes).unwrap();
+        let run = |words: &[&str]| -> (i32, String) {
+            let mut words: Vec<&str> = words.to_vec();
+            let output = path(&format!("{}.out", words[0]));
+            let input = path("in.bin");
+            words.extend(["-i", input.as_str(), "-o", output.as_str()]);
+            let code = dispatch(&args(&words));
+            (code, fs::read_to_string(&output).unwrap_or_default())
+        };
+        let (dump_code, dumped) = run(&["dump", "-f", "fasta"]);
+        let (code, written) = run(&["to-fasta"]);
+        let expected: String = records
+            .iter()
+            .map(|r| format!(">{}\n{}\n", r.name(), r.sequence()))
+            .collect();
+        assert!(
+            dump_code == 0 && code == 0 && written == dumped && written == expected,
+            "{} failed: {} {} {:?}",
+            function_name!(),
+            dump_code,
+            code,
+            written
+        );
+        // to-fastaの書式は変えられない
+        let (code, _) = run(&["to-fasta", "-f", "text"]);
+        assert!(code == 2, "{} failed: {}", function_name!(), code);
+    }
+}
@@ -0,0 +1,214 @@
+use crate::cli::{create, for_each_fasta_record, parse_opt, required, threads, CliError};
+use getopts::{Matches, Options};
+use search_primer::amplicon_util::{
+    format_amplicon, summarize_amplicons, summary_tsv_header, summary_tsv_line, AmpliconFormat,
+};
+use search_primer::in_silico_pcr::{run_in_silico_pcr, PcrParams, PrimerPair};
+use search_primer::primer_set::{expand_primer_pair, load_primer_set, PrimerSetOptions, TrimRule};
+use std::io::{BufWriter, Write};
+
+pub fn options() -> Options {
+    let mut opts = Options::new();
+    opts.optopt("i", "input", "reads (FASTA).", "READS");
+    opts.optopt(
+        "p",
+        "primer",
+        "primer pairs (TSV, CSV or FASTA).",
+        "PRIMERS",
+    );
+    opts.optopt("o", "output", "output file.", "OUTPUT");
+    opts.optopt(
+        "t",
+        "thread",
+        "number of threads to use. default value is 8.",
+        "THREAD",
+    );
+    opts.optopt(
+        "l",
+        "length",
+        "maximum product size. default value is 200.",
+        "LENGTH",
+    );
+    opts.optopt("f", "format", "fasta (default), bed or gff3.", "FORMAT");
+    opts.optopt(
+        "s",
+        "summary",
+        "write per-primer-pair hit counts and product length distribution (TSV).",
+        "SUMMARY",
+    );
+    opts.optopt(
+        "k",
+        "mismatch",
+        "maximum number of mismatches in each primer binding site. default value is 0.",
+        "K",
+    );
+    opts.optopt(
+        "",
+        "trim_left",
+        "trimming rule for left primers: 3p:N keeps N bases at the 3' end, 5p:N removes N bases from the 5' end.",
+        "RULE",
+    );
+    opts.optopt("", "trim_right", "trimming rule for right primers.", "RULE");
+    opts
+}
+
+pub fn run(matches: &Matches) -> Result<(), CliError> {
+    let read_file = required(matches, "i")?;
+    let primer_file = required(matches, "p")?;
+    let output_file = required(matches, "o")?;
+    let threads = threads(matches, 8)?;
+    let max_product_size: usize = parse_opt(matches, "l", 200)?;
+    let max_mismatches: u32 = parse_opt(matches, "k", 0)?;
+    let format =
+        AmpliconFormat::parse(&matches.opt_str("f").unwrap_or_else(|| "fasta".to_string()))
+            .map_err(CliError::Usage)?;
+    let primer_set_options = PrimerSetOptions {
+        trim_left: TrimRule::parse(&matches.opt_str("trim_left").unwrap_or_default())
+            .map_err(CliError::Usage)?,
+        trim_right: TrimRule::parse(&matches.opt_str("trim_right").unwrap_or_default())
+            .map_err(CliError::Usage)?,
+        ..PrimerSetOptions::default()
+    };
+
+    let primer_pairs: Vec<PrimerPair> = load_primer_set(&primer_file, &primer_set_options)?;
+    let mut search_pairs: Vec<PrimerPair> = Vec::new();
+    for pair in &primer_pairs {
+        search_pairs.extend(expand_primer_pair(pair)?);
+    }
+    eprintln!(
+        "Number of primer pairs: {} ({} after expanding degenerate bases)",
+        primer_pairs.len(),
+        search_pairs.len()
+    );
+    let params = PcrParams {
+        max_mismatches,
+        max_weighted_mismatches: max_mismatches as f64,
+        max_product_size,
+        ..PcrParams::default()
+    };
+
+    let mut reads: Vec<(String, Vec<u8>)> = Vec::new();
+    for_each_fasta_record(&read_file, |id, seq| {
+        reads.push((id.to_string(), seq.to_ascii_uppercase()))
+    })?;
+    eprintln!("loading {:?} done: {} reads", read_file, reads.len());
+    let amplicons = run_in_silico_pcr(&reads, &search_pairs, &params, threads);
+
+    let mut w = BufWriter::new(create(&output_file)?);
+    if let Some(header) = format.header() {
+        w.write_all(header.as_bytes())?;
+    }
+    for amplicon in &amplicons {
+        w.write_all(format_amplicon(amplicon, format).as_bytes())?;
+    }
+    w.flush()?;
+    eprintln!("{} amplicons written to {:?}", amplicons.len(), output_file);
+
+    if let Some(summary_file) = matches.opt_str("s") {
+        let summary = summarize_amplicons(&amplicons);
+        let mut w = BufWriter::new(create(&summary_file)?);
+        w.write_all(summary_tsv_header().as_bytes())?;
+        // ヒットしなかったプライマーも0件として出力する
+        for pair in &primer_pairs {
+            let line = match summary.get(&pair.id) {
+                Some(s) => summary_tsv_line(&pair.id, s),
+                None => summary_tsv_line(&pair.id, &Default::default()),
+            };
+            w.write_all(line.as_bytes())?;
+        }
+        w.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{args, dispatch};
+    use ::function_name::named;
+    use search_primer::in_silico_pcr::reverse_complement;
+    use std::fs;
+
+    const TARGET: &[u8] =
+        b"ACGTTGCAAGGCTTACCGTAGGATCCTTAGCAGTCAAGTCTTGACCATGCGATACGGTCAGTACCTGAAGCTTCGAT";
+
+    #[test]
+    #[named]
+    fn extract_amplicon_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        let left = String::from_utf8(TARGET[..20].to_vec()).unwrap();
+        let right = String::from_utf8(reverse_complement(&TARGET[58..])).unwrap();
+        let target = String::from_utf8(TARGET.to_vec()).unwrap();
+        // r1は順鎖、r2は逆鎖に産物がある。r3には右のプライマーがない
+        fs::write(
+            path("reads.fa"),
+            format!(
+                ">r1\nGG{}CC\n>r2\n{}\n>r3\n{}\n",
+                target,
+                String::from_utf8(reverse_complement(TARGET)).unwrap(),
+                &target[..60]
+            ),
+        )
+        .unwrap();
+        fs::write(
+            path("primers.tsv"),
+            format!(
+                "p1\t{}\t{}\np2\tACGTACGTACGTACGTACGT\tTTTTTTTTTTTTTTTTTTTT\n",
+                left, right
+            ),
+        )
+        .unwrap();
+        let code = dispatch(&args(&[
+            "extract",
+            "-i",
+            &path("reads.fa"),
+            "-p",
+            &path("primers.tsv"),
+            "-o",
+            &path("amplicons.fa"),
+            "-s",
+            &path("summary.tsv"),
+            "-t",
+            "2",
+        ]));
+        let amplicons = fs::read_to_string(path("amplicons.fa")).unwrap_or_default();
+        let summary = fs::read_to_string(path("summary.tsv")).unwrap_or_default();
+        let n = TARGET.len();
+        let expected_amplicons = format!(
+            ">p1L-R_{} r1:3-{}(+)\n{}\n>p1R-L_{} r2:1-{}(-)\n{}\n",
+            n,
+            n + 2,
+            target,
+            n,
+            n,
+            String::from_utf8(reverse_complement(TARGET)).unwrap()
+        );
+        // ヒットのないp2も0件として出る
+        let summary_lines: Vec<&str> = summary.lines().collect();
+        assert!(
+            code == 0
+                && amplicons == expected_amplicons
+                && summary_lines.len() == 3
+                && summary_lines[1] == format!("p1\t0\t1\t1\t0\t2\t{}\t{}\t{}\t{}:2", n, n, n, n)
+                && summary_lines[2].starts_with("p2\t0\t0\t0\t0\t0\t"),
+            "{} failed: {} {:?} {:?}",
+            function_name!(),
+            code,
+            amplicons,
+            summary
+        );
+
+        let bad_trim = dispatch(&args(&[
+            "extract",
+            "-i",
+            &path("reads.fa"),
+            "-p",
+            &path("primers.tsv"),
+            "-o",
+            &path("trimmed.fa"),
+            "--trim_left",
+            "3p:0",
+        ]));
+        assert!(bad_trim == 2, "{} failed: {}", function_name!(), bad_trim);
+    }
+}
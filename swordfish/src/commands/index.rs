@@ -0,0 +1,62 @@
+use crate::cli::{create, parse_opt, required, CliError};
+use crate::index::SparseIndex;
+use crate::records::{kind_option, record_kind, RecordReader};
+use getopts::{Matches, Options};
+use std::io::{BufWriter, Write};
+
+pub fn options() -> Options {
+    let mut opts = Options::new();
+    opts.optopt("i", "input", "sorted binary record file.", "FILE");
+    opts.optopt(
+        "o",
+        "output",
+        "index file. default value is FILE.idx.",
+        "OUTPUT",
+    );
+    opts.optopt(
+        "n",
+        "interval",
+        "records per block; one key of every block is kept. default value is 1024.",
+        "N",
+    );
+    opts.optflag(
+        "c",
+        "counts",
+        "every record of the input is followed by a 4 byte count.",
+    );
+    kind_option(&mut opts);
+    opts
+}
+
+pub fn run(matches: &Matches) -> Result<(), CliError> {
+    let input_file = required(matches, "i")?;
+    let output_file = matches
+        .opt_str("o")
+        .unwrap_or_else(|| format!("{}.idx", input_file));
+    let interval: u64 = parse_opt(matches, "n", 1024)?;
+    if interval == 0 {
+        return Err(CliError::Usage("--interval must be at least 1".to_string()));
+    }
+    let kind = record_kind(matches)?;
+    let counted = matches.opt_present("c");
+
+    let file = crate::cli::open(&input_file)?;
+    let reader = if counted {
+        RecordReader::with_counts(file, kind)
+    } else {
+        RecordReader::new(file, kind)
+    };
+    let index = SparseIndex::build(reader, kind, counted, interval)
+        .map_err(|e| CliError::Failure(format!("{}: {}", input_file, e)))?;
+    let mut w = BufWriter::new(create(&output_file)?);
+    index.write(&mut w)?;
+    w.flush()?;
+    eprintln!(
+        "{} records of {:?} indexed with {} keys: {}",
+        index.records,
+        input_file,
+        index.fences.len(),
+        output_file
+    );
+    Ok(())
+}
@@ -0,0 +1,290 @@
+// ソート済みのバイナリをk-way mergeする。各入力から1件ずつ読むだけなので、メモリは入力ファイルの数に比例するだけで済む。
+// 入力がソートされていなければエラーにする。同じキーのカウントは足し合わせる(カウントのないファイルは1件を1と数える)。
+// union: どれかの入力にあるキー、intersection: すべての入力にあるキー、difference: 1つ目の入力にだけあるキー、
+// symmetric_difference: ちょうど1つの入力にあるキー。
+
+use crate::cli::{create, required, required_multi, CliError};
+use crate::records::{kind_option, record_kind, Record, RecordKind, RecordReader};
+use getopts::{Matches, Options};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{BufWriter, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SetOperation {
+    Union,
+    Intersection,
+    Difference,
+    SymmetricDifference,
+}
+
+impl SetOperation {
+    pub fn parse(name: &str) -> Result<SetOperation, CliError> {
+        match name {
+            "union" => Ok(SetOperation::Union),
+            "intersection" => Ok(SetOperation::Intersection),
+            "difference" => Ok(SetOperation::Difference),
+            "symmetric_difference" => Ok(SetOperation::SymmetricDifference),
+            _ => Err(CliError::Usage(format!(
+                "unknown set operation {:?} (union, intersection, difference or symmetric_difference)",
+                name
+            ))),
+        }
+    }
+
+    /// Whether a key found in the inputs flagged by `present` is kept.
+    pub fn keeps(&self, present: &[bool]) -> bool {
+        match self {
+            SetOperation::Union => true,
+            SetOperation::Intersection => present.iter().all(|&p| p),
+            SetOperation::Difference => present[0] && present[1..].iter().all(|&p| !p),
+            SetOperation::SymmetricDifference => present.iter().filter(|&&p| p).count() == 1,
+        }
+    }
+}
+
+/// A record and its count, or the error reading it.
+pub type CountedRecord = Result<(Record, u32), CliError>;
+
+/// Records read from each input and records written by `merge_sorted`.
+#[derive(Debug, Default, PartialEq)]
+pub struct MergeStats {
+    pub input_records: Vec<usize>,
+    pub output_records: usize,
+}
+
+pub fn options() -> Options {
+    let mut opts = Options::new();
+    opts.optmulti(
+        "i",
+        "input",
+        "sorted binary record file. can be given more than once.",
+        "FILE",
+    );
+    opts.optopt("o", "output", "merged binary record file.", "OUTPUT");
+    opts.optopt(
+        "s",
+        "set",
+        "union (default), intersection, difference (keys of the first input absent from the others) or symmetric_difference (keys in exactly one input).",
+        "OPERATION",
+    );
+    opts.optflag(
+        "c",
+        "counts",
+        "every record of the inputs and the output is followed by a 4 byte count.",
+    );
+    opts.optflag(
+        "",
+        "write_counts",
+        "write a count after every output record even if the inputs have none.",
+    );
+    kind_option(&mut opts);
+    opts
+}
+
+/// Merges sorted `inputs` and calls `emit` with every kept key and the sum of its counts, in order.
+/// Counts saturate at `u32::MAX`.
+pub fn merge_sorted<I, F>(
+    inputs: Vec<I>,
+    operation: SetOperation,
+    mut emit: F,
+) -> Result<MergeStats, CliError>
+where
+    I: Iterator<Item = CountedRecord>,
+    F: FnMut(Record, u32) -> Result<(), CliError>,
+{
+    let mut output_records: usize = 0;
+    let mut stats = merge_sorted_keys(inputs, |key, total, present| {
+        if operation.keeps(present) {
+            emit(key, total)?;
+            output_records += 1;
+        }
+        Ok(())
+    })?;
+    stats.output_records = output_records;
+    Ok(stats)
+}
+
+/// Calls `emit` with every key of the sorted `inputs` in order, the sum of its counts
+/// and which inputs have it. `output_records` of the result is left at 0.
+pub fn merge_sorted_keys<I, F>(mut inputs: Vec<I>, mut emit: F) -> Result<MergeStats, CliError>
+where
+    I: Iterator<Item = CountedRecord>,
+    F: FnMut(Record, u32, &[bool]) -> Result<(), CliError>,
+{
+    let mut stats = MergeStats {
+        input_records: vec![0; inputs.len()],
+        output_records: 0,
+    };
+    let mut heap: BinaryHeap<Reverse<(Record, usize, u32)>> = BinaryHeap::new();
+    let mut last: Vec<Option<Record>> = vec![None; inputs.len()];
+    // 各入力の次の1件をheapに積む。直前のキーより小さければソートされていない
+    let mut advance = |i: usize,
+                       input: &mut I,
+                       heap: &mut BinaryHeap<Reverse<(Record, usize, u32)>>,
+                       stats: &mut MergeStats|
+     -> Result<(), CliError> {
+        if let Some(next) = input.next() {
+            let (record, count) = next?;
+            if last[i].is_some_and(|previous| previous > record) {
+                return Err(CliError::Failure(format!(
+                    "input {} is not sorted at record {}; inputs must be sorted as written by swordfish count",
+                    i + 1,
+                    stats.input_records[i] + 1
+                )));
+            }
+            last[i] = Some(record);
+            stats.input_records[i] += 1;
+            heap.push(Reverse((record, i, count)));
+        }
+        Ok(())
+    };
+    for (i, input) in inputs.iter_mut().enumerate() {
+        advance(i, input, &mut heap, &mut stats)?;
+    }
+    while let Some(Reverse((key, i, count))) = heap.pop() {
+        let mut total: u32 = count;
+        let mut present: Vec<bool> = vec![false; inputs.len()];
+        present[i] = true;
+        advance(i, &mut inputs[i], &mut heap, &mut stats)?;
+        while let Some(Reverse((next, j, next_count))) = heap.peek().copied() {
+            if next != key {
+                break;
+            }
+            heap.pop();
+            total = total.saturating_add(next_count);
+            present[j] = true;
+            advance(j, &mut inputs[j], &mut heap, &mut stats)?;
+        }
+        emit(key, total, &present)?;
+    }
+    Ok(stats)
+}
+
+/// Opens every input as a stream of records and their counts, whose errors name the file.
+pub fn counted_sources(
+    inputs: &[String],
+    kind: RecordKind,
+    counted: bool,
+) -> Result<Vec<impl Iterator<Item = CountedRecord> + '_>, CliError> {
+    let mut sources = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let file = crate::cli::open(input)?;
+        let mut reader = if counted {
+            RecordReader::with_counts(file, kind)
+        } else {
+            RecordReader::new(file, kind)
+        };
+        sources.push(
+            std::iter::from_fn(move || reader.next_counted())
+                .map(move |r| r.map_err(|e| CliError::Failure(format!("{}: {}", input, e)))),
+        );
+    }
+    Ok(sources)
+}
+
+pub fn run(matches: &Matches) -> Result<(), CliError> {
+    let inputs = required_multi(matches, "i")?;
+    let output_file = required(matches, "o")?;
+    let kind = record_kind(matches)?;
+    let operation =
+        SetOperation::parse(&matches.opt_str("s").unwrap_or_else(|| "union".to_string()))?;
+    let counted = matches.opt_present("c");
+    let write_counts = counted || matches.opt_present("write_counts");
+
+    let sources = counted_sources(&inputs, kind, counted)?;
+    let mut w = BufWriter::new(create(&output_file)?);
+    let stats = merge_sorted(sources, operation, |record, count| {
+        w.write_all(&record.to_bytes())?;
+        if write_counts {
+            w.write_all(&count.to_be_bytes())?;
+        }
+        Ok(())
+    })?;
+    w.flush()?;
+
+    let total: usize = stats.input_records.iter().sum();
+    eprintln!("| Input File | Records |");
+    eprintln!("|------------|--------:|");
+    for (input, count) in inputs.iter().zip(&stats.input_records) {
+        eprintln!("| {} | {} |", input, count);
+    }
+    eprintln!("\n| Output File | Records | Records not written |");
+    eprintln!("|-------------|--------:|-------------------:|");
+    eprintln!(
+        "| {} | {} | {} |",
+        output_file,
+        stats.output_records,
+        total - stats.output_records
+    );
+    eprintln!(
+        "{}\t{}\t{}",
+        total,
+        stats.output_records,
+        total - stats.output_records
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::CliError;
+    use crate::commands::merge::{merge_sorted, SetOperation};
+    use crate::records::Record;
+    use ::function_name::named;
+
+    fn merged(
+        inputs: &[Vec<(u128, u32)>],
+        operation: SetOperation,
+    ) -> Result<Vec<(u128, u32)>, CliError> {
+        let sources: Vec<_> = inputs
+            .iter()
+            .map(|input| input.iter().map(|&(v, c)| Ok((Record::Lr(v), c))))
+            .collect();
+        let mut out: Vec<(u128, u32)> = Vec::new();
+        merge_sorted(sources, operation, |record, count| {
+            if let Record::Lr(v) = record {
+                out.push((v, count));
+            }
+            Ok(())
+        })?;
+        Ok(out)
+    }
+
+    #[test]
+    #[named]
+    fn merge_sorted_test() {
+        let inputs = vec![
+            vec![(1, 1), (3, 2), (5, 1), (5, 1)],
+            vec![(2, 4), (3, 1), (5, 3)],
+            vec![(3, 1), (4, 1), (5, u32::MAX)],
+        ];
+        let union = merged(&inputs, SetOperation::Union).unwrap();
+        assert!(
+            union == vec![(1, 1), (2, 4), (3, 4), (4, 1), (5, u32::MAX)],
+            "{} failed: {:?}",
+            function_name!(),
+            union
+        );
+        let intersection = merged(&inputs, SetOperation::Intersection).unwrap();
+        let difference = merged(&inputs, SetOperation::Difference).unwrap();
+        let symmetric = merged(&inputs, SetOperation::SymmetricDifference).unwrap();
+        assert!(
+            intersection == vec![(3, 4), (5, u32::MAX)]
+                && difference == vec![(1, 1)]
+                && symmetric == vec![(1, 1), (2, 4), (4, 1)],
+            "{} failed: {:?} {:?} {:?}",
+            function_name!(),
+            intersection,
+            difference,
+            symmetric
+        );
+        let unsorted = merged(&[vec![(1, 1)], vec![(3, 1), (2, 1)]], SetOperation::Union);
+        assert!(
+            unsorted.is_err(),
+            "{} failed: {:?}",
+            function_name!(),
+            unsorted
+        );
+    }
+}
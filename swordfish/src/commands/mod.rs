@@ -0,0 +1,274 @@
+// swordfishのサブコマンドの一覧と振り分け。
+// サブコマンドはそれぞれoptions()でオプションを宣言し、run()で実行する。
+// -h/--helpと余分な引数の扱いはここでまとめて行う。
+
+pub mod annotate;
+pub mod blast;
+pub mod count;
+pub mod dump;
+pub mod extract;
+pub mod index;
+pub mod merge;
+pub mod primer3;
+pub mod probe;
+pub mod query;
+pub mod set;
+pub mod split;
+
+use crate::cli::CliError;
+use getopts::{Matches, Options};
+
+pub struct Command {
+    pub name: &'static str,
+    pub summary: &'static str,
+    /// Arguments shown after `swordfish NAME` in the usage line.
+    pub usage: &'static str,
+    pub options: fn() -> Options,
+    pub run: fn(&Matches) -> Result<(), CliError>,
+}
+
+impl Command {
+    pub fn help(&self, opts: &Options) -> String {
+        let brief = format!(
+            "Usage: swordfish {} {}\n{}",
+            self.name, self.usage, self.summary
+        );
+        opts.usage(&brief)
+    }
+}
+
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "count",
+        summary: "Counts lr-tuples occurring at least THRESHOLD times in reads or at THRESHOLD loci of an assembly.",
+        usage: "-i READS -o OUTPUT [options]",
+        options: count::options,
+        run: count::run,
+    },
+    Command {
+        name: "merge",
+        summary: "Merges sorted binary record files into one, summing counts, as a union, intersection or difference.",
+        usage: "-i FILE [-i FILE ...] -o OUTPUT [options]",
+        options: merge::options,
+        run: merge::run,
+    },
+    Command {
+        name: "set",
+        summary: "Set operations and Jaccard/containment statistics over sorted binary record files.",
+        usage: "-i FILE -i FILE [...] -s OPERATION -o OUTPUT [options]",
+        options: set::options,
+        run: set::run,
+    },
+    Command {
+        name: "split",
+        summary: "Splits a binary record file by key range or hash into PREFIX_001.bin, PREFIX_002.bin, ... with a manifest.",
+        usage: "-i FILE -o PREFIX [options]",
+        options: split::options,
+        run: split::run,
+    },
+    Command {
+        name: "dump",
+        summary: "Decodes records as text, TSV, FASTA or JSON lines, with count, GC and pattern filters and sampling.",
+        usage: "-i FILE [-i FILE ...] -o OUTPUT [options]",
+        options: dump::options,
+        run: dump::run,
+    },
+    Command {
+        name: "to-fasta",
+        summary: "Same as dump -f fasta: writes each record as one FASTA entry.",
+        usage: "-i FILE [-i FILE ...] -o OUTPUT [options]",
+        options: dump::to_fasta_options,
+        run: dump::run_to_fasta,
+    },
+    Command {
+        name: "index",
+        summary: "Builds a sparse index of a sorted binary record file for swordfish query.",
+        usage: "-i FILE [options]",
+        options: index::options,
+        run: index::run,
+    },
+    Command {
+        name: "query",
+        summary: "Looks up keys in an indexed binary record file and reports presence and count.",
+        usage: "-i FILE -q KEY [-q KEY ...] [options]",
+        options: query::options,
+        run: query::run,
+    },
+    Command {
+        name: "primer3",
+        summary: "Designs primers for every record with primer3_core.",
+        usage: "-i FILE -o OUTPUT [options]",
+        options: primer3::options,
+        run: primer3::run,
+    },
+    Command {
+        name: "blast",
+        summary: "Writes BLAST queries for every record and the list of their names.",
+        usage: "-i FILE -o FASTA -n NAMES [options]",
+        options: blast::options,
+        run: blast::run,
+    },
+    Command {
+        name: "annotate",
+        summary: "Appends the L/R (and M) sequences of every tuple id found in a TSV or FASTA file.",
+        usage: "-i FILE -o OUTPUT [options]",
+        options: annotate::options,
+        run: annotate::run,
+    },
+    Command {
+        name: "extract",
+        summary: "Extracts the regions amplified by primer pairs from reads (in silico PCR).",
+        usage: "-i READS -p PRIMERS -o OUTPUT [options]",
+        options: extract::options,
+        run: extract::run,
+    },
+    Command {
+        name: "probe",
+        summary: "Searches TaqMan probe candidates between primer pairs in reads.",
+        usage: "-i READS -p PRIMERS -o OUTPUT [options]",
+        options: probe::options,
+        run: probe::run,
+    },
+];
+
+pub fn find_command(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|c| c.name == name)
+}
+
+pub fn top_usage() -> String {
+    let mut usage = String::from(
+        "Usage: swordfish COMMAND [options]\n       swordfish help COMMAND\n\nCommands:\n",
+    );
+    for command in COMMANDS {
+        usage.push_str(&format!("    {:<10}{}\n", command.name, command.summary));
+    }
+    usage
+}
+
+/// Runs `swordfish` with `args` (without the program name) and returns the exit code.
+pub fn dispatch(args: &[String]) -> i32 {
+    let name: &str = match args.first() {
+        Some(name) => name,
+        None => {
+            eprint!("{}", top_usage());
+            return 2;
+        }
+    };
+    match name {
+        "-h" | "--help" => {
+            print!("{}", top_usage());
+            return 0;
+        }
+        "help" => {
+            return match args.get(1) {
+                None => {
+                    print!("{}", top_usage());
+                    0
+                }
+                Some(topic) => match find_command(topic) {
+                    Some(command) => {
+                        print!("{}", command.help(&with_help((command.options)())));
+                        0
+                    }
+                    None => {
+                        eprintln!("swordfish: unknown command {:?}", topic);
+                        eprint!("{}", top_usage());
+                        2
+                    }
+                },
+            };
+        }
+        _ => {}
+    }
+    let command = match find_command(name) {
+        Some(command) => command,
+        None => {
+            eprintln!("swordfish: unknown command {:?}", name);
+            eprint!("{}", top_usage());
+            return 2;
+        }
+    };
+    let opts = with_help((command.options)());
+    let result = match opts.parse(&args[1..]) {
+        Ok(m) if m.opt_present("h") => {
+            print!("{}", command.help(&opts));
+            return 0;
+        }
+        Ok(m) if !m.free.is_empty() => Err(CliError::Usage(format!(
+            "unexpected argument {:?}",
+            m.free[0]
+        ))),
+        Ok(m) => (command.run)(&m),
+        Err(f) => Err(CliError::Usage(f.to_string())),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("swordfish {}: {}", command.name, e);
+            if let CliError::Usage(_) = e {
+                eprintln!("Try 'swordfish help {}' for the options.", command.name);
+            }
+            e.exit_code()
+        }
+    }
+}
+
+fn with_help(mut opts: Options) -> Options {
+    opts.optflag("h", "help", "print this help menu");
+    opts
+}
+
+/// Command line words for `dispatch` in tests.
+#[cfg(test)]
+pub fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{args, dispatch, COMMANDS};
+    use ::function_name::named;
+
+    #[test]
+    #[named]
+    fn dispatch_test() {
+        // 未知のコマンド・オプション、必須オプションの欠落、不正な値、余分な引数はいずれも2
+        let codes: Vec<i32> = [
+            &[][..],
+            &["frobnicate"],
+            &["merge", "--frobnicate"],
+            &["merge", "-o", "out.bin"],
+            &["split", "-i", "in.bin", "-o", "out", "-n", "zero"],
+            &["split", "-i", "in.bin", "-o", "out", "-n", "0"],
+            &["dump", "-i", "in.bin", "-o", "out.txt", "-k", "xyz"],
+            &["dump", "-i", "in.bin", "-o", "out.txt", "stray"],
+        ]
+        .iter()
+        .map(|a| dispatch(&args(a)))
+        .collect();
+        assert!(
+            codes.iter().all(|&c| c == 2),
+            "{} failed: {:?}",
+            function_name!(),
+            codes
+        );
+        let missing = dispatch(&args(&[
+            "dump",
+            "-i",
+            "/nonexistent/in.bin",
+            "-o",
+            "/nonexistent/out.txt",
+        ]));
+        assert!(
+            missing == 1
+                && dispatch(&args(&["help"])) == 0
+                && dispatch(&args(&["merge", "-h"])) == 0
+                && COMMANDS
+                    .iter()
+                    .all(|c| dispatch(&args(&["help", c.name])) == 0),
+            "{} failed: {}",
+            function_name!(),
+            missing
+        );
+    }
+}
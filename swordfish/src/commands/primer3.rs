@@ -0,0 +1,237 @@
+use crate::cli::{create, open, required, threads, CliError};
+use crate::records::{kind_option, read_record_file, record_kind, RecordKind};
+use getopts::{Matches, Options};
+use std::io::{BufWriter, Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Instant;
+
+/// Records sent to one primer3_core process.
+const RECORDS_PER_CALL: usize = 100;
+
+pub fn options() -> Options {
+    let mut opts = Options::new();
+    opts.optopt("i", "input", "binary record file.", "FILE");
+    opts.optopt(
+        "o",
+        "output",
+        "primer3_core output, in the order of the input.",
+        "OUTPUT",
+    );
+    opts.optopt(
+        "c",
+        "config",
+        "primer3_core settings appended to every lr-tuple. required for lr, not accepted for probe and lmr, which use built-in settings.",
+        "CONFIG",
+    );
+    opts.optopt(
+        "t",
+        "thread",
+        "number of primer3_core processes run at once. default value is 4.",
+        "THREAD",
+    );
+    opts.optflag(
+        "p",
+        "print_input",
+        "write the primer3_core input to OUTPUT instead of running primer3_core",
+    );
+    kind_option(&mut opts);
+    opts
+}
+
+fn execute_primer3(input: &str) -> Result<String, CliError> {
+    let mut child = Command::new("primer3_core")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| CliError::Failure(format!("couldn't run primer3_core: {}", e)))?;
+    let mut stdin = child.stdin.take().unwrap();
+    // 出力を読みながら書き込まないと、パイプが詰まって止まる
+    let (written, output) = thread::scope(|scope| {
+        let writer = scope.spawn(move || stdin.write_all(input.as_bytes()));
+        let output = child.wait_with_output();
+        (writer.join().unwrap(), output)
+    });
+    let output = output?;
+    if !output.status.success() {
+        return Err(CliError::Failure(format!(
+            "primer3_core exited with {}",
+            output.status
+        )));
+    }
+    written.map_err(|e| CliError::Failure(format!("couldn't write to primer3_core: {}", e)))?;
+    String::from_utf8(output.stdout)
+        .map_err(|e| CliError::Failure(format!("primer3_core output: {}", e)))
+}
+
+pub fn run(matches: &Matches) -> Result<(), CliError> {
+    let input_file = required(matches, "i")?;
+    let output_file = required(matches, "o")?;
+    let kind = record_kind(matches)?;
+    let threads = threads(matches, 4)?;
+    let config: String = match (kind, matches.opt_str("c")) {
+        (RecordKind::Lr, Some(config_file)) => {
+            let mut config = String::new();
+            open(&config_file)?.read_to_string(&mut config)?;
+            config
+        }
+        (RecordKind::Lr, None) => {
+            return Err(CliError::Usage("--config is required for lr".to_string()))
+        }
+        (_, Some(_)) => {
+            return Err(CliError::Usage(format!(
+                "--config is not accepted for {}, which uses built-in settings",
+                kind.name()
+            )))
+        }
+        (_, None) => String::new(),
+    };
+
+    let records = read_record_file(&input_file, kind)?;
+    let inputs: Vec<String> = records
+        .chunks(RECORDS_PER_CALL)
+        .map(|chunk| chunk.iter().map(|r| r.primer3_input(&config)).collect())
+        .collect();
+    let mut w = BufWriter::new(create(&output_file)?);
+    if matches.opt_present("p") {
+        for input in &inputs {
+            w.write_all(input.as_bytes())?;
+        }
+        w.flush()?;
+        return Ok(());
+    }
+
+    // i番目のスレッドはi, i + threads, ...番目の塊を受け持ち、結果は入力の順に並べ直す
+    let mut results: Vec<Option<String>> = vec![None; inputs.len()];
+    thread::scope(|scope| -> Result<(), CliError> {
+        let mut children = Vec::new();
+        for i in 0..threads {
+            let inputs = &inputs;
+            children.push(
+                scope.spawn(move || -> Result<Vec<(usize, String)>, CliError> {
+                    let mut done: Vec<(usize, String)> = Vec::new();
+                    for idx in (i..inputs.len()).step_by(threads) {
+                        let start = Instant::now();
+                        done.push((idx, execute_primer3(&inputs[idx])?));
+                        let elapsed = start.elapsed();
+                        eprintln!(
+                            "primer3[{:02}]: call {}/{} took {}.{:03} sec",
+                            i,
+                            idx + 1,
+                            inputs.len(),
+                            elapsed.as_secs(),
+                            elapsed.subsec_millis()
+                        );
+                    }
+                    Ok(done)
+                }),
+            );
+        }
+        for child in children {
+            for (idx, result) in child.join().unwrap()? {
+                results[idx] = Some(result);
+            }
+        }
+        Ok(())
+    })?;
+    for result in results.into_iter().flatten() {
+        w.write_all(result.as_bytes())?;
+    }
+    w.flush()?;
+    eprintln!(
+        "{} records designed by primer3_core: {:?}",
+        records.len(),
+        output_file
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{args, dispatch};
+    use crate::records::Record;
+    use ::function_name::named;
+    use std::fs;
+
+    #[test]
+    #[named]
+    fn primer3_print_input_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        // 1塊(100件)を超える数にして、塊をまたいでも入力の順のままかを見る
+        let records: Vec<Record> = (0..250u128).map(|i| Record::Lr(i << 64 | 0x1b)).collect();
+        fs::write(
+            path("lr.bin"),
+            records
+                .iter()
+                .flat_map(|r| r.to_bytes())
+                .collect::<Vec<u8>>(),
+        )
+        .unwrap();
+        fs::write(path("config.txt"), "PRIMER_TASK=generic\n").unwrap();
+        let code = dispatch(&args(&[
+            "primer3",
+            "-i",
+            &path("lr.bin"),
+            "-o",
+            &path("input.txt"),
+            "-c",
+            &path("config.txt"),
+            "-p",
+        ]));
+        let output = fs::read_to_string(path("input.txt")).unwrap_or_default();
+        let blocks: Vec<&str> = output.split_terminator("=\n").collect();
+        let ids: Vec<String> = blocks
+            .iter()
+            .map(|b| b.lines().next().unwrap_or("").to_string())
+            .collect();
+        let expected_ids: Vec<String> = (0..250u128)
+            .map(|i| format!("SEQUENCE_ID=lr_32.32_{:032x}", i << 64 | 0x1b))
+            .collect();
+        // L(上位64 bit)が0なら全部A、Rは末尾がCGT
+        let first_template = format!(
+            "SEQUENCE_TEMPLATE={}{}{}CGT",
+            "A".repeat(32),
+            "N".repeat(50),
+            "A".repeat(29)
+        );
+        assert!(
+            code == 0
+                && ids == expected_ids
+                && blocks[0].lines().nth(1) == Some(first_template.as_str())
+                && blocks.iter().all(|b| b.ends_with("PRIMER_TASK=generic\n")),
+            "{} failed: {} {:?}",
+            function_name!(),
+            code,
+            blocks.first()
+        );
+
+        let probe = dispatch(&args(&[
+            "primer3",
+            "-i",
+            &path("lr.bin"),
+            "-o",
+            &path("probe.txt"),
+            "-k",
+            "probe",
+            "-c",
+            &path("config.txt"),
+            "-p",
+        ]));
+        let no_config = dispatch(&args(&[
+            "primer3",
+            "-i",
+            &path("lr.bin"),
+            "-o",
+            &path("none.txt"),
+            "-p",
+        ]));
+        assert!(
+            probe == 2 && no_config == 2,
+            "{} failed: {} {}",
+            function_name!(),
+            probe,
+            no_config
+        );
+    }
+}
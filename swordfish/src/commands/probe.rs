@@ -0,0 +1,404 @@
+use crate::cli::{create, for_each_fasta_record, parse_opt, required, threads, CliError};
+use getopts::{Matches, Options};
+use search_primer::in_silico_pcr::PrimerPair;
+use search_primer::primer_set::{expand_primer_pair, load_primer_set, PrimerSetOptions};
+use search_primer::probe_rules::ProbeRules;
+use search_primer::tuple_record::write_tuple_records;
+use search_probe::find_taqman_probe::{
+    build_counting_bloom_filter, collect_probe_candidates, merge_probe_candidates,
+    number_of_high_occurence_kmer, primer_tuples, probe_candidate_tsv_header,
+    probe_candidate_tsv_line, rank_probe_candidates_by_pair, ProbeCandidateKey,
+    BLOOMFILTER_TABLE_SIZE, PROBE_LEN,
+};
+use search_probe::sequence_encoder_util::{decode_u128_2_dna_seq, DnaSequence, ProbeKmer};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufWriter, Write};
+use std::thread;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ProbeFormat {
+    Candidates,
+    Bin,
+    Text,
+    Count,
+}
+
+pub fn options() -> Options {
+    let mut opts = Options::new();
+    opts.optopt("i", "input", "reads (FASTA).", "READS");
+    opts.optopt(
+        "p",
+        "primer",
+        "primer pairs (TSV, CSV or FASTA).",
+        "PRIMERS",
+    );
+    opts.optopt("o", "output", "output file.", "OUTPUT");
+    opts.optopt(
+        "t",
+        "thread",
+        "number of threads to use. default value is 8.",
+        "THREAD",
+    );
+    opts.optopt(
+        "a",
+        "threshold",
+        "threshold of probe occurence. default value is 8.",
+        "THRESHOLD",
+    );
+    opts.optopt(
+        "s",
+        "triming_size",
+        "primers are trimmed to this many bases at the 3' end. default value is 15.",
+        "TRIMSIZE",
+    );
+    opts.optopt(
+        "l",
+        "max_product_size",
+        "maximum product size. default value is unlimited.",
+        "LENGTH",
+    );
+    opts.optopt(
+        "n",
+        "top",
+        "number of probe candidates reported per primer pair. default value is all.",
+        "N",
+    );
+    opts.optopt(
+        "",
+        "probe_rules",
+        "TaqMan probe rules each probe is annotated with (comma separated: no_5p_g,c_gt_g,tm_delta,no_g_run,gc). default value is all.",
+        "RULES",
+    );
+    opts.optopt(
+        "",
+        "primer_tm",
+        "primer Tm used by the tm_delta rule. default value is the highest Tm of the input primers.",
+        "TM",
+    );
+    opts.optopt(
+        "f",
+        "format",
+        "candidates (ranked probes per primer pair, default), bin (16 bytes per probe), text (one probe per line with its evaluation) or count.",
+        "FORMAT",
+    );
+    opts
+}
+
+/// Writes the `top` candidates of every primer pair as a TSV, and returns the
+/// number of primer pairs with candidates.
+fn write_probe_candidates<W: Write>(
+    w: &mut W,
+    candidates: &HashMap<ProbeCandidateKey, (u32, usize)>,
+    primer_pair_info: &[(String, char, usize)],
+    top: usize,
+    probe_rules: &ProbeRules,
+    primer_tm: Option<f64>,
+) -> Result<usize, CliError> {
+    let ranked_by_pair = rank_probe_candidates_by_pair(candidates, primer_pair_info, top);
+    w.write_all(probe_candidate_tsv_header().as_bytes())?;
+    for (pair_id, ranked) in &ranked_by_pair {
+        for (rank, candidate) in ranked.iter().enumerate() {
+            let evaluation = probe_rules.evaluate(
+                &decode_u128_2_dna_seq(&candidate.probe, PROBE_LEN),
+                primer_tm,
+            );
+            let strand = primer_pair_info[candidate.primer_idx].1;
+            w.write_all(
+                probe_candidate_tsv_line(pair_id, rank + 1, candidate, strand, &evaluation)
+                    .as_bytes(),
+            )?;
+        }
+    }
+    Ok(ranked_by_pair.len())
+}
+
+pub fn run(matches: &Matches) -> Result<(), CliError> {
+    let read_file = required(matches, "i")?;
+    let primer_file = required(matches, "p")?;
+    let output_file = required(matches, "o")?;
+    let threads = threads(matches, 8)?;
+    let threshold: u32 = parse_opt(matches, "a", 8)?;
+    let triming_size: usize = parse_opt(matches, "s", 15)?;
+    let max_product_size: usize = parse_opt(matches, "l", usize::MAX)?;
+    let top: usize = parse_opt(matches, "n", usize::MAX)?;
+    let format = match matches.opt_str("f").as_deref() {
+        None | Some("candidates") => ProbeFormat::Candidates,
+        Some("bin") => ProbeFormat::Bin,
+        Some("text") => ProbeFormat::Text,
+        Some("count") => ProbeFormat::Count,
+        Some(other) => {
+            return Err(CliError::Usage(format!(
+                "unknown format {:?} (candidates, bin, text or count)",
+                other
+            )))
+        }
+    };
+    let probe_rules: ProbeRules = match matches.opt_str("probe_rules") {
+        Some(names) => ProbeRules::from_names(&names).map_err(CliError::Usage)?,
+        None => ProbeRules::default(),
+    };
+
+    let primer_pairs = load_primer_set(&primer_file, &PrimerSetOptions::default())?;
+    let mut expanded_pairs: Vec<PrimerPair> = Vec::new();
+    let mut max_primer_tm: f64 = f64::NAN;
+    for pair in &primer_pairs {
+        for pair in expand_primer_pair(pair)? {
+            max_primer_tm = max_primer_tm
+                .max(probe_rules.tm(&pair.left))
+                .max(probe_rules.tm(&pair.right));
+            expanded_pairs.push(pair);
+        }
+    }
+    let (primer, primer_pair_info) =
+        primer_tuples(&expanded_pairs, triming_size).map_err(CliError::Usage)?;
+    let primer_tm: Option<f64> = match matches.opt_str("primer_tm") {
+        Some(_) => Some(parse_opt(matches, "primer_tm", 0.0)?),
+        None if max_primer_tm.is_nan() => None,
+        None => Some(max_primer_tm),
+    };
+    eprintln!(
+        "Number of primers: {}\tprimer Tm for probe rules: {:?}",
+        primer.len(),
+        primer_tm
+    );
+
+    let mut sequences: Vec<DnaSequence> = Vec::new();
+    for_each_fasta_record(&read_file, |_, seq| {
+        sequences.push(DnaSequence::new(&seq.to_vec()))
+    })?;
+    eprintln!("loading {:?} done: {} reads", read_file, sequences.len());
+    let chunk_size: usize = sequences.len().div_ceil(threads).max(1);
+    let ranges: Vec<(usize, usize)> = (0..sequences.len())
+        .step_by(chunk_size)
+        .map(|start| (start, (start + chunk_size).min(sequences.len())))
+        .collect();
+
+    let mut cbf: Vec<u32> = vec![0; BLOOMFILTER_TABLE_SIZE];
+    thread::scope(|scope| {
+        let mut children = Vec::new();
+        for (i, &(start, end)) in ranges.iter().enumerate() {
+            let (sequences, primer) = (&sequences, &primer);
+            children.push(
+                scope.spawn(move || build_counting_bloom_filter(sequences, start, end, i, primer)),
+            );
+        }
+        for child in children {
+            for (x, y) in cbf.iter_mut().zip(child.join().unwrap()) {
+                *x = x.saturating_add(y);
+            }
+        }
+    });
+
+    let mut w = BufWriter::new(create(&output_file)?);
+    if format == ProbeFormat::Candidates {
+        let mut candidates: HashMap<ProbeCandidateKey, (u32, usize)> = HashMap::new();
+        thread::scope(|scope| {
+            let mut children = Vec::new();
+            for (i, &(start, end)) in ranges.iter().enumerate() {
+                let (cbf, sequences, primer, primer_pair_info) =
+                    (&cbf, &sequences, &primer, &primer_pair_info);
+                children.push(scope.spawn(move || {
+                    collect_probe_candidates(
+                        cbf,
+                        &sequences[start..end],
+                        threshold,
+                        i,
+                        primer,
+                        primer_pair_info,
+                        max_product_size,
+                    )
+                }));
+            }
+            for child in children {
+                merge_probe_candidates(&mut candidates, child.join().unwrap());
+            }
+        });
+        let pairs: usize = write_probe_candidates(
+            &mut w,
+            &candidates,
+            &primer_pair_info,
+            top,
+            &probe_rules,
+            primer_tm,
+        )?;
+        w.flush()?;
+        eprintln!(
+            "primer pairs with probe candidates: {}\tcandidates: {}",
+            pairs,
+            candidates.len()
+        );
+        return Ok(());
+    }
+
+    let mut probes: HashSet<u128> = HashSet::new();
+    thread::scope(|scope| {
+        let mut children = Vec::new();
+        for (i, &(start, end)) in ranges.iter().enumerate() {
+            let (cbf, sequences, primer) = (&cbf, &sequences, &primer);
+            children.push(scope.spawn(move || {
+                number_of_high_occurence_kmer(cbf, sequences, start, end, threshold, i, primer)
+            }));
+        }
+        for child in children {
+            probes.extend(child.join().unwrap());
+        }
+    });
+    let mut probes: Vec<u128> = probes.into_iter().collect();
+    probes.sort_unstable();
+    match format {
+        ProbeFormat::Count => writeln!(
+            w,
+            "k-mer count: {}\tthreshold: {}\tinput file {:?}",
+            probes.len(),
+            threshold,
+            read_file
+        )?,
+        ProbeFormat::Bin => {
+            write_tuple_records(&mut w, probes.iter().map(|probe| ProbeKmer(*probe)))?;
+        }
+        _ => {
+            // プローブ配列, Tm, GC, 全ルールを満たすか, ルールごとの結果
+            for probe in &probes {
+                let probe_seq: Vec<u8> = decode_u128_2_dna_seq(probe, PROBE_LEN);
+                let evaluation = probe_rules.evaluate(&probe_seq, primer_tm);
+                writeln!(
+                    w,
+                    "{}\t{:.1}\t{:.2}\t{}\t{}",
+                    String::from_utf8(probe_seq).unwrap(),
+                    evaluation.tm,
+                    evaluation.gc,
+                    if evaluation.passes() { "pass" } else { "fail" },
+                    evaluation
+                )?;
+            }
+        }
+    }
+    w.flush()?;
+    eprintln!(
+        "{} probes occurring {} or more times: {:?}",
+        probes.len(),
+        threshold,
+        output_file
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::probe::write_probe_candidates;
+    use crate::commands::{args, dispatch};
+    use ::function_name::named;
+    use search_primer::in_silico_pcr::PrimerPair;
+    use search_primer::probe_rules::ProbeRules;
+    use search_probe::find_taqman_probe::{primer_tuples, ProbeCandidateKey, PROBE_LEN};
+    use search_probe::sequence_encoder_util::DnaSequence;
+    use std::collections::HashMap;
+    use std::fs;
+
+    const TARGET: &[u8] =
+        b"ACGTTGCAAGGCTTACCGTAGGATCCTTAGCAGTCAAGTCTTGACCATGCGATACGGTCAGTACCTGAAGCTTCGAT";
+
+    #[test]
+    #[named]
+    fn write_probe_candidates_test() {
+        let pairs: Vec<PrimerPair> = ["p1", "p2"]
+            .iter()
+            .map(|id| PrimerPair {
+                id: id.to_string(),
+                left: TARGET[..20].to_vec(),
+                right: TARGET[57..].to_vec(),
+            })
+            .collect();
+        let (_, primer_pair_info) = primer_tuples(&pairs, 15).unwrap();
+        let probe = |start: usize| -> u128 {
+            DnaSequence::new(&TARGET[start..start + PROBE_LEN].to_vec())
+                .subsequence_as_u128(vec![[0, PROBE_LEN]])
+        };
+        // タプルは(p1, +), (p1, -), (p2, +), (p2, -)の順。p1はアンプリコンの多い-鎖の候補が1位
+        let candidates: HashMap<ProbeCandidateKey, (u32, usize)> = HashMap::from([
+            ((0, probe(20), 25), (50, 3)),
+            ((1, probe(22), 30), (40, 5)),
+            ((2, probe(24), 22), (10, 1)),
+        ]);
+        let mut output: Vec<u8> = Vec::new();
+        let written = write_probe_candidates(
+            &mut output,
+            &candidates,
+            &primer_pair_info,
+            1,
+            &ProbeRules::default(),
+            None,
+        )
+        .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        let prefix = |pair: &str, start: usize, strand: char, offset: usize, counts: &str| {
+            format!(
+                "{}\t1\t{}\t{}\t{}\t{}\t",
+                pair,
+                String::from_utf8_lossy(&TARGET[start..start + PROBE_LEN]),
+                strand,
+                offset,
+                counts
+            )
+        };
+        assert!(
+            written == 2
+                && primer_pair_info[1] == ("p1".to_string(), '-', 5)
+                && lines.len() == 3
+                && lines[0].starts_with("primer_id\trank\tprobe\tstrand\toffset")
+                && lines[1].starts_with(&prefix("p1", 22, '-', 30, "40\t5"))
+                && lines[2].starts_with(&prefix("p2", 24, '+', 22, "10\t1")),
+            "{} failed: {} {:?}",
+            function_name!(),
+            written,
+            lines
+        );
+    }
+
+    #[test]
+    #[named]
+    fn probe_usage_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        fs::write(
+            path("reads.fa"),
+            format!(">r1\n{}\n", String::from_utf8_lossy(TARGET)),
+        )
+        .unwrap();
+        fs::write(
+            path("primers.tsv"),
+            format!(
+                "p1\t{}\t{}\n",
+                String::from_utf8_lossy(&TARGET[..20]),
+                String::from_utf8_lossy(&TARGET[57..])
+            ),
+        )
+        .unwrap();
+        let probe = |extra: &[&str]| -> i32 {
+            let mut words: Vec<String> = args(&[
+                "probe",
+                "-i",
+                &path("reads.fa"),
+                "-p",
+                &path("primers.tsv"),
+                "-o",
+                &path("out.tsv"),
+            ]);
+            words.extend(args(extra));
+            dispatch(&words)
+        };
+        // いずれもcounting bloom filterを作る前に使い方の誤りとして終わる
+        let codes = [
+            probe(&["-s", "0"]),
+            probe(&["-f", "xyz"]),
+            probe(&["--probe_rules", "frobnicate"]),
+        ];
+        assert!(
+            codes == [2, 2, 2],
+            "{} failed: {:?}",
+            function_name!(),
+            codes
+        );
+    }
+}
@@ -0,0 +1,170 @@
+// 索引つきのバイナリからキーを探す。索引(swordfish indexの出力)に記録した種類とカウントの有無で読む。
+// 出力はクエリごとに query, key(hex), present, count のTSV。
+
+use crate::cli::{create, for_each_line, open, required, CliError};
+use crate::index::SparseIndex;
+use crate::records::Record;
+use getopts::{Matches, Options};
+use std::io::{BufReader, BufWriter, Write};
+
+pub fn options() -> Options {
+    let mut opts = Options::new();
+    opts.optopt("i", "input", "sorted binary record file.", "FILE");
+    opts.optopt(
+        "x",
+        "index",
+        "index made by swordfish index. default value is FILE.idx.",
+        "INDEX",
+    );
+    opts.optmulti(
+        "q",
+        "query",
+        "tuple id (SEQUENCE_ID= lines of primer3_core input are accepted), hex value, bases of the whole record, or for lmr the hex of its 56 bytes. can be given more than once.",
+        "KEY",
+    );
+    opts.optopt("Q", "query_file", "file with one query per line.", "FILE");
+    opts.optopt("o", "output", "output TSV. default is stdout.", "OUTPUT");
+    opts
+}
+
+pub fn run(matches: &Matches) -> Result<(), CliError> {
+    let input_file = required(matches, "i")?;
+    let index_file = matches
+        .opt_str("x")
+        .unwrap_or_else(|| format!("{}.idx", input_file));
+    let mut queries: Vec<String> = matches.opt_strs("q");
+    if let Some(query_file) = matches.opt_str("Q") {
+        for_each_line(&query_file, |line| {
+            if !line.trim().is_empty() {
+                queries.push(line.to_string());
+            }
+        })?;
+    }
+    if queries.is_empty() {
+        return Err(CliError::Usage(
+            "--query or --query_file is required".to_string(),
+        ));
+    }
+
+    let index_reader = std::fs::File::open(&index_file).map_err(|e| {
+        CliError::Failure(format!(
+            "{}: {}; build it with swordfish index",
+            index_file, e
+        ))
+    })?;
+    let index = SparseIndex::read(&mut BufReader::new(index_reader))
+        .map_err(|e| CliError::Failure(format!("{}: {}", index_file, e)))?;
+    let mut data = open(&input_file)?;
+    let data_size = data.metadata()?.len();
+    if data_size != index.records * index.stride() {
+        return Err(CliError::Failure(format!(
+            "{} has {} bytes but {} indexes {} records of {} bytes; rebuild it with swordfish index",
+            input_file,
+            data_size,
+            index_file,
+            index.records,
+            index.stride()
+        )));
+    }
+    let keys: Vec<Record> = queries
+        .iter()
+        .map(|q| Record::parse_key(index.kind, q))
+        .collect::<Result<_, _>>()?;
+
+    let mut w: Box<dyn Write> = match matches.opt_str("o") {
+        Some(output_file) => Box::new(BufWriter::new(create(&output_file)?)),
+        None => Box::new(BufWriter::new(std::io::stdout())),
+    };
+    writeln!(w, "query\tkey\tpresent\tcount")?;
+    let mut found: usize = 0;
+    for (query, key) in queries.iter().zip(&keys) {
+        let count = index.lookup(&mut data, key)?;
+        found += count.is_some() as usize;
+        writeln!(
+            w,
+            "{}\t{}\t{}\t{}",
+            query.trim(),
+            key.hex(),
+            count.is_some(),
+            count.unwrap_or(0)
+        )?;
+    }
+    w.flush()?;
+    eprintln!(
+        "{} of {} queries found in {:?}",
+        found,
+        keys.len(),
+        input_file
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{args, dispatch};
+    use crate::records::Record;
+    use ::function_name::named;
+    use std::fs;
+
+    #[test]
+    #[named]
+    fn query_indexed_file_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        // 5は2件ある。索引は2件ごと
+        let records: Vec<Record> = [1u128, 5, 5, 0x1b, 1 << 64]
+            .iter()
+            .map(|&v| Record::Lr(v))
+            .collect();
+        fs::write(
+            path("lr.bin"),
+            records
+                .iter()
+                .flat_map(|r| r.to_bytes())
+                .collect::<Vec<u8>>(),
+        )
+        .unwrap();
+        let indexed = dispatch(&args(&["index", "-i", &path("lr.bin"), "-n", "2"]));
+        let bases_of_1b = format!("{}{}CGT", "A".repeat(32), "A".repeat(29));
+        let code = dispatch(&args(&[
+            "query",
+            "-i",
+            &path("lr.bin"),
+            "-q",
+            "5",
+            "-q",
+            &bases_of_1b,
+            "-q",
+            "SEQUENCE_ID=lr_32.32_00000000000000000000000000000007",
+            "-o",
+            &path("out.tsv"),
+        ]));
+        let output = fs::read_to_string(path("out.tsv")).unwrap_or_default();
+        let expected = format!(
+            "query\tkey\tpresent\tcount\n5\t{:032x}\ttrue\t2\n{}\t{:032x}\ttrue\t1\nSEQUENCE_ID=lr_32.32_00000000000000000000000000000007\t{:032x}\tfalse\t0\n",
+            5, bases_of_1b, 0x1b, 7
+        );
+        assert!(
+            indexed == 0 && code == 0 && output == expected,
+            "{} failed: {} {} {:?}",
+            function_name!(),
+            indexed,
+            code,
+            output
+        );
+
+        // 索引を作り直さずにデータが変わったら失敗する
+        let mut grown: Vec<u8> = fs::read(path("lr.bin")).unwrap();
+        grown.extend(Record::Lr(2 << 64).to_bytes());
+        fs::write(path("lr.bin"), grown).unwrap();
+        let stale = dispatch(&args(&["query", "-i", &path("lr.bin"), "-q", "5"]));
+        let missing_query = dispatch(&args(&["query", "-i", &path("lr.bin")]));
+        assert!(
+            stale == 1 && missing_query == 2,
+            "{} failed: {} {}",
+            function_name!(),
+            stale,
+            missing_query
+        );
+    }
+}
@@ -0,0 +1,242 @@
+// ソート済みのバイナリ同士の集合演算と、Jaccard係数・包含率の集計。
+// --min_countを指定すると、各入力でカウントがそれ未満のキーは無いものとして扱う。
+// 1つだけ指定すれば全入力に、カンマ区切りで入力の数だけ指定すれば入力ごとに適用する。
+
+use crate::cli::{create, required, required_multi, CliError};
+use crate::commands::merge::{counted_sources, merge_sorted, merge_sorted_keys, SetOperation};
+use crate::records::{kind_option, record_kind};
+use getopts::{Matches, Options};
+use std::io::{BufWriter, Write};
+
+pub fn options() -> Options {
+    let mut opts = Options::new();
+    opts.optmulti(
+        "i",
+        "input",
+        "sorted binary record file. give it once for each operand, in order.",
+        "FILE",
+    );
+    opts.optopt(
+        "o",
+        "output",
+        "binary record file, or TSV for jaccard.",
+        "OUTPUT",
+    );
+    opts.optopt(
+        "s",
+        "set",
+        "union, intersection, difference (keys of the first input absent from the others), symmetric_difference (keys in exactly one input) or jaccard (pairwise statistics).",
+        "OPERATION",
+    );
+    opts.optopt(
+        "",
+        "min_count",
+        "ignore keys counted fewer than N times; one value for every input or one per input separated by commas. default value is 1.",
+        "N[,N...]",
+    );
+    opts.optflag(
+        "c",
+        "counts",
+        "every record of the inputs and the output is followed by a 4 byte count.",
+    );
+    opts.optflag(
+        "",
+        "write_counts",
+        "write a count after every output record even if the inputs have none.",
+    );
+    kind_option(&mut opts);
+    opts
+}
+
+/// Per-input thresholds from `--min_count`.
+pub fn min_counts(value: Option<&str>, inputs: usize) -> Result<Vec<u32>, CliError> {
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(vec![1; inputs]),
+    };
+    let parsed: Vec<u32> = value
+        .split(',')
+        .map(|each| each.trim().parse())
+        .collect::<Result<Vec<u32>, _>>()
+        .map_err(|_| CliError::Usage(format!("invalid value for --min_count: {:?}", value)))?;
+    match parsed.len() {
+        1 => Ok(vec![parsed[0]; inputs]),
+        n if n == inputs => Ok(parsed),
+        n => Err(CliError::Usage(format!(
+            "--min_count has {} values for {} inputs",
+            n, inputs
+        ))),
+    }
+}
+
+/// Number of keys of each input and of each pair of inputs.
+#[derive(Debug, PartialEq)]
+pub struct Overlap {
+    pub sizes: Vec<usize>,
+    /// `shared[i][j]`: keys in both input i and input j.
+    pub shared: Vec<Vec<usize>>,
+}
+
+impl Overlap {
+    pub fn new(inputs: usize) -> Overlap {
+        Overlap {
+            sizes: vec![0; inputs],
+            shared: vec![vec![0; inputs]; inputs],
+        }
+    }
+
+    pub fn add(&mut self, present: &[bool]) {
+        let indices: Vec<usize> = (0..present.len()).filter(|&i| present[i]).collect();
+        for &i in &indices {
+            self.sizes[i] += 1;
+            for &j in &indices {
+                self.shared[i][j] += 1;
+            }
+        }
+    }
+
+    pub fn jaccard(&self, i: usize, j: usize) -> f64 {
+        let union = self.sizes[i] + self.sizes[j] - self.shared[i][j];
+        if union == 0 {
+            return 0.0;
+        }
+        self.shared[i][j] as f64 / union as f64
+    }
+
+    /// Fraction of the keys of input i that input j also has.
+    pub fn containment(&self, i: usize, j: usize) -> f64 {
+        if self.sizes[i] == 0 {
+            return 0.0;
+        }
+        self.shared[i][j] as f64 / self.sizes[i] as f64
+    }
+}
+
+pub fn run(matches: &Matches) -> Result<(), CliError> {
+    let inputs = required_multi(matches, "i")?;
+    let output_file = required(matches, "o")?;
+    let kind = record_kind(matches)?;
+    let operation = required(matches, "s")?;
+    let counted = matches.opt_present("c");
+    let write_counts = counted || matches.opt_present("write_counts");
+    let thresholds = min_counts(matches.opt_str("min_count").as_deref(), inputs.len())?;
+    let set_operation = match operation.as_str() {
+        "jaccard" => None,
+        name => Some(SetOperation::parse(name)?),
+    };
+
+    let sources: Vec<_> = counted_sources(&inputs, kind, counted)?
+        .into_iter()
+        .zip(thresholds)
+        .map(|(source, min)| source.filter(move |r| r.as_ref().map_or(true, |(_, c)| *c >= min)))
+        .collect();
+    let mut w = BufWriter::new(create(&output_file)?);
+    match set_operation {
+        Some(set_operation) => {
+            let stats = merge_sorted(sources, set_operation, |record, count| {
+                w.write_all(&record.to_bytes())?;
+                if write_counts {
+                    w.write_all(&count.to_be_bytes())?;
+                }
+                Ok(())
+            })?;
+            eprintln!("| Input File | Records |");
+            eprintln!("|------------|--------:|");
+            for (input, count) in inputs.iter().zip(&stats.input_records) {
+                eprintln!("| {} | {} |", input, count);
+            }
+            eprintln!(
+                "{} of {:?}: {} records written to {}",
+                operation, inputs, stats.output_records, output_file
+            );
+        }
+        None => {
+            let mut overlap = Overlap::new(inputs.len());
+            merge_sorted_keys(sources, |_, _, present| {
+                overlap.add(present);
+                Ok(())
+            })?;
+            writeln!(
+                w,
+                "file_a\tfile_b\tsize_a\tsize_b\tintersection\tunion\tjaccard\tcontainment_a_in_b\tcontainment_b_in_a"
+            )?;
+            for i in 0..inputs.len() {
+                for j in i + 1..inputs.len() {
+                    writeln!(
+                        w,
+                        "{}\t{}\t{}\t{}\t{}\t{}\t{:.6}\t{:.6}\t{:.6}",
+                        inputs[i],
+                        inputs[j],
+                        overlap.sizes[i],
+                        overlap.sizes[j],
+                        overlap.shared[i][j],
+                        overlap.sizes[i] + overlap.sizes[j] - overlap.shared[i][j],
+                        overlap.jaccard(i, j),
+                        overlap.containment(i, j),
+                        overlap.containment(j, i)
+                    )?;
+                }
+            }
+            eprintln!(
+                "jaccard of {} inputs written to {}",
+                inputs.len(),
+                output_file
+            );
+        }
+    }
+    w.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::merge::merge_sorted_keys;
+    use crate::commands::set::{min_counts, Overlap};
+    use crate::records::Record;
+    use ::function_name::named;
+
+    #[test]
+    #[named]
+    fn overlap_test() {
+        let inputs: Vec<Vec<(u128, u32)>> = vec![
+            vec![(1, 5), (2, 5), (3, 1), (4, 5)],
+            vec![(2, 1), (3, 1), (4, 1), (5, 1)],
+        ];
+        // 1つ目の入力はカウント2以上だけ: {1, 2, 4} と {2, 3, 4, 5}
+        let thresholds = min_counts(Some("2,1"), 2).unwrap();
+        let sources: Vec<_> = inputs
+            .iter()
+            .zip(thresholds)
+            .map(|(input, min)| {
+                input
+                    .iter()
+                    .filter(move |(_, c)| *c >= min)
+                    .map(|&(v, c)| Ok((Record::Lr(v), c)))
+            })
+            .collect();
+        let mut overlap = Overlap::new(2);
+        merge_sorted_keys(sources, |_, _, present| {
+            overlap.add(present);
+            Ok(())
+        })
+        .unwrap();
+        assert!(
+            overlap.sizes == vec![3, 4]
+                && overlap.shared[0][1] == 2
+                && (overlap.jaccard(0, 1) - 0.4).abs() < 1e-9
+                && (overlap.containment(0, 1) - 2.0 / 3.0).abs() < 1e-9
+                && (overlap.containment(1, 0) - 0.5).abs() < 1e-9,
+            "{} failed: {:?}",
+            function_name!(),
+            overlap
+        );
+        assert!(
+            min_counts(None, 3).unwrap() == vec![1, 1, 1]
+                && min_counts(Some("4"), 2).unwrap() == vec![4, 4]
+                && min_counts(Some("1,2,3"), 2).is_err()
+                && min_counts(Some("x"), 2).is_err(),
+            "{} failed",
+            function_name!()
+        );
+    }
+}
@@ -0,0 +1,319 @@
+// 1つのバイナリを複数のファイルに分ける。レコードは1件ずつ読み書きするので、入力の大きさによらずメモリは一定。
+// range: 入力はソート済み(mergeの出力)とし、先頭から件数の等しい連続した範囲に分ける。各ファイルもソート済みになる。
+// hash: counting bloom filterと同じhashで振り分ける。入力の順序は問わない。
+// 各ファイルの件数と最小・最大のキーを PREFIX_manifest.tsv に書き出す。
+// -cではカウントつきのレコードを読み、カウントをつけたまま書き出す。
+
+use crate::cli::{create, parse_opt, required, CliError};
+use crate::commands::merge::CountedRecord;
+use crate::records::{kind_option, record_count, record_kind, Record, RecordReader};
+use getopts::{Matches, Options};
+use std::io::{BufWriter, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SplitMode {
+    Range,
+    Hash,
+}
+
+impl SplitMode {
+    pub fn parse(name: &str) -> Result<SplitMode, CliError> {
+        match name {
+            "range" => Ok(SplitMode::Range),
+            "hash" => Ok(SplitMode::Hash),
+            _ => Err(CliError::Usage(format!(
+                "unknown split mode {:?} (range or hash)",
+                name
+            ))),
+        }
+    }
+}
+
+/// Number of records and smallest and largest record written to one output file.
+#[derive(Default, PartialEq)]
+pub struct PartStats {
+    pub records: usize,
+    pub min: Option<Record>,
+    pub max: Option<Record>,
+}
+
+impl PartStats {
+    fn add(&mut self, record: Record) {
+        self.records += 1;
+        if self.min.is_none_or(|min| record < min) {
+            self.min = Some(record);
+        }
+        if self.max.is_none_or(|max| record > max) {
+            self.max = Some(record);
+        }
+    }
+}
+
+pub fn options() -> Options {
+    let mut opts = Options::new();
+    opts.optopt("i", "input", "binary record file.", "FILE");
+    opts.optopt(
+        "o",
+        "output",
+        "output prefix; writes PREFIX_001.bin to PREFIX_NNN.bin and PREFIX_manifest.tsv.",
+        "PREFIX",
+    );
+    opts.optopt(
+        "n",
+        "parts",
+        "number of output files. default value is 20.",
+        "N",
+    );
+    opts.optopt(
+        "m",
+        "mode",
+        "range (default): contiguous ranges of equal size from a sorted input, such as the output of merge. hash: by the hash of each record.",
+        "MODE",
+    );
+    opts.optflag(
+        "c",
+        "counts",
+        "every record of the input and the outputs is followed by a 4 byte count.",
+    );
+    kind_option(&mut opts);
+    opts
+}
+
+/// Writes each record of `records` (`total` of them) to one of `writers`,
+/// followed by its count when `write_counts` is set.
+/// In range mode the records must be sorted without duplicates.
+pub fn split_records<I, W>(
+    records: I,
+    total: usize,
+    mode: SplitMode,
+    write_counts: bool,
+    writers: &mut [W],
+) -> Result<Vec<PartStats>, CliError>
+where
+    I: Iterator<Item = CountedRecord>,
+    W: Write,
+{
+    let parts = writers.len();
+    let mut stats: Vec<PartStats> = (0..parts).map(|_| PartStats::default()).collect();
+    let mut previous: Option<Record> = None;
+    for (i, record) in records.enumerate() {
+        let (record, count) = record?;
+        let part = match mode {
+            SplitMode::Range => {
+                if previous.is_some_and(|p| p >= record) {
+                    return Err(CliError::Failure(format!(
+                        "record {} is not larger than the previous one; range mode needs a sorted input without duplicates (see swordfish merge)",
+                        i + 1
+                    )));
+                }
+                if i >= total {
+                    return Err(CliError::Failure(format!(
+                        "more than {} records in the input",
+                        total
+                    )));
+                }
+                previous = Some(record);
+                i * parts / total
+            }
+            SplitMode::Hash => record.bucket(parts),
+        };
+        writers[part].write_all(&record.to_bytes())?;
+        if write_counts {
+            writers[part].write_all(&count.to_be_bytes())?;
+        }
+        stats[part].add(record);
+    }
+    Ok(stats)
+}
+
+fn manifest_key(record: &Option<Record>) -> String {
+    match record {
+        Some(record) => record.hex(),
+        None => "-".to_string(),
+    }
+}
+
+pub fn run(matches: &Matches) -> Result<(), CliError> {
+    let input_file = required(matches, "i")?;
+    let prefix = required(matches, "o")?;
+    let parts: usize = parse_opt(matches, "n", 20)?;
+    if parts == 0 {
+        return Err(CliError::Usage("--parts must be at least 1".to_string()));
+    }
+    let mode = SplitMode::parse(&matches.opt_str("m").unwrap_or_else(|| "range".to_string()))?;
+    let kind = record_kind(matches)?;
+    let counted = matches.opt_present("c");
+
+    let total = record_count(&input_file, kind, counted)?;
+    let file = crate::cli::open(&input_file)?;
+    let mut reader = if counted {
+        RecordReader::with_counts(file, kind)
+    } else {
+        RecordReader::new(file, kind)
+    };
+    let file_names: Vec<String> = (0..parts)
+        .map(|i| format!("{}_{:03}.bin", prefix, i + 1))
+        .collect();
+    let mut writers: Vec<BufWriter<std::fs::File>> = Vec::with_capacity(parts);
+    for name in &file_names {
+        writers.push(BufWriter::new(create(name)?));
+    }
+    let records = std::iter::from_fn(|| reader.next_counted());
+    let stats = split_records(records, total, mode, counted, &mut writers)
+        .map_err(|e| CliError::Failure(format!("{}: {}", input_file, e)))?;
+    for w in writers.iter_mut() {
+        w.flush()?;
+    }
+
+    let manifest_file = format!("{}_manifest.tsv", prefix);
+    let mut manifest = BufWriter::new(create(&manifest_file)?);
+    writeln!(manifest, "file\trecords\tmin_key\tmax_key")?;
+    for (name, each) in file_names.iter().zip(&stats) {
+        writeln!(
+            manifest,
+            "{}\t{}\t{}\t{}",
+            name,
+            each.records,
+            manifest_key(&each.min),
+            manifest_key(&each.max)
+        )?;
+    }
+    manifest.flush()?;
+    eprintln!(
+        "{} records of {:?} split into {} files: {}_NNN.bin, manifest: {}",
+        total, input_file, parts, prefix, manifest_file
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::split::{split_records, SplitMode};
+    use crate::commands::{args, dispatch};
+    use crate::records::Record;
+    use ::function_name::named;
+    use std::fs;
+
+    #[test]
+    #[named]
+    fn split_records_test() {
+        let records: Vec<Record> = (0..10u128).map(Record::Lr).collect();
+        let mut writers: Vec<Vec<u8>> = vec![Vec::new(); 3];
+        let stats = split_records(
+            records.iter().map(|r| Ok((*r, 1))),
+            10,
+            SplitMode::Range,
+            false,
+            &mut writers,
+        )
+        .unwrap();
+        let counts: Vec<usize> = stats.iter().map(|s| s.records).collect();
+        assert!(
+            counts == vec![4, 3, 3]
+                && stats[1].min == Some(Record::Lr(4))
+                && stats[1].max == Some(Record::Lr(6))
+                && writers[2]
+                    == [7u128, 8, 9]
+                        .iter()
+                        .flat_map(|v| v.to_be_bytes())
+                        .collect::<Vec<u8>>(),
+            "{} failed: {:?}",
+            function_name!(),
+            counts
+        );
+
+        // hashは何度やっても同じ振り分け、順不同の入力でよい
+        let shuffled: Vec<Record> = (0..100u128).rev().map(Record::Lr).collect();
+        let mut hashed: Vec<Vec<u8>> = vec![Vec::new(); 4];
+        let stats = split_records(
+            shuffled.iter().map(|r| Ok((*r, 1))),
+            100,
+            SplitMode::Hash,
+            false,
+            &mut hashed,
+        )
+        .unwrap();
+        let mut again: Vec<Vec<u8>> = vec![Vec::new(); 4];
+        split_records(
+            shuffled.iter().map(|r| Ok((*r, 1))),
+            100,
+            SplitMode::Hash,
+            false,
+            &mut again,
+        )
+        .unwrap();
+        assert!(
+            hashed == again
+                && stats.iter().map(|s| s.records).sum::<usize>() == 100
+                && stats.iter().all(|s| s.records > 0),
+            "{} failed: {:?}",
+            function_name!(),
+            stats.iter().map(|s| s.records).collect::<Vec<usize>>()
+        );
+
+        let mut unsorted: Vec<Vec<u8>> = vec![Vec::new(); 2];
+        assert!(
+            split_records(
+                shuffled.iter().map(|r| Ok((*r, 1))),
+                100,
+                SplitMode::Range,
+                false,
+                &mut unsorted
+            )
+            .is_err(),
+            "{} failed",
+            function_name!()
+        );
+    }
+
+    #[test]
+    #[named]
+    fn split_counted_file_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        // merge -cの出力と同じく、16 byteのレコードの後に4 byteのカウント
+        let counted: Vec<u8> = (1..=5u128)
+            .flat_map(|v| {
+                let mut bytes = Record::Lr(v).to_bytes();
+                bytes.extend((v as u32 * 10).to_be_bytes());
+                bytes
+            })
+            .collect();
+        fs::write(path("counted.bin"), &counted).unwrap();
+        let split = |extra: &[&str]| -> i32 {
+            let mut words: Vec<String> = args(&[
+                "split",
+                "-i",
+                &path("counted.bin"),
+                "-o",
+                &path("part"),
+                "-n",
+                "2",
+            ]);
+            words.extend(args(extra));
+            dispatch(&words)
+        };
+        let code = split(&["-c"]);
+        let first = fs::read(path("part_001.bin")).unwrap_or_default();
+        let second = fs::read(path("part_002.bin")).unwrap_or_default();
+        let manifest = fs::read_to_string(path("part_manifest.tsv")).unwrap_or_default();
+        assert!(
+            code == 0
+                && first == counted[..60]
+                && second == counted[60..]
+                && manifest
+                    .lines()
+                    .nth(2)
+                    .is_some_and(|l| l.ends_with(&format!("\t2\t{:032x}\t{:032x}", 4, 5))),
+            "{} failed: {} {} {} {:?}",
+            function_name!(),
+            code,
+            first.len(),
+            second.len(),
+            manifest
+        );
+        // 100 byteは16 byteのレコードの整数倍ではない
+        let uncounted = split(&[]);
+        assert!(uncounted == 1, "{} failed: {}", function_name!(), uncounted);
+    }
+}
@@ -0,0 +1,203 @@
+// ソート済みのバイナリに対する疎な索引(fence pointer)。
+// interval件ごとに先頭のキーだけを持ち、キーを探すときは該当するブロックだけをseekして読む。
+// 索引ファイル(データファイル名 + ".idx")の形式:
+//   "SWFIDX01", kind(1 byte), countsの有無(1 byte), 予約(6 byte), interval(u64), 件数(u64), 各ブロック先頭のレコード
+// 数値はすべてbig endian。
+
+use crate::cli::CliError;
+use crate::records::{Record, RecordKind, RecordReader, COUNT_SIZE};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+const MAGIC: &[u8; 8] = b"SWFIDX01";
+const HEADER_SIZE: usize = 32;
+
+pub struct SparseIndex {
+    pub kind: RecordKind,
+    pub counted: bool,
+    pub interval: u64,
+    pub records: u64,
+    /// First record of each block of `interval` records.
+    pub fences: Vec<Record>,
+}
+
+impl SparseIndex {
+    /// Reads a whole sorted file once; a record smaller than the one before it is an error.
+    pub fn build<R: Read>(
+        mut reader: RecordReader<R>,
+        kind: RecordKind,
+        counted: bool,
+        interval: u64,
+    ) -> Result<SparseIndex, CliError> {
+        let mut index = SparseIndex {
+            kind,
+            counted,
+            interval,
+            records: 0,
+            fences: Vec::new(),
+        };
+        let mut previous: Option<Record> = None;
+        while let Some(next) = reader.next_counted() {
+            let (record, _) = next?;
+            if previous.is_some_and(|p| p > record) {
+                return Err(CliError::Failure(format!(
+                    "not sorted at record {}",
+                    index.records + 1
+                )));
+            }
+            if index.records.is_multiple_of(interval) {
+                index.fences.push(record);
+            }
+            previous = Some(record);
+            index.records += 1;
+        }
+        Ok(index)
+    }
+
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), CliError> {
+        let mut header = [0u8; HEADER_SIZE];
+        header[0..8].copy_from_slice(MAGIC);
+        header[8] = match self.kind {
+            RecordKind::Lr => 0,
+            RecordKind::Probe => 1,
+            RecordKind::Lmr => 2,
+        };
+        header[9] = self.counted as u8;
+        header[16..24].copy_from_slice(&self.interval.to_be_bytes());
+        header[24..32].copy_from_slice(&self.records.to_be_bytes());
+        w.write_all(&header)?;
+        for fence in &self.fences {
+            w.write_all(&fence.to_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(r: &mut R) -> Result<SparseIndex, CliError> {
+        let mut header = [0u8; HEADER_SIZE];
+        r.read_exact(&mut header)
+            .map_err(|_| CliError::Failure("not a swordfish index".to_string()))?;
+        if &header[0..8] != MAGIC {
+            return Err(CliError::Failure("not a swordfish index".to_string()));
+        }
+        let kind = match header[8] {
+            0 => RecordKind::Lr,
+            1 => RecordKind::Probe,
+            2 => RecordKind::Lmr,
+            other => {
+                return Err(CliError::Failure(format!(
+                    "unknown record kind {} in the index",
+                    other
+                )))
+            }
+        };
+        let interval = u64::from_be_bytes(header[16..24].try_into().unwrap());
+        let records = u64::from_be_bytes(header[24..32].try_into().unwrap());
+        let fences: Vec<Record> = RecordReader::new(r, kind).collect::<Result<_, _>>()?;
+        if interval == 0 || fences.len() as u64 != records.div_ceil(interval) {
+            return Err(CliError::Failure(format!(
+                "broken index: {} fences for {} records every {}",
+                fences.len(),
+                records,
+                interval
+            )));
+        }
+        Ok(SparseIndex {
+            kind,
+            counted: header[9] != 0,
+            interval,
+            records,
+            fences,
+        })
+    }
+
+    /// Bytes of one record of the indexed file, with its count.
+    pub fn stride(&self) -> u64 {
+        (self.kind.record_size() + if self.counted { COUNT_SIZE } else { 0 }) as u64
+    }
+
+    /// Sum of the counts of `key` in the indexed `data` (number of copies without counts),
+    /// or `None` when it is absent. Reads only the blocks that can hold `key`.
+    pub fn lookup<R: Read + Seek>(
+        &self,
+        data: &mut R,
+        key: &Record,
+    ) -> Result<Option<u64>, CliError> {
+        // 同じキーが前のブロックの末尾から続いていることがあるので、keyより小さい最後のfenceから読む
+        let block = self.fences.partition_point(|fence| fence < key);
+        let block = block.saturating_sub(1) as u64;
+        data.seek(SeekFrom::Start(block * self.interval * self.stride()))?;
+        let mut reader = if self.counted {
+            RecordReader::with_counts(data, self.kind)
+        } else {
+            RecordReader::new(data, self.kind)
+        };
+        let mut total: Option<u64> = None;
+        while let Some(next) = reader.next_counted() {
+            let (record, count) = next?;
+            if record > *key {
+                break;
+            }
+            if record == *key {
+                total = Some(total.unwrap_or(0) + count as u64);
+            }
+        }
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::index::SparseIndex;
+    use crate::records::{Record, RecordKind, RecordReader};
+    use ::function_name::named;
+    use std::io::Cursor;
+
+    #[test]
+    #[named]
+    fn sparse_index_test() {
+        // 0, 2, 4, ..., 6が3件(ブロックの境目をまたぐ)、カウントつき
+        let mut keys: Vec<u128> = (0..20).map(|v| v * 2).collect();
+        keys.insert(3, 6);
+        keys.insert(3, 6);
+        let mut data: Vec<u8> = Vec::new();
+        for (i, key) in keys.iter().enumerate() {
+            data.extend(key.to_be_bytes());
+            data.extend((i as u32 + 1).to_be_bytes());
+        }
+        let index = SparseIndex::build(
+            RecordReader::with_counts(data.as_slice(), RecordKind::Lr),
+            RecordKind::Lr,
+            true,
+            4,
+        )
+        .unwrap();
+        let mut written: Vec<u8> = Vec::new();
+        index.write(&mut written).unwrap();
+        let index = SparseIndex::read(&mut written.as_slice()).unwrap();
+        let mut cursor = Cursor::new(data);
+        let found: Vec<Option<u64>> = [6, 0, 38, 7, 40]
+            .iter()
+            .map(|&v| index.lookup(&mut cursor, &Record::Lr(v)).unwrap())
+            .collect();
+        assert!(
+            index.records == 22
+                && index.fences.len() == 6
+                && found == vec![Some(4 + 5 + 6), Some(1), Some(22), None, None],
+            "{} failed: {:?}",
+            function_name!(),
+            found
+        );
+
+        let unsorted: Vec<u8> = [3u128, 1].iter().flat_map(|v| v.to_be_bytes()).collect();
+        assert!(
+            SparseIndex::build(
+                RecordReader::new(unsorted.as_slice(), RecordKind::Lr),
+                RecordKind::Lr,
+                false,
+                4
+            )
+            .is_err(),
+            "{} failed",
+            function_name!()
+        );
+    }
+}
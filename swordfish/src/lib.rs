@@ -0,0 +1,4 @@
+pub mod cli;
+pub mod commands;
+pub mod index;
+pub mod records;
@@ -0,0 +1,7 @@
+use std::{env, process};
+use swordfish::commands::dispatch;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    process::exit(dispatch(&args));
+}
@@ -0,0 +1,503 @@
+// 各パイプラインが書き出すバイナリのレコード(lr-tuple, プローブ, lmr-tuple)の読み書きと、
+// テキスト・FASTA・BLAST問い合わせ・primer3_core入力への整形。
+// バイト列との変換や配列・逆相補鎖は各crateのTupleRecordの実装(LrTuple, ProbeKmer, LmrTuple)に任せる。
+// どの種類もバイト列の辞書順と値の順序が一致するので、ソートはそのまま比較すればよい。
+
+use crate::cli::CliError;
+use getopts::{Matches, Options};
+use search_primer::counting_bloomfilter_util::hash_from_u128;
+use search_primer::sequence_encoder_util::{decode_u128_l, decode_u128_r, DnaSequence};
+use search_primer::tuple_id::TupleId;
+use search_primer::tuple_record::{LrTuple, TupleRecord};
+use search_primer_and_probe::sequence_encoder_util::{LmrTuple, LMR_RECORD_SIZE};
+use search_probe::find_taqman_probe::PROBE_LEN;
+use search_probe::sequence_encoder_util::ProbeKmer;
+use std::io::{BufReader, ErrorKind, Read};
+
+const LR_LEN: usize = 64;
+/// Bases between L and R in the primer3_core template of an lr-tuple.
+const LR_INTERNAL_N: usize = 50;
+/// Fixed primer sites around a probe in its primer3_core template.
+const PROBE_LEFT_FLANK: &str = "GGCACATAGGACGTTAGGGT";
+const PROBE_RIGHT_FLANK: &str = "GCCGTTAAGGTAGGCTGG";
+const PROBE_INTERNAL_N: usize = 38;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecordKind {
+    Lr,
+    Probe,
+    Lmr,
+}
+
+impl RecordKind {
+    pub fn parse(name: &str) -> Result<RecordKind, CliError> {
+        match name {
+            "lr" => Ok(RecordKind::Lr),
+            "probe" => Ok(RecordKind::Probe),
+            "lmr" => Ok(RecordKind::Lmr),
+            _ => Err(CliError::Usage(format!(
+                "unknown record kind {:?} (lr, probe or lmr)",
+                name
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RecordKind::Lr => "lr",
+            RecordKind::Probe => "probe",
+            RecordKind::Lmr => "lmr",
+        }
+    }
+
+    pub fn record_size(&self) -> usize {
+        match self {
+            RecordKind::Lr => LrTuple::RECORD_SIZE,
+            RecordKind::Probe => ProbeKmer::RECORD_SIZE,
+            RecordKind::Lmr => LmrTuple::RECORD_SIZE,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Record {
+    Lr(u128),
+    Probe(u128),
+    Lmr(LmrTuple),
+}
+
+impl Record {
+    /// Fails unless `bytes` is `kind.record_size()` long and holds a valid record.
+    pub fn from_bytes(kind: RecordKind, bytes: &[u8]) -> Result<Record, CliError> {
+        let record = match kind {
+            RecordKind::Lr => LrTuple::from_bytes(bytes).map(|t| Record::Lr(t.0)),
+            RecordKind::Probe => ProbeKmer::from_bytes(bytes).map(|t| Record::Probe(t.0)),
+            RecordKind::Lmr => LmrTuple::from_bytes(bytes).map(Record::Lmr),
+        };
+        record.map_err(|e| CliError::Failure(format!("invalid {} record: {}", kind.name(), e)))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Record::Lr(v) => LrTuple(*v).to_bytes(),
+            Record::Probe(v) => ProbeKmer(*v).to_bytes(),
+            Record::Lmr(t) => t.to_bytes(),
+        }
+    }
+
+    /// The same record read on the other strand.
+    pub fn reverse_complement(&self) -> Record {
+        match self {
+            Record::Lr(v) => Record::Lr(LrTuple(*v).reverse_complement().0),
+            Record::Probe(v) => Record::Probe(ProbeKmer(*v).reverse_complement().0),
+            Record::Lmr(t) => Record::Lmr(t.reverse_complement()),
+        }
+    }
+
+    /// Bucket in `0..buckets`, from the same hashes as the counting bloom filters.
+    pub fn bucket(&self, buckets: usize) -> usize {
+        let hash: u32 = match self {
+            Record::Lr(v) | Record::Probe(v) => hash_from_u128(*v, buckets)[0],
+            Record::Lmr(t) => t.hash(buckets)[0],
+        };
+        hash as usize % buckets
+    }
+
+    /// Record written as text: its tuple id (also as a `SEQUENCE_ID=` line of primer3_core input),
+    /// the hex of the value for lr and probe, the bases of the whole record for lr (64) and probe (30),
+    /// or the hex of the 56 record bytes for lmr.
+    pub fn parse_key(kind: RecordKind, text: &str) -> Result<Record, CliError> {
+        let text = text.trim();
+        let text = text.strip_prefix("SEQUENCE_ID=").unwrap_or(text);
+        let invalid = || CliError::Usage(format!("invalid {} key {:?}", kind.name(), text));
+        if let Ok(id) = text.parse::<TupleId>() {
+            return Record::from_tuple_id(kind, &id).ok_or_else(invalid);
+        }
+        let bases = match kind {
+            RecordKind::Lr => LR_LEN,
+            RecordKind::Probe => PROBE_LEN,
+            RecordKind::Lmr => 0,
+        };
+        if bases > 0 && text.len() == bases && text.bytes().all(|b| b"ACGTacgt".contains(&b)) {
+            let v =
+                DnaSequence::new(&text.as_bytes().to_vec()).subsequence_as_u128(vec![[0, bases]]);
+            return Record::from_bytes(kind, &v.to_be_bytes()).map_err(|_| invalid());
+        }
+        match kind {
+            RecordKind::Lr | RecordKind::Probe => {
+                let v = u128::from_str_radix(text, 16).map_err(|_| invalid())?;
+                Record::from_bytes(kind, &v.to_be_bytes()).map_err(|_| invalid())
+            }
+            RecordKind::Lmr => {
+                if text.len() != 2 * LMR_RECORD_SIZE || !text.is_ascii() {
+                    return Err(invalid());
+                }
+                let bytes: Vec<u8> = (0..LMR_RECORD_SIZE)
+                    .map(|i| u8::from_str_radix(&text[2 * i..2 * i + 2], 16))
+                    .collect::<Result<Vec<u8>, _>>()
+                    .map_err(|_| invalid())?;
+                Record::from_bytes(kind, &bytes).map_err(|_| invalid())
+            }
+        }
+    }
+
+    /// Record bytes in hex; `parse_key` reads it back.
+    pub fn hex(&self) -> String {
+        self.to_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    pub fn tuple_id(&self) -> TupleId {
+        match self {
+            Record::Lr(v) => LrTuple(*v).tuple_id(),
+            Record::Probe(v) => ProbeKmer(*v).tuple_id(),
+            Record::Lmr(t) => TupleRecord::tuple_id(t),
+        }
+    }
+
+    /// `None` when `id` names another kind of record.
+    pub fn from_tuple_id(kind: RecordKind, id: &TupleId) -> Option<Record> {
+        match (kind, id) {
+            (RecordKind::Lr, TupleId::Lr(v)) => Some(Record::Lr(*v)),
+            (RecordKind::Probe, TupleId::Probe { value, len }) if *len as usize == PROBE_LEN => {
+                Some(Record::Probe(*value))
+            }
+            (RecordKind::Lmr, _) => LmrTuple::from_tuple_id(id).map(Record::Lmr),
+            _ => None,
+        }
+    }
+
+    /// Name used for FASTA, BLAST queries and primer3_core ids; the tuple id.
+    pub fn name(&self) -> String {
+        self.tuple_id().to_string()
+    }
+
+    /// Decoded bases of the whole record.
+    pub fn sequence(&self) -> String {
+        let bases: Vec<u8> = match self {
+            Record::Lr(v) => LrTuple(*v).sequence(),
+            Record::Probe(v) => ProbeKmer(*v).sequence(),
+            Record::Lmr(t) => t.sequence(),
+        };
+        String::from_utf8(bases).unwrap()
+    }
+
+    /// BLAST queries for the windows, named `NAME-L`, `NAME-M`, `NAME-R` or `NAME-P`.
+    pub fn blast_query(&self) -> String {
+        let name = self.name();
+        self.tuple_id()
+            .windows()
+            .into_iter()
+            .map(|(window, bases)| {
+                format!(
+                    ">{}-{}\n{}\n",
+                    name,
+                    window,
+                    String::from_utf8(bases).unwrap()
+                )
+            })
+            .collect()
+    }
+
+    /// One primer3_core input record, ending with the `=` line.
+    /// `config` is appended to lr-tuples only; probes and lmr-tuples carry their own settings.
+    pub fn primer3_input(&self, config: &str) -> String {
+        match self {
+            Record::Lr(v) => format!(
+                "SEQUENCE_ID={}\nSEQUENCE_TEMPLATE={}{}{}\n{}=\n",
+                self.name(),
+                String::from_utf8(decode_u128_l(v)).unwrap(),
+                "N".repeat(LR_INTERNAL_N),
+                String::from_utf8(decode_u128_r(v)).unwrap(),
+                config
+            ),
+            Record::Probe(_) => format!(
+                "SEQUENCE_ID={}
+SEQUENCE_TEMPLATE={}{}{}{}{}
+PRIMER_TASK=pick_pcr_primers_and_hyb_probe
+PRIMER_OPT_SIZE=27
+PRIMER_MIN_SIZE=15
+PRIMER_MAX_SIZE=31
+PRIMER_PRODUCT_SIZE_RANGE=101-200 201-301
+P3_FILE_FLAG=0
+PRIMER_EXPLAIN_FLAG=1
+PRIMER_OPT_TM=65.0
+PRIMER_MAX_TM=70.0
+=\n",
+                self.name(),
+                PROBE_LEFT_FLANK,
+                "N".repeat(PROBE_INTERNAL_N),
+                self.sequence(),
+                "N".repeat(PROBE_INTERNAL_N),
+                PROBE_RIGHT_FLANK
+            ),
+            // L, M, Rの間はNで埋め、Lの先頭からの距離を保つ。
+            // MはSEQUENCE_INTERNAL_OLIGOとして渡し、左右のプライマーはそれぞれL, Rの範囲から選ばせる。
+            Record::Lmr(t) => {
+                let (l, m, r) = t.decode_as_triple_vec();
+                let lm_gap: usize = t.m_offset as usize - l.len();
+                let mr_gap: usize = t.r_offset as usize - t.m_offset as usize - m.len();
+                let m_str = String::from_utf8(m).unwrap();
+                format!(
+                    "SEQUENCE_ID={}
+SEQUENCE_TEMPLATE={}{}{}{}{}
+SEQUENCE_INTERNAL_OLIGO={}
+SEQUENCE_PRIMER_PAIR_OK_REGION_LIST=0,{},{},{}
+PRIMER_TASK=generic
+PRIMER_PICK_LEFT_PRIMER=1
+PRIMER_PICK_INTERNAL_OLIGO=1
+PRIMER_PICK_RIGHT_PRIMER=1
+PRIMER_OPT_SIZE=27
+PRIMER_MIN_SIZE=15
+PRIMER_MAX_SIZE=31
+PRIMER_PRODUCT_SIZE_RANGE=1-{}
+P3_FILE_FLAG=0
+PRIMER_EXPLAIN_FLAG=1
+PRIMER_OPT_TM=65.0
+PRIMER_MAX_TM=70.0
+=\n",
+                    self.name(),
+                    String::from_utf8_lossy(&l),
+                    "N".repeat(lm_gap),
+                    m_str,
+                    "N".repeat(mr_gap),
+                    String::from_utf8_lossy(&r),
+                    m_str,
+                    l.len(),
+                    t.r_offset,
+                    r.len(),
+                    t.product_len()
+                )
+            }
+        }
+    }
+}
+
+/// Every record of a binary file; a file that ends inside a record is an error.
+pub fn read_records<R: Read>(r: &mut R, kind: RecordKind) -> Result<Vec<Record>, CliError> {
+    let mut bytes: Vec<u8> = Vec::new();
+    r.read_to_end(&mut bytes)?;
+    if !bytes.len().is_multiple_of(kind.record_size()) {
+        return Err(CliError::Failure(format!(
+            "{} bytes is not a whole number of {} records ({} bytes each)",
+            bytes.len(),
+            kind.name(),
+            kind.record_size()
+        )));
+    }
+    bytes
+        .chunks_exact(kind.record_size())
+        .map(|chunk| Record::from_bytes(kind, chunk))
+        .collect()
+}
+
+/// Bytes of the count following each record in a file with counts.
+pub const COUNT_SIZE: usize = 4;
+
+/// Reads records one at a time, so a file of any size is read in constant memory.
+/// With counts, every record is followed by a big endian u32 count; otherwise each record counts once.
+pub struct RecordReader<R: Read> {
+    reader: BufReader<R>,
+    kind: RecordKind,
+    counted: bool,
+}
+
+impl<R: Read> RecordReader<R> {
+    pub fn new(reader: R, kind: RecordKind) -> RecordReader<R> {
+        RecordReader {
+            reader: BufReader::new(reader),
+            kind,
+            counted: false,
+        }
+    }
+
+    pub fn with_counts(reader: R, kind: RecordKind) -> RecordReader<R> {
+        RecordReader {
+            counted: true,
+            ..RecordReader::new(reader, kind)
+        }
+    }
+
+    pub fn next_counted(&mut self) -> Option<Result<(Record, u32), CliError>> {
+        let record_size = self.kind.record_size();
+        let mut buffer = vec![0u8; record_size + if self.counted { COUNT_SIZE } else { 0 }];
+        let mut filled: usize = 0;
+        while filled < buffer.len() {
+            match self.reader.read(&mut buffer[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+        if filled == 0 {
+            return None;
+        }
+        if filled < buffer.len() {
+            return Some(Err(CliError::Failure(format!(
+                "file ends inside a {} record ({} of {} bytes)",
+                self.kind.name(),
+                filled,
+                buffer.len()
+            ))));
+        }
+        let count: u32 = if self.counted {
+            u32::from_be_bytes(buffer[record_size..].try_into().unwrap())
+        } else {
+            1
+        };
+        Some(Record::from_bytes(self.kind, &buffer[..record_size]).map(|record| (record, count)))
+    }
+}
+
+impl<R: Read> Iterator for RecordReader<R> {
+    type Item = Result<Record, CliError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_counted().map(|r| r.map(|(record, _)| record))
+    }
+}
+
+/// Number of records in a binary file, from its size. With counts, every
+/// record takes `COUNT_SIZE` more bytes.
+pub fn record_count(path: &str, kind: RecordKind, counted: bool) -> Result<usize, CliError> {
+    let stride = kind.record_size() + if counted { COUNT_SIZE } else { 0 };
+    let bytes = std::fs::metadata(path)
+        .map_err(|e| CliError::Failure(format!("{}: {}", path, e)))?
+        .len() as usize;
+    if !bytes.is_multiple_of(stride) {
+        return Err(CliError::Failure(format!(
+            "{}: {} bytes is not a whole number of {} records ({} bytes each{})",
+            path,
+            bytes,
+            kind.name(),
+            stride,
+            if counted { " with counts" } else { "" }
+        )));
+    }
+    Ok(bytes / stride)
+}
+
+pub fn read_record_file(path: &str, kind: RecordKind) -> Result<Vec<Record>, CliError> {
+    let mut file = crate::cli::open(path)?;
+    read_records(&mut file, kind).map_err(|e| CliError::Failure(format!("{}: {}", path, e)))
+}
+
+/// Adds `-k/--kind`, shared by the commands reading binary records.
+pub fn kind_option(opts: &mut Options) {
+    opts.optopt(
+        "k",
+        "kind",
+        "record kind: lr (search_primer, default), probe (search_probe) or lmr (search_primer_and_probe)",
+        "KIND",
+    );
+}
+
+pub fn record_kind(matches: &Matches) -> Result<RecordKind, CliError> {
+    RecordKind::parse(&matches.opt_str("k").unwrap_or_else(|| "lr".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::records::{read_records, Record, RecordKind};
+    use ::function_name::named;
+    use search_primer_and_probe::sequence_encoder_util::LmrTuple;
+
+    #[test]
+    #[named]
+    fn record_round_trip_test() {
+        let lr = Record::Lr(0x1b);
+        let lmr = Record::Lmr(LmrTuple::with_layout(0x1b, 2, 3, [4, 2, 3], 10, 20));
+        let mut bytes: Vec<u8> = lr.to_bytes();
+        bytes.extend(Record::Lr(5).to_bytes());
+        let records = read_records(&mut bytes.as_slice(), RecordKind::Lr).unwrap();
+        assert!(
+            records == vec![lr, Record::Lr(5)]
+                && read_records(&mut &bytes[..20], RecordKind::Lr).is_err()
+                && read_records(&mut lmr.to_bytes().as_slice(), RecordKind::Lmr).unwrap()
+                    == vec![lmr]
+                && read_records(&mut [0xffu8; 56].as_slice(), RecordKind::Lmr).is_err()
+                && Record::parse_key(RecordKind::Lmr, &"ff".repeat(56)).is_err(),
+            "{} failed",
+            function_name!()
+        );
+        assert!(
+            lr.sequence() == format!("{}CGT", "A".repeat(61))
+                && lr.name() == format!("lr_32.32_{:032x}", 0x1b)
+                && lr.blast_query()
+                    == format!(
+                        ">{}-L\n{}\n>{}-R\n{}CGT\n",
+                        lr.name(),
+                        "A".repeat(32),
+                        lr.name(),
+                        "A".repeat(29)
+                    ),
+            "{} failed: {}",
+            function_name!(),
+            lr.blast_query()
+        );
+        let input = lmr.primer3_input("");
+        assert!(
+            lmr.sequence() == "ACGTAGAAT"
+                && lmr.blast_query()
+                    == format!(
+                        ">{}-L\nACGT\n>{}-M\nAG\n>{}-R\nAAT\n",
+                        lmr.name(),
+                        lmr.name(),
+                        lmr.name()
+                    )
+                && input.contains("SEQUENCE_TEMPLATE=ACGTNNNNNNAGNNNNNNNNAAT\n")
+                && input.contains("SEQUENCE_PRIMER_PAIR_OK_REGION_LIST=0,4,20,3\n")
+                && input.ends_with("=\n"),
+            "{} failed: {}",
+            function_name!(),
+            input
+        );
+        let rc = lmr.reverse_complement();
+        assert!(
+            rc.sequence() == "ATTCTACGT"
+                && rc
+                    .primer3_input("")
+                    .contains("SEQUENCE_TEMPLATE=ATTNNNNNNNNCTNNNNNNACGT\n")
+                && rc.reverse_complement() == lmr
+                && lr.reverse_complement().sequence() == format!("ACG{}", "T".repeat(61))
+                && Record::Probe(0).reverse_complement().sequence() == "T".repeat(30),
+            "{} failed: {}",
+            function_name!(),
+            rc.name()
+        );
+    }
+
+    #[test]
+    #[named]
+    fn parse_key_test() {
+        let lr = Record::Lr(0x1b);
+        let lmr = Record::Lmr(LmrTuple::with_layout(0x1b, 2, 3, [4, 2, 3], 10, 20));
+        let parsed: Vec<Record> = [
+            "1b",
+            "SEQUENCE_ID=1b",
+            &format!("{}CGT", "A".repeat(61)),
+            &format!("{}CGT", "a".repeat(61)),
+        ]
+        .iter()
+        .map(|text| Record::parse_key(RecordKind::Lr, text).unwrap())
+        .collect();
+        assert!(
+            parsed.iter().all(|&r| r == lr)
+                && Record::parse_key(RecordKind::Probe, &format!("{}CA", "A".repeat(28))).unwrap()
+                    == Record::Probe(4)
+                && Record::parse_key(RecordKind::Lmr, &lmr.hex()).unwrap() == lmr
+                && Record::parse_key(RecordKind::Lr, "xyz").is_err()
+                && Record::parse_key(RecordKind::Lmr, &lmr.name()).unwrap() == lmr
+                && Record::parse_key(RecordKind::Lr, &format!("SEQUENCE_ID={}", lr.name()))
+                    .unwrap()
+                    == lr
+                && Record::parse_key(RecordKind::Probe, &lr.name()).is_err()
+                && Record::parse_key(RecordKind::Lmr, "1b").is_err(),
+            "{} failed",
+            function_name!()
+        );
+    }
+}
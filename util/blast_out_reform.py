@@ -48,12 +48,19 @@ def blast_checker_1(primer_pairs, records):
 	return discarded_primer_pairs
 
 def blast_checker_2(records):
-	return set([x.qseqid for x in records])
+	return set([candidate_name(x.qseqid.rsplit("-", 1)[0]) for x in records])
+
+def candidate_name(name):
+	# 古い版の名前(u128の16進数)はlr-tupleの識別子に直す
+	name = name.strip()
+	if name != "" and all(c in "0123456789abcdefABCDEF" for c in name):
+		return "lr_32.32_{:032x}".format(int(name, 16))
+	return name
 
 def main():
 	parser = argparse.ArgumentParser(description = "blastn-short result reformer")
 	parser.add_argument("-b", "--blast_result", required=True, nargs="+", type=str, metavar="Blast result", help="Blast results file (outfmt 6)")
-	parser.add_argument("-p", "--primer_candidates",required=True, type=str, metavar="Primer candidates", help="Primer candidate name list file. One candidate in u128 in a line.")
+	parser.add_argument("-p", "--primer_candidates",required=True, type=str, metavar="Primer candidates", help="Primer candidate name list file (swordfish blast -n). One tuple id, or u128 in hex from older versions, in a line.")
 	args = parser.parse_args()
 	filenames = args.blast_result
 	discarded_primer_pairs = set()
@@ -61,15 +68,16 @@ def main():
 	primer_candidates_set = set()
 	with open(args.primer_candidates) as f:
 		for line in f:
-			primer_candidates_set.add(int(line, 16))
+			if line.strip() != "":
+				primer_candidates_set.add(candidate_name(line))
 
 	for each_db in filenames:
 		records = []
 		with open(each_db) as f:
 			for line in f:
 				elm = line.strip().split("\t")
-				seqid = int(elm[0].split("-")[0], 16)
-				role = elm[0].split("-")[1]
+				seqid = candidate_name(elm[0].rsplit("-", 1)[0])
+				role = elm[0].rsplit("-", 1)[1]
 				qseqid, sseqid, sacc, slen, qstart, qend, sstart, send, qseq, sseq, evalue, length, staxid, staxids, ssciname, scomname = elm
 				qseqid   = str(qseqid)
 				sseqid   = str(sseqid)
@@ -101,8 +109,8 @@ def main():
 	#print(f"discarded:  {len(discarded_primer_pairs)}", file = sys.stderr)
 	#print(f"remained:   {len(remained)}", file = sys.stderr)
 
-	for i in remained:
-		print(hex(i).replace("0x", ""))
+	for i in sorted(remained):
+		print(i)
 	# for i in primer_candidates_set - discarded_primer_pairs:
 	# 	print(f'{hex(i).replace("0x", "")}')
 	# print(f"input file: {filename}", file = sys.stderr)